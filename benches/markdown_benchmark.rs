@@ -3,11 +3,15 @@
 
 #![allow(missing_docs)]
 
-use comrak::ComrakOptions;
+use comrak::{markdown_to_html, ComrakOptions};
 use criterion::{
     black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
     Throughput,
 };
+use mdx_gen::extensions::{
+    process_custom_blocks_with_options, process_github_alerts,
+    process_tables, process_tables_with_options, CustomBlockOptions,
+};
 use mdx_gen::{process_markdown, MarkdownOptions};
 
 /// Create a valid MarkdownOptions configuration
@@ -103,5 +107,82 @@ fn markdown_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, markdown_benchmark);
+/// Benchmark the enhanced table pass on its own, to track the cost of
+/// each call independently of Markdown parsing.
+fn table_benchmark(c: &mut Criterion) {
+    let row = r#"<tr><td>Cell</td><td align="center">Cell</td><td align="right">Cell</td></tr>"#;
+    let table_html = format!(
+        "<table><thead><tr><th>A</th><th align=\"center\">B</th><th align=\"right\">C</th></tr></thead><tbody>{}</tbody></table>",
+        row.repeat(50)
+    );
+
+    c.bench_function("process_tables", |b| {
+        b.iter(|| process_tables(black_box(&table_html)));
+    });
+}
+
+/// Benchmark highlighting a document that repeats the same code block,
+/// which only needs highlighting once per call.
+fn repeated_code_block_benchmark(c: &mut Criterion) {
+    let markdown = "```rust\nuse std::collections::HashMap;\nuse std::io::{self, Write};\n```\n\n".repeat(50);
+    let options = create_valid_options(true, false, false, false);
+
+    c.bench_function("repeated_code_blocks", |b| {
+        b.iter(|| {
+            process_markdown(black_box(&markdown), black_box(&options))
+                .expect("Highlighting should not fail")
+        });
+    });
+}
+
+/// Benchmark the custom block, GitHub alert and table passes on plain
+/// prose, which contains none of them. With the same options, the passes
+/// run unconditionally and behind the marker checks `process_markdown`
+/// uses to skip them.
+fn prose_benchmark(c: &mut Criterion) {
+    let markdown = "## Section\n\nThis paragraph has **bold**, *italic* and a [link](https://example.com). It goes on for a while so the passes have text to scan.\n\n- One\n- Two\n\n".repeat(200);
+    let options = create_valid_options(true, true, true, true)
+        .with_github_alerts(true);
+    let html = markdown_to_html(&markdown, &options.comrak_options);
+    let block_options = CustomBlockOptions::default();
+    let table_options = options.table_options();
+    let run_passes = |guarded: bool| {
+        let mut html = html.clone();
+        if !guarded || html.contains("<div") {
+            html = process_custom_blocks_with_options(
+                &html,
+                &options.custom_block_registry,
+                &block_options,
+            );
+        }
+        if !guarded || html.contains("<blockquote>") {
+            html = process_github_alerts(
+                &html,
+                &options.custom_block_registry,
+                &block_options,
+            );
+        }
+        if !guarded || html.contains("<t") || html.contains("<T") {
+            html = process_tables_with_options(&html, &table_options);
+        }
+        html
+    };
+
+    let mut group = c.benchmark_group("Plain prose passes");
+    group.throughput(Throughput::Bytes(html.len() as u64));
+    for (name, guarded) in [("unguarded", false), ("guarded", true)] {
+        group.bench_function(name, |b| {
+            b.iter(|| run_passes(black_box(guarded)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    markdown_benchmark,
+    table_benchmark,
+    repeated_code_block_benchmark,
+    prose_benchmark
+);
 criterion_main!(benches);
@@ -120,6 +120,7 @@ fn column_alignment_example() -> Result<(), Box<dyn std::error::Error>>
         ColumnAlignment::Left,
         ColumnAlignment::Center,
         ColumnAlignment::Right,
+        ColumnAlignment::Justify,
     ];
 
     for alignment in alignments {
@@ -148,6 +149,10 @@ fn custom_block_type_example() -> Result<(), Box<dyn std::error::Error>>
         CustomBlockType::Info,
         CustomBlockType::Important,
         CustomBlockType::Caution,
+        CustomBlockType::Danger,
+        CustomBlockType::Success,
+        CustomBlockType::Primary,
+        CustomBlockType::Secondary,
     ];
 
     for block_type in block_types {
@@ -0,0 +1,334 @@
+//! Structured output for downstream tooling.
+//!
+//! This module converts Markdown into a JSON tree built from Comrak's
+//! syntax tree, as an alternative to the HTML produced by
+//! [`process_markdown`](crate::process_markdown).
+
+use crate::error::MarkdownError;
+use crate::extensions::{custom_block_type, table_caption_index};
+use crate::markdown::{
+    check_input, comrak_options_for, pre_process, prepare_for_comrak,
+    MarkdownOptions,
+};
+use comrak::nodes::{AstNode, ListType, NodeValue, TableAlignment};
+use comrak::{parse_document, Arena};
+use serde_json::{json, Map, Value};
+
+/// Processes the input Markdown content into a JSON syntax tree.
+///
+/// Every node is an object with a `type` (e.g. `"heading"`,
+/// `"paragraph"`, `"code_block"`, `"table"`) and, when it has any, a
+/// `children` array. Nodes carry extra fields depending on their type:
+///
+/// - `heading`: `level` and the plain `text` of the heading;
+/// - `code_block`: `language` (`null` when absent), `info` and `code`;
+/// - `table`: `alignments`; `table_row`: `header`;
+/// - `list`: `ordered` and `start`; `task_item`: `checked`;
+/// - `link` and `image`: `url` and `title`;
+/// - `text`, `code` and `math`: `value`;
+/// - `html_block` and `html_inline`: `html`;
+/// - `table_caption`: the `text` of a `[Table: ...]` caption, just
+///   before its table.
+///
+/// An HTML block opening a registered custom block becomes a
+/// `custom_block` node with `block_type`, `title` and `html` fields. When
+/// the block's content is Markdown, that content follows as sibling
+/// nodes.
+///
+/// The Markdown goes through the same passes, and is parsed with the
+/// same Comrak options, as in [`process_markdown`](crate::process_markdown):
+/// the [pre-processor](MarkdownOptions::with_pre_processor), includes,
+/// fenced and multi-paragraph custom blocks and table captions are
+/// handled when enabled, and e.g. `$...$` math is parsed when a math
+/// renderer is set. None of the HTML post-processing passes run.
+///
+/// # Errors
+///
+/// Returns `MarkdownError::InvalidOptionsError` if the options are
+/// inconsistent, `MarkdownError::ParseError` if the input, or the input
+/// with its includes expanded, exceeds `max_input_bytes`, and
+/// `MarkdownError::IncludeError` or `MarkdownError::IoError` if an
+/// include cannot be resolved or read.
+pub fn process_markdown_to_ast(
+    content: &str,
+    options: &MarkdownOptions,
+) -> Result<Value, MarkdownError> {
+    check_input(content, options)?;
+
+    let content = pre_process(content, options)?;
+    let (content, captions) = prepare_for_comrak(content, options);
+
+    let arena = Arena::new();
+    let root =
+        parse_document(&arena, &content, &comrak_options_for(options));
+    Ok(node_to_json(root, options, &captions))
+}
+
+/// Converts a node and its descendants to JSON, replacing the
+/// placeholders of the table `captions` with `table_caption` nodes.
+fn node_to_json<'a>(
+    node: &'a AstNode<'a>,
+    options: &MarkdownOptions,
+    captions: &[String],
+) -> Value {
+    let mut object = Map::new();
+    let node_type = match &node.data.borrow().value {
+        NodeValue::Document => "document",
+        NodeValue::FrontMatter(_) => "front_matter",
+        NodeValue::BlockQuote | NodeValue::MultilineBlockQuote(_) => {
+            "block_quote"
+        }
+        NodeValue::List(list) => {
+            let ordered = list.list_type == ListType::Ordered;
+            object.insert("ordered".into(), json!(ordered));
+            object.insert(
+                "start".into(),
+                if ordered {
+                    json!(list.start)
+                } else {
+                    Value::Null
+                },
+            );
+            "list"
+        }
+        NodeValue::Item(_) => "item",
+        NodeValue::TaskItem(checked) => {
+            object.insert("checked".into(), json!(checked.is_some()));
+            "task_item"
+        }
+        NodeValue::DescriptionList => "description_list",
+        NodeValue::DescriptionItem(_) => "description_item",
+        NodeValue::DescriptionTerm => "description_term",
+        NodeValue::DescriptionDetails => "description_details",
+        NodeValue::CodeBlock(code_block) => {
+            let language = code_block.info.split_whitespace().next();
+            object.insert("language".into(), json!(language));
+            object.insert("info".into(), json!(code_block.info));
+            object.insert("code".into(), json!(code_block.literal));
+            "code_block"
+        }
+        NodeValue::HtmlBlock(html_block) => {
+            let caption = table_caption_index(&html_block.literal)
+                .and_then(|index| captions.get(index));
+            let custom_block = if options.enable_custom_blocks
+                && options.allow_raw_html
+            {
+                custom_block_type(
+                    &html_block.literal,
+                    &options.custom_block_registry,
+                )
+            } else {
+                None
+            };
+            match (caption, custom_block) {
+                (Some(caption), _) => {
+                    object.insert("text".into(), json!(caption));
+                    "table_caption"
+                }
+                (None, Some((block_type, title))) => {
+                    object.insert(
+                        "html".into(),
+                        json!(html_block.literal),
+                    );
+                    object
+                        .insert("block_type".into(), json!(block_type));
+                    object.insert("title".into(), json!(title));
+                    "custom_block"
+                }
+                (None, None) => {
+                    object.insert(
+                        "html".into(),
+                        json!(html_block.literal),
+                    );
+                    "html_block"
+                }
+            }
+        }
+        NodeValue::Paragraph => "paragraph",
+        NodeValue::Heading(heading) => {
+            object.insert("level".into(), json!(heading.level));
+            object.insert("text".into(), json!(collect_text(node)));
+            "heading"
+        }
+        NodeValue::ThematicBreak => "thematic_break",
+        NodeValue::FootnoteDefinition(_) => "footnote_definition",
+        NodeValue::Table(table) => {
+            let alignments: Vec<&str> = table
+                .alignments
+                .iter()
+                .map(|alignment| match alignment {
+                    TableAlignment::Left => "left",
+                    TableAlignment::Center => "center",
+                    TableAlignment::Right => "right",
+                    TableAlignment::None => "none",
+                })
+                .collect();
+            object.insert("alignments".into(), json!(alignments));
+            "table"
+        }
+        NodeValue::TableRow(header) => {
+            object.insert("header".into(), json!(header));
+            "table_row"
+        }
+        NodeValue::TableCell => "table_cell",
+        NodeValue::Text(text) => {
+            object.insert("value".into(), json!(text));
+            "text"
+        }
+        NodeValue::SoftBreak => "soft_break",
+        NodeValue::LineBreak => "line_break",
+        NodeValue::Code(code) => {
+            object.insert("value".into(), json!(code.literal));
+            "code"
+        }
+        NodeValue::HtmlInline(html) => {
+            object.insert("html".into(), json!(html));
+            "html_inline"
+        }
+        NodeValue::Emph => "emph",
+        NodeValue::Strong => "strong",
+        NodeValue::Strikethrough => "strikethrough",
+        NodeValue::Link(link) => {
+            object.insert("url".into(), json!(link.url));
+            object.insert("title".into(), json!(link.title));
+            "link"
+        }
+        NodeValue::Image(link) => {
+            object.insert("url".into(), json!(link.url));
+            object.insert("title".into(), json!(link.title));
+            "image"
+        }
+        NodeValue::FootnoteReference(_) => "footnote_reference",
+        NodeValue::Math(math) => {
+            object.insert("value".into(), json!(math.literal));
+            object.insert("display".into(), json!(math.display_math));
+            "math"
+        }
+        _ => "other",
+    };
+    object.insert("type".into(), json!(node_type));
+
+    let children: Vec<Value> = node
+        .children()
+        .map(|child| node_to_json(child, options, captions))
+        .collect();
+    if !children.is_empty() {
+        object.insert("children".into(), Value::Array(children));
+    }
+    Value::Object(object)
+}
+
+/// Returns the plain text of a node's descendants.
+fn collect_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for descendant in node.descendants() {
+        match &descendant.data.borrow().value {
+            NodeValue::Text(value) => text.push_str(value),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => {
+                text.push(' ')
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::default_markdown_options;
+
+    #[test]
+    fn test_process_markdown_to_ast() {
+        let markdown = "# Hello `world`\n\n```rust title=\"main.rs\"\nfn main() {}\n```\n";
+        let ast = process_markdown_to_ast(
+            markdown,
+            &default_markdown_options(),
+        )
+        .unwrap();
+
+        assert_eq!(ast["type"], "document");
+        let heading = &ast["children"][0];
+        assert_eq!(heading["type"], "heading");
+        assert_eq!(heading["level"], 1);
+        assert_eq!(heading["text"], "Hello world");
+
+        let code_block = &ast["children"][1];
+        assert_eq!(code_block["type"], "code_block");
+        assert_eq!(code_block["language"], "rust");
+        assert_eq!(code_block["info"], "rust title=\"main.rs\"");
+        assert_eq!(code_block["code"], "fn main() {}\n");
+        assert!(code_block.get("children").is_none());
+    }
+
+    #[test]
+    fn test_ast_tables_and_custom_blocks() {
+        let markdown = "| A | B |\n|:--|--:|\n| 1 | 2 |\n\n<div class=\"note\" data-title=\"Heads up\">Careful.</div>\n\n```\nplain\n```\n";
+        let ast = process_markdown_to_ast(
+            markdown,
+            &default_markdown_options(),
+        )
+        .unwrap();
+
+        let table = &ast["children"][0];
+        assert_eq!(table["type"], "table");
+        assert_eq!(table["alignments"], json!(["left", "right"]));
+        assert_eq!(table["children"][0]["header"], true);
+
+        let block = &ast["children"][1];
+        assert_eq!(block["type"], "custom_block");
+        assert_eq!(block["block_type"], "note");
+        assert_eq!(block["title"], "Heads up");
+
+        assert_eq!(ast["children"][2]["language"], Value::Null);
+    }
+
+    #[test]
+    fn test_ast_math() {
+        let markdown = "Euler: $e^{i\\pi} = -1$\n";
+        let ast = process_markdown_to_ast(
+            markdown,
+            &default_markdown_options(),
+        )
+        .unwrap();
+        assert_eq!(
+            ast["children"][0]["children"].as_array().unwrap().len(),
+            1
+        );
+
+        let options = default_markdown_options()
+            .with_math(crate::extensions::MathRenderer::KaTeX);
+        let ast = process_markdown_to_ast(markdown, &options).unwrap();
+        let math = &ast["children"][0]["children"][1];
+        assert_eq!(math["type"], "math");
+        assert_eq!(math["value"], "e^{i\\pi} = -1");
+        assert_eq!(math["display"], false);
+    }
+
+    #[test]
+    fn test_ast_matches_rendered_markdown() {
+        let markdown = "<div class=\"note\">One.\n\nTwo.</div>\n\n[Table: Totals]\n| A |\n|---|\n| 1 |\n";
+        let options =
+            default_markdown_options().with_table_captions(true);
+        let ast = process_markdown_to_ast(markdown, &options).unwrap();
+        let types: Vec<&str> = ast["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| node["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "custom_block",
+                "paragraph",
+                "paragraph",
+                "html_block",
+                "table_caption",
+                "table"
+            ]
+        );
+        assert_eq!(ast["children"][4]["text"], "Totals");
+    }
+}
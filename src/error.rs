@@ -28,6 +28,41 @@ pub enum MarkdownError {
     /// An error occurred while loading a syntax set.
     #[error("Failed to load syntax set: {0}")]
     SyntaxSetError(String),
+
+    /// An error occurred while parsing a document's frontmatter.
+    #[error("Failed to parse frontmatter: {0}")]
+    FrontmatterError(String),
+
+    /// An `{{include:...}}` directive is circular or nested too deeply.
+    #[error("Failed to include file: {0}")]
+    IncludeError(String),
+
+    /// An I/O error occurred while reading input or writing output.
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl MarkdownError {
+    /// Returns whether the error was caused by the input being
+    /// processed, rather than by the configuration or environment.
+    ///
+    /// Recoverable errors may go away with different input or with a
+    /// feature disabled, e.g. by retrying without syntax highlighting.
+    /// Invalid options, syntax sets that fail to load and I/O errors
+    /// will fail again on retry.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            MarkdownError::ParseError(_)
+            | MarkdownError::ConversionError(_)
+            | MarkdownError::CustomBlockError(_)
+            | MarkdownError::SyntaxHighlightError(_)
+            | MarkdownError::FrontmatterError(_)
+            | MarkdownError::IncludeError(_) => true,
+            MarkdownError::InvalidOptionsError(_)
+            | MarkdownError::SyntaxSetError(_)
+            | MarkdownError::IoError(_) => false,
+        }
+    }
 }
 
 /// A helper function that adds context to errors occurring during Markdown processing.
@@ -76,6 +111,52 @@ fn test_successful_parse() {
         assert_eq!(result.unwrap(), "Parsed markdown content");
     }
 
+    #[test]
+    fn test_io_error_with_context() {
+        fn read() -> Result<String> {
+            let err = std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "access denied",
+            );
+            Err(MarkdownError::from(err))
+                .with_context(|| "Failed while reading markdown file")
+        }
+
+        let err = read().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed while reading markdown file"
+        );
+        let source = err.downcast_ref::<MarkdownError>().unwrap();
+        assert!(matches!(source, MarkdownError::IoError(_)));
+        assert_eq!(source.to_string(), "I/O error: access denied");
+    }
+
+    #[test]
+    fn test_is_recoverable() {
+        let message = || "error".to_string();
+        for error in [
+            MarkdownError::ParseError(message()),
+            MarkdownError::ConversionError(message()),
+            MarkdownError::CustomBlockError(message()),
+            MarkdownError::SyntaxHighlightError(message()),
+            MarkdownError::FrontmatterError(message()),
+            MarkdownError::IncludeError(message()),
+        ] {
+            assert!(error.is_recoverable(), "{:?}", error);
+        }
+        for error in [
+            MarkdownError::InvalidOptionsError(message()),
+            MarkdownError::SyntaxSetError(message()),
+            MarkdownError::IoError(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "missing",
+            )),
+        ] {
+            assert!(!error.is_recoverable(), "{:?}", error);
+        }
+    }
+
     #[test]
     fn test_parse_markdown_with_context() {
         let result = parse_markdown_with_context("");
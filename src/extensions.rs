@@ -6,12 +6,79 @@
 use crate::error::MarkdownError;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
+#[cfg(feature = "syntax_highlighting")]
 use syntect::{
-    highlighting::ThemeSet, html::highlighted_html_for_string,
-    parsing::SyntaxSet,
+    easy::HighlightLines,
+    highlighting::{Color, Theme, ThemeSet},
+    html::{
+        highlighted_html_for_string, styled_line_to_highlighted_html,
+        IncludeBackground,
+    },
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
 };
 
+/// The name of the theme used when no custom theme is configured.
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// The themes bundled with the syntax highlighter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxTheme {
+    /// `base16-ocean.dark`, the [`DEFAULT_THEME`].
+    Base16OceanDark,
+    /// `base16-ocean.light`.
+    Base16OceanLight,
+    /// `InspiredGitHub`.
+    InspiredGitHub,
+    /// `Solarized (dark)`.
+    SolarizedDark,
+    /// `Solarized (light)`.
+    SolarizedLight,
+}
+
+impl SyntaxTheme {
+    /// Returns the name of this theme in the bundled theme set.
+    pub fn as_name(&self) -> &'static str {
+        match self {
+            SyntaxTheme::Base16OceanDark => DEFAULT_THEME,
+            SyntaxTheme::Base16OceanLight => "base16-ocean.light",
+            SyntaxTheme::InspiredGitHub => "InspiredGitHub",
+            SyntaxTheme::SolarizedDark => "Solarized (dark)",
+            SyntaxTheme::SolarizedLight => "Solarized (light)",
+        }
+    }
+}
+
+/// Built-in language aliases, mapping a code block token to a syntax name.
+///
+/// These are only consulted when the token does not resolve on its own.
+pub const DEFAULT_LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("js", "JavaScript"),
+    ("jsx", "JavaScript"),
+    ("ts", "TypeScript"),
+    ("typescript", "TypeScript"),
+    ("yml", "YAML"),
+    ("sh", "Bourne Again Shell (bash)"),
+    ("shell", "Bourne Again Shell (bash)"),
+    ("console", "Bourne Again Shell (bash)"),
+    ("golang", "Go"),
+    ("c++", "C++"),
+    ("cpp", "C++"),
+    ("c#", "C#"),
+    ("cs", "C#"),
+    ("csharp", "C#"),
+    // F# is not bundled; these resolve with a custom syntax set that has it
+    ("f#", "F#"),
+    ("fsharp", "F#"),
+];
+
+#[cfg(feature = "syntax_highlighting")]
 lazy_static! {
     /// Cached `SyntaxSet` to avoid reloading on every function call.
     static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
@@ -28,10 +95,91 @@ pub enum ColumnAlignment {
     Center,
     /// Align the column to the right.
     Right,
+    /// Justify the column's text. Comrak never emits this alignment;
+    /// it applies to cells carrying `align="justify"`.
+    Justify,
+}
+
+lazy_static! {
+    static ref ALIGN_ATTR_REGEX: Regex = Regex::new(
+        r#"(?i)(?:^|\s)align\s*=\s*["']?(left|center|right|justify)\b"#
+    )
+    .unwrap();
+}
+
+impl ColumnAlignment {
+    /// Reads the alignment from a cell's attributes, such as
+    /// ` align="center"`. Defaults to `Left` when no alignment is set.
+    pub fn from_html_attr(attrs: &str) -> ColumnAlignment {
+        match ALIGN_ATTR_REGEX
+            .captures(attrs)
+            .map(|caps| caps[1].to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("center") => ColumnAlignment::Center,
+            Some("right") => ColumnAlignment::Right,
+            Some("justify") => ColumnAlignment::Justify,
+            _ => ColumnAlignment::Left,
+        }
+    }
+
+    /// Returns the value of the `align` attribute for this alignment,
+    /// e.g. `"right"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnAlignment::Left => "left",
+            ColumnAlignment::Center => "center",
+            ColumnAlignment::Right => "right",
+            ColumnAlignment::Justify => "justify",
+        }
+    }
+
+    /// Returns the CSS class used for cells with this alignment.
+    pub fn to_css_class(&self) -> &'static str {
+        match self {
+            ColumnAlignment::Left => "text-left",
+            ColumnAlignment::Center => "text-center",
+            ColumnAlignment::Right => "text-right",
+            ColumnAlignment::Justify => "text-justify",
+        }
+    }
+}
+
+/// The text direction of generated tables and custom blocks, emitted as
+/// a `dir` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Left to right (`dir="ltr"`).
+    Ltr,
+    /// Right to left (`dir="rtl"`), e.g. for Arabic or Hebrew.
+    Rtl,
+    /// Let the browser decide from the content (`dir="auto"`).
+    Auto,
+}
+
+impl Direction {
+    /// Returns the value of the `dir` attribute for this direction.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Ltr => "ltr",
+            Direction::Rtl => "rtl",
+            Direction::Auto => "auto",
+        }
+    }
+}
+
+/// Formats an optional direction as a ` dir="..."` attribute.
+fn dir_attr(direction: Option<Direction>) -> String {
+    direction.map_or_else(String::new, |direction| {
+        format!(r#" dir="{}""#, direction.as_str())
+    })
 }
 
 /// Represents different types of custom blocks.
+///
+/// More block types may be added in future releases.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
 pub enum CustomBlockType {
     /// A note block.
     Note,
@@ -45,9 +193,34 @@ pub enum CustomBlockType {
     Important,
     /// A caution block.
     Caution,
+    /// A danger block, matching Bootstrap's `alert-danger`.
+    Danger,
+    /// A success block, matching Bootstrap's `alert-success`.
+    Success,
+    /// A primary block, matching Bootstrap's `alert-primary`.
+    Primary,
+    /// A secondary block, matching Bootstrap's `alert-secondary`.
+    Secondary,
 }
 
 impl CustomBlockType {
+    /// Returns the lowercase keyword for the block type, e.g. `note`. This
+    /// is the inverse of `from_str`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomBlockType::Note => "note",
+            CustomBlockType::Warning => "warning",
+            CustomBlockType::Tip => "tip",
+            CustomBlockType::Info => "info",
+            CustomBlockType::Important => "important",
+            CustomBlockType::Caution => "caution",
+            CustomBlockType::Danger => "danger",
+            CustomBlockType::Success => "success",
+            CustomBlockType::Primary => "primary",
+            CustomBlockType::Secondary => "secondary",
+        }
+    }
+
     /// Returns the appropriate Bootstrap alert class for the custom block type.
     pub fn get_alert_class(&self) -> &'static str {
         match self {
@@ -57,6 +230,43 @@ pub fn get_alert_class(&self) -> &'static str {
             CustomBlockType::Info => "alert-primary",
             CustomBlockType::Important => "alert-danger",
             CustomBlockType::Caution => "alert-secondary",
+            CustomBlockType::Danger => "alert-danger",
+            CustomBlockType::Success => "alert-success",
+            CustomBlockType::Primary => "alert-primary",
+            CustomBlockType::Secondary => "alert-secondary",
+        }
+    }
+
+    /// Returns the icon class for the custom block type.
+    pub fn get_icon_class(&self) -> &'static str {
+        match self {
+            CustomBlockType::Note => "icon-info",
+            CustomBlockType::Warning => "icon-alert",
+            CustomBlockType::Tip => "icon-lightbulb",
+            CustomBlockType::Info => "icon-info",
+            CustomBlockType::Important => "icon-report",
+            CustomBlockType::Caution => "icon-stop",
+            CustomBlockType::Danger => "icon-stop",
+            CustomBlockType::Success => "icon-check",
+            CustomBlockType::Primary => "icon-info",
+            CustomBlockType::Secondary => "icon-info",
+        }
+    }
+
+    /// Returns the ARIA role for the custom block type: `alert` for types
+    /// that need the reader's immediate attention, `note` for the others.
+    pub fn get_role(&self) -> &'static str {
+        match self {
+            CustomBlockType::Warning
+            | CustomBlockType::Important
+            | CustomBlockType::Caution
+            | CustomBlockType::Danger => "alert",
+            CustomBlockType::Note
+            | CustomBlockType::Tip
+            | CustomBlockType::Info
+            | CustomBlockType::Success
+            | CustomBlockType::Primary
+            | CustomBlockType::Secondary => "note",
         }
     }
 
@@ -69,10 +279,35 @@ pub fn get_title(&self) -> &'static str {
             CustomBlockType::Info => "Info",
             CustomBlockType::Important => "Important",
             CustomBlockType::Caution => "Caution",
+            CustomBlockType::Danger => "Danger",
+            CustomBlockType::Success => "Success",
+            CustomBlockType::Primary => "Primary",
+            CustomBlockType::Secondary => "Secondary",
         }
     }
 }
 
+/// All built-in custom block types, preloaded into every
+/// [`CustomBlockRegistry`].
+const BUILTIN_BLOCK_TYPES: &[CustomBlockType] = &[
+    CustomBlockType::Note,
+    CustomBlockType::Warning,
+    CustomBlockType::Tip,
+    CustomBlockType::Info,
+    CustomBlockType::Important,
+    CustomBlockType::Caution,
+    CustomBlockType::Danger,
+    CustomBlockType::Success,
+    CustomBlockType::Primary,
+    CustomBlockType::Secondary,
+];
+
+impl fmt::Display for CustomBlockType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl FromStr for CustomBlockType {
     type Err = MarkdownError;
 
@@ -84,6 +319,10 @@ fn from_str(block_type: &str) -> Result<Self, Self::Err> {
             "info" => Ok(CustomBlockType::Info),
             "important" => Ok(CustomBlockType::Important),
             "caution" => Ok(CustomBlockType::Caution),
+            "danger" => Ok(CustomBlockType::Danger),
+            "success" => Ok(CustomBlockType::Success),
+            "primary" => Ok(CustomBlockType::Primary),
+            "secondary" => Ok(CustomBlockType::Secondary),
             _ => Err(MarkdownError::CustomBlockError(format!(
                 "Unknown block type: {}",
                 block_type
@@ -92,14 +331,201 @@ fn from_str(block_type: &str) -> Result<Self, Self::Err> {
     }
 }
 
+/// Describes how a registered custom block is rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomBlockDefinition {
+    /// The Bootstrap alert class applied to the block, e.g. `alert-info`.
+    pub alert_class: String,
+    /// The title shown at the start of the block, e.g. `Note`.
+    pub title: String,
+    /// The icon class used when block icons are enabled, e.g. `icon-info`.
+    pub icon_class: String,
+    /// The ARIA role of the block element, e.g. `note` or `alert`.
+    pub role: String,
+}
+
+/// A registry mapping custom block class names to their rendering.
+///
+/// The built-in block types (note, warning, tip, info, important,
+/// caution, danger, success, primary and secondary) are preloaded. Class
+/// names are matched case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomBlockRegistry {
+    blocks: HashMap<String, CustomBlockDefinition>,
+}
+
+impl Default for CustomBlockRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        for block_type in BUILTIN_BLOCK_TYPES {
+            registry.blocks.insert(
+                block_type.as_str().to_string(),
+                CustomBlockDefinition {
+                    alert_class: block_type
+                        .get_alert_class()
+                        .to_string(),
+                    title: block_type.get_title().to_string(),
+                    icon_class: block_type.get_icon_class().to_string(),
+                    role: block_type.get_role().to_string(),
+                },
+            );
+        }
+        registry
+    }
+}
+
+impl CustomBlockRegistry {
+    /// Creates a registry preloaded with the built-in block types.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with no block types registered.
+    pub fn empty() -> Self {
+        Self {
+            blocks: HashMap::new(),
+        }
+    }
+
+    /// Registers a custom block, replacing any existing block with the
+    /// same class name. The block uses the `icon-info` icon class and the
+    /// `note` role.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        alert_class: impl Into<String>,
+        title: impl Into<String>,
+    ) {
+        self.register_with_icon(name, alert_class, title, "icon-info");
+    }
+
+    /// Registers a custom block with an explicit icon class, replacing any
+    /// existing block with the same class name. The block uses the `note`
+    /// role.
+    pub fn register_with_icon(
+        &mut self,
+        name: impl Into<String>,
+        alert_class: impl Into<String>,
+        title: impl Into<String>,
+        icon_class: impl Into<String>,
+    ) {
+        self.blocks.insert(
+            name.into().to_lowercase(),
+            CustomBlockDefinition {
+                alert_class: alert_class.into(),
+                title: title.into(),
+                icon_class: icon_class.into(),
+                role: "note".to_string(),
+            },
+        );
+    }
+
+    /// Looks up a custom block by class name.
+    pub fn get(&self, name: &str) -> Option<&CustomBlockDefinition> {
+        self.blocks.get(&name.to_lowercase())
+    }
+}
+
+/// What to do with a `<div class="...">` whose class is not a registered
+/// custom block type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownBlockBehavior {
+    /// Replace the block with an `alert-danger` error box. This is the
+    /// default.
+    RenderError,
+    /// Leave the `<div>` and its content untouched.
+    PassThrough,
+    /// Remove the `<div>` and its content.
+    Strip,
+}
+
+impl Default for UnknownBlockBehavior {
+    fn default() -> Self {
+        UnknownBlockBehavior::RenderError
+    }
+}
+
+/// Class names used in the generated HTML, for CSS frameworks other
+/// than Bootstrap. The defaults reproduce the Bootstrap classes.
+///
+/// The classes of the `<table>` elements themselves are set with
+/// `MarkdownOptions::with_table_classes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassNames {
+    /// Class of the `<pre>` element of each highlighted code block.
+    /// Empty, adding no class, by default.
+    pub code_pre: String,
+    /// Class of inline `<code>` elements. Empty, adding no class, by
+    /// default.
+    pub code_inline: String,
+    /// Prefix of custom block classes, replacing `alert` in e.g.
+    /// `alert alert-info` and `alert-icon`. `alert` by default.
+    pub alert_prefix: String,
+    /// Class of the `<div>` wrapping each enhanced table.
+    /// `table-responsive` by default.
+    pub table_wrapper: String,
+}
+
+impl Default for ClassNames {
+    fn default() -> Self {
+        Self {
+            code_pre: String::new(),
+            code_inline: String::new(),
+            alert_prefix: "alert".to_string(),
+            table_wrapper: "table-responsive".to_string(),
+        }
+    }
+}
+
+/// Options controlling how custom blocks are rendered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CustomBlockOptions {
+    /// Render every block as a collapsible `<details>` element. Individual
+    /// blocks can also opt in with a `collapse` class, e.g.
+    /// `<div class="note collapse">`.
+    pub collapsible: bool,
+    /// Prepend an `<span class="alert-icon ...">` icon to every block.
+    pub icons: bool,
+    /// Render blocks with the `note` role as `<aside>` rather than
+    /// `<div>` elements.
+    pub aside_for_notes: bool,
+    /// What to do with blocks whose type is not registered.
+    pub unknown_blocks: UnknownBlockBehavior,
+    /// HTML-escape the content of `<div>` custom blocks, so any markup in
+    /// it is shown as text rather than interpreted.
+    pub escape_content: bool,
+    /// ARIA roles keyed by lowercase block class name, overriding the
+    /// registered role of those blocks.
+    pub role_overrides: HashMap<String, String>,
+    /// Optional text direction, added as a `dir` attribute on every block.
+    pub direction: Option<Direction>,
+    /// Optional prefix replacing `alert` in block classes, e.g. `callout`
+    /// for `callout callout-info`.
+    pub alert_prefix: Option<String>,
+}
+
 lazy_static! {
-    static ref CUSTOM_BLOCK_REGEX: Regex = Regex::new(
-        r#"(?i)<div\s+class=["']?(note|warning|tip|info|important|caution)["']?>(.*?)</div>"#
+    /// Matches the opening tag of a custom block. The class value may be
+    /// double-quoted, single-quoted or unquoted, and an optional
+    /// `data-title` attribute may follow.
+    static ref CUSTOM_BLOCK_OPEN_REGEX: Regex = Regex::new(
+        r#"<div\s+class=(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))(?:\s+data-title=(?:"([^"]*)"|'([^']*)'))?\s*>"#
     ).unwrap();
+    /// Matches a blockquote that starts with a GitHub alert marker such as
+    /// `[!NOTE]`, as rendered by Comrak.
+    static ref GITHUB_ALERT_REGEX: Regex = Regex::new(
+        r#"(?s)<blockquote>\s*<p>\[!((?i:note|tip|important|warning|caution))\][ \t]*\n?(.*?)\s*</blockquote>"#
+    ).unwrap();
+    /// Matches any opening or closing `div` tag, used to balance nesting.
+    static ref DIV_TAG_REGEX: Regex =
+        Regex::new(r#"(?i)<(/?)div\b[^>]*>"#).unwrap();
 }
 
+#[cfg(feature = "syntax_highlighting")]
 /// Applies syntax highlighting to code blocks in the Markdown.
 ///
+/// Uses the [`DEFAULT_THEME`].
+///
 /// # Arguments
 ///
 /// * `code` - The code block string to be highlighted.
@@ -112,158 +538,3657 @@ pub fn apply_syntax_highlighting(
     code: &str,
     lang: &str,
 ) -> Result<String, MarkdownError> {
-    let theme = &THEME_SET.themes["base16-ocean.dark"];
-    let syntax = SYNTAX_SET
-        .find_syntax_by_token(lang)
-        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    apply_syntax_highlighting_with_theme(code, lang, DEFAULT_THEME)
+}
+
+#[cfg(feature = "syntax_highlighting")]
+/// Applies syntax highlighting to a code block using the given syntax
+/// set and theme, instead of the bundled ones.
+///
+/// Applications that already load their own syntaxes and themes can use
+/// this to avoid loading the bundled sets a second time.
+///
+/// # Arguments
+///
+/// * `code` - The code block string to be highlighted.
+/// * `lang` - The programming language of the code block.
+/// * `syntax_set` - The syntax set used to look up `lang`.
+/// * `theme` - The theme used to highlight the code.
+///
+/// # Returns
+///
+/// A `Result` containing the HTML for the highlighted code or a `MarkdownError`.
+pub fn apply_syntax_highlighting_with(
+    code: &str,
+    lang: &str,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+) -> Result<String, MarkdownError> {
+    let syntax = find_syntax(syntax_set, lang, &HashMap::new())
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+    highlight_with_theme(code, syntax, syntax_set, theme)
+}
 
-    highlighted_html_for_string(code, &SYNTAX_SET, syntax, theme)
-        .map_err(|e| MarkdownError::SyntaxHighlightError(e.to_string()))
+#[cfg(feature = "syntax_highlighting")]
+/// Applies syntax highlighting to a code block using a named theme.
+///
+/// # Arguments
+///
+/// * `code` - The code block string to be highlighted.
+/// * `lang` - The programming language of the code block.
+/// * `theme_name` - The name of a theme in the bundled `ThemeSet`.
+///
+/// # Returns
+///
+/// A `Result` containing the HTML for the highlighted code, or a
+/// `MarkdownError::SyntaxHighlightError` if the theme is unknown.
+pub fn apply_syntax_highlighting_with_theme(
+    code: &str,
+    lang: &str,
+    theme_name: &str,
+) -> Result<String, MarkdownError> {
+    let theme = resolve_theme(theme_name)?;
+    apply_syntax_highlighting_with(code, lang, &SYNTAX_SET, theme)
 }
 
-/// Processes tables, enhancing them with responsive design and alignment classes.
+#[cfg(feature = "syntax_highlighting")]
+/// Applies syntax highlighting to inline code.
+///
+/// Unlike [`apply_syntax_highlighting`], the result is a single
+/// `<code class="language-...">` element without a `<pre>` wrapper, and
+/// trailing newlines are removed. Uses the [`DEFAULT_THEME`].
 ///
 /// # Arguments
 ///
-/// * `table_html` - The HTML string representing the table.
+/// * `code` - The inline code to be highlighted.
+/// * `lang` - The programming language of the code.
 ///
 /// # Returns
 ///
-/// The enhanced HTML string.
-pub fn process_tables(table_html: &str) -> String {
-    let table_regex = Regex::new(r"<table>").unwrap();
-    let table_html = table_regex.replace(
-        table_html,
-        r#"<div class="table-responsive"><table class="table">"#,
-    );
+/// A `Result` containing the HTML for the highlighted code or a `MarkdownError`.
+pub fn highlight_inline_code(
+    code: &str,
+    lang: &str,
+) -> Result<String, MarkdownError> {
+    let theme = resolve_theme(DEFAULT_THEME)?;
+    let syntax = find_syntax(&SYNTAX_SET, lang, &HashMap::new())
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    let lines = highlight_lines_with_theme(
+        code.trim_end_matches(['\r', '\n'].as_ref()),
+        syntax,
+        &SYNTAX_SET,
+        theme,
+    )?;
 
-    let table_end_regex = Regex::new(r"</table>").unwrap();
-    let table_html =
-        table_end_regex.replace(&table_html, "</table></div>");
+    Ok(format!(
+        "<code class=\"language-{}\">{}</code>",
+        html_escape::encode_double_quoted_attribute(lang),
+        lines.concat()
+    ))
+}
 
-    // Add alignment classes to table cells
-    let cell_regex = Regex::new(r"<td([^>]*)>").unwrap();
-    let table_html = cell_regex.replace_all(
-        &table_html,
-        |caps: &regex::Captures| {
-            let attrs = &caps[1];
-            if attrs.contains("align=\"center\"") {
-                format!(r#"<td{} class="text-center">"#, attrs)
-            } else if attrs.contains("align=\"right\"") {
-                format!(r#"<td{} class="text-right">"#, attrs)
-            } else {
-                format!(r#"<td{} class="text-left">"#, attrs)
-            }
-        },
-    );
+#[cfg(feature = "syntax_highlighting")]
+/// Returns the sorted names of all themes in the bundled `ThemeSet`.
+///
+/// Any of these names can be passed to
+/// [`MarkdownOptions::with_custom_theme`](crate::MarkdownOptions::with_custom_theme).
+pub fn list_available_themes() -> Vec<String> {
+    // `ThemeSet::themes` is a `BTreeMap`, so the keys are already sorted.
+    THEME_SET.themes.keys().cloned().collect()
+}
 
-    table_html.to_string()
+#[cfg(feature = "syntax_highlighting")]
+/// Returns the sorted names of all syntaxes in the bundled `SyntaxSet`.
+pub fn list_available_languages() -> Vec<String> {
+    let mut languages: Vec<String> = SYNTAX_SET
+        .syntaxes()
+        .iter()
+        .map(|syntax| syntax.name.clone())
+        .collect();
+    languages.sort();
+    languages
 }
 
-/// Processes custom blocks in the Markdown content, such as note, warning, tip, info, important, and caution blocks.
-/// These custom blocks are represented by div elements with specific class names.
-/// The function replaces these div elements with corresponding Bootstrap alert elements.
+#[cfg(feature = "syntax_highlighting")]
+/// Loads a syntax highlighting theme from a `.tmTheme` file.
 ///
 /// # Arguments
 ///
-/// * `content` - A string containing the Markdown content.
+/// * `path` - The path to the `.tmTheme` file.
 ///
 /// # Returns
 ///
-/// A string containing the processed Markdown content with custom blocks replaced by Bootstrap alert elements.
-pub fn process_custom_blocks(content: &str) -> String {
-    // Adjusted to match any block type (including unknown ones)
-    Regex::new(r#"<div\s+class=["']?(.*?)["']?>(.*?)</div>"#)
-        .unwrap()
-        .replace_all(content, |caps: &regex::Captures| {
-            match CustomBlockType::from_str(caps.get(1).unwrap().as_str()) {
-                Ok(block_type) => generate_custom_block_html(block_type, &caps[2]),
-                Err(e) => format!(
-                    r#"<div class="alert alert-danger" role="alert"><strong>Error:</strong> {}</div>"#,
-                    e
-                ),
-            }
-        })
-        .to_string()
+/// A `Result` containing the loaded `Theme`, or a
+/// `MarkdownError::SyntaxSetError` naming the path if it cannot be loaded.
+pub fn load_theme_from_file(
+    path: impl AsRef<Path>,
+) -> Result<Theme, MarkdownError> {
+    let path = path.as_ref();
+    ThemeSet::get_theme(path).map_err(|e| {
+        MarkdownError::SyntaxSetError(format!(
+            "Failed to load theme from '{}': {}",
+            path.display(),
+            e
+        ))
+    })
 }
 
-/// Generates the HTML for a custom block based on its type and content.
+#[cfg(feature = "syntax_highlighting")]
+/// Looks up a theme by name in the bundled `ThemeSet`.
+pub(crate) fn resolve_theme(
+    theme_name: &str,
+) -> Result<&'static Theme, MarkdownError> {
+    THEME_SET.themes.get(theme_name).ok_or_else(|| {
+        MarkdownError::SyntaxHighlightError(format!(
+            "Unknown theme: {}",
+            theme_name
+        ))
+    })
+}
+
+#[cfg(feature = "syntax_highlighting")]
+/// Returns the bundled default `SyntaxSet`.
+pub(crate) fn default_syntax_set() -> &'static SyntaxSet {
+    &SYNTAX_SET
+}
+
+#[cfg(feature = "syntax_highlighting")]
+/// Builds a `SyntaxSet` containing the default syntaxes plus every
+/// `.sublime-syntax` file found in `dir`.
 ///
 /// # Arguments
 ///
-/// * `block_type` - The type of the custom block.
-/// * `block_content` - The content inside the custom block.
+/// * `dir` - The directory to search for `.sublime-syntax` files.
 ///
 /// # Returns
 ///
-/// A string containing the HTML for the custom block.
-fn generate_custom_block_html(
-    block_type: CustomBlockType,
-    block_content: &str,
-) -> String {
-    format!(
-        r#"<div class="alert {}" role="alert"><strong>{}:</strong> {}</div>"#,
-        block_type.get_alert_class(),
-        block_type.get_title(),
-        block_content
-    )
+/// A `Result` containing the combined `SyntaxSet`, or a
+/// `MarkdownError::SyntaxSetError` if the directory cannot be read or a
+/// syntax fails to compile.
+pub fn load_syntax_set_with_extras(
+    dir: impl AsRef<Path>,
+) -> Result<SyntaxSet, MarkdownError> {
+    let dir = dir.as_ref();
+    let mut builder = SYNTAX_SET.clone().into_builder();
+    builder.add_from_folder(dir, true).map_err(|e| {
+        MarkdownError::SyntaxSetError(format!(
+            "Failed to load syntaxes from '{}': {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    Ok(builder.build())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[cfg(feature = "syntax_highlighting")]
+/// Finds the syntax for a code block language token.
+///
+/// User-supplied `aliases` are consulted first, then the token itself,
+/// then [`DEFAULT_LANGUAGE_ALIASES`]. Returns `None` if nothing matches.
+pub(crate) fn find_syntax<'s>(
+    syntax_set: &'s SyntaxSet,
+    lang: &str,
+    aliases: &HashMap<String, String>,
+) -> Option<&'s SyntaxReference> {
+    let token = lang.to_lowercase();
+    aliases
+        .get(&token)
+        .and_then(|name| syntax_set.find_syntax_by_name(name))
+        .or_else(|| syntax_set.find_syntax_by_token(lang))
+        .or_else(|| {
+            DEFAULT_LANGUAGE_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == token)
+                .and_then(|(_, name)| {
+                    syntax_set.find_syntax_by_name(name)
+                })
+        })
+}
 
-    #[test]
-    fn test_process_custom_blocks() {
-        let input = r#"
-            <div class="note">This is a note.</div>
-            <div class="WARNING">This is a warning.</div>
-            <div class="Tip">This is a tip.</div>
-            <div class="INFO">This is an info block.</div>
-            <div class="Important">This is important.</div>
-            <div class="caution">This is a caution.</div>
-        "#;
+#[cfg(feature = "syntax_highlighting")]
+/// Replaces the tabs in `code` with spaces, up to the next multiple of
+/// `width` columns on each line.
+pub(crate) fn expand_tabs(code: &str, width: usize) -> String {
+    let mut result = String::with_capacity(code.len());
+    let mut column = 0;
+    for c in code.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                result.extend(std::iter::repeat(' ').take(spaces));
+                column += spaces;
+            }
+            '\n' => {
+                result.push(c);
+                column = 0;
+            }
+            _ => {
+                result.push(c);
+                column += 1;
+            }
+        }
+    }
+    result
+}
 
-        let processed = process_custom_blocks(input);
+#[cfg(feature = "syntax_highlighting")]
+/// Highlights a code block with an already resolved syntax and theme.
+///
+/// The final newline of the code, and the one syntect appends after the
+/// closing `</pre>`, are dropped so the block does not end with an empty
+/// line. Blank lines elsewhere are kept.
+pub(crate) fn highlight_with_theme(
+    code: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+) -> Result<String, MarkdownError> {
+    let code = code
+        .strip_suffix('\n')
+        .map(|code| code.strip_suffix('\r').unwrap_or(code))
+        .unwrap_or(code);
+    let mut html =
+        highlighted_html_for_string(code, syntax_set, syntax, theme)
+            .map_err(|e| {
+                MarkdownError::SyntaxHighlightError(e.to_string())
+            })?;
+    if html.ends_with('\n') {
+        html.pop();
+    }
+    Ok(html)
+}
 
-        assert!(processed.contains(r#"<div class="alert alert-info" role="alert"><strong>Note:</strong> This is a note.</div>"#));
-        assert!(processed.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong> This is a warning.</div>"#));
-        assert!(processed.contains(r#"<div class="alert alert-success" role="alert"><strong>Tip:</strong> This is a tip.</div>"#));
-        assert!(processed.contains(r#"<div class="alert alert-primary" role="alert"><strong>Info:</strong> This is an info block.</div>"#));
-        assert!(processed.contains(r#"<div class="alert alert-danger" role="alert"><strong>Important:</strong> This is important.</div>"#));
-        assert!(processed.contains(r#"<div class="alert alert-secondary" role="alert"><strong>Caution:</strong> This is a caution.</div>"#));
+#[cfg(feature = "syntax_highlighting")]
+/// Highlights a code block line by line, returning the HTML for each
+/// source line.
+///
+/// Each entry keeps the line's trailing newline inside its markup, so
+/// concatenating the entries reproduces the block body.
+pub(crate) fn highlight_lines_with_theme(
+    code: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+) -> Result<Vec<String>, MarkdownError> {
+    let background = theme.settings.background.unwrap_or(Color::WHITE);
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    LinesWithEndings::from(code)
+        .map(|line| {
+            let regions = highlighter
+                .highlight_line(line, syntax_set)
+                .map_err(|e| {
+                    MarkdownError::SyntaxHighlightError(e.to_string())
+                })?;
+            styled_line_to_highlighted_html(
+                &regions,
+                IncludeBackground::IfDifferent(background),
+            )
+            .map_err(|e| {
+                MarkdownError::SyntaxHighlightError(e.to_string())
+            })
+        })
+        .collect()
+}
+
+lazy_static! {
+    /// Matches a `[Table: caption]` line in the Markdown source.
+    static ref TABLE_CAPTION_REGEX: Regex =
+        Regex::new(r"^\s*\[Table:\s*(.+)\]\s*$").unwrap();
+    /// Matches the placeholder left by `extract_table_captions`, and the
+    /// table that follows it if there is one.
+    static ref TABLE_CAPTION_MARKER_REGEX: Regex = Regex::new(
+        r"<!-- mdx-gen-table-caption:(\d+) -->\n?(<table>)?"
+    ).unwrap();
+}
+
+/// Replaces `[Table: caption]` lines that immediately precede a table row
+/// with placeholder comments, returning the rewritten Markdown and the
+/// extracted captions.
+///
+/// Lines inside fenced code blocks are left untouched.
+pub(crate) fn extract_table_captions(
+    markdown: &str,
+) -> (String, Vec<String>) {
+    let lines: Vec<&str> = markdown.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(markdown.len());
+    let mut captions = Vec::new();
+    let mut fences = FenceScanner::default();
+
+    for (index, line) in lines.iter().enumerate() {
+        let in_fence = fences.is_code(line);
+
+        let next_is_table_row = lines
+            .get(index + 1)
+            .map_or(false, |next| next.contains('|'));
+        match TABLE_CAPTION_REGEX.captures(line.trim_end_matches('\n'))
+        {
+            Some(caps) if !in_fence && next_is_table_row => {
+                output.push_str(&format!(
+                    "<!-- mdx-gen-table-caption:{} -->\n",
+                    captions.len()
+                ));
+                captions.push(caps[1].trim().to_string());
+            }
+            _ => output.push_str(line),
+        }
     }
 
-    #[test]
-    fn test_unknown_custom_block() {
-        let input = r#"<div class="unknown">This is an unknown block type.</div>"#;
-        let processed = process_custom_blocks(input);
+    (output, captions)
+}
 
-        // Print the processed output to verify the content
-        println!("Processed content: {}", processed);
+/// Returns the index of the caption whose placeholder, left by
+/// `extract_table_captions`, is the HTML block `html`.
+pub(crate) fn table_caption_index(html: &str) -> Option<usize> {
+    html.trim()
+        .strip_prefix("<!-- mdx-gen-table-caption:")?
+        .strip_suffix(" -->")?
+        .parse()
+        .ok()
+}
 
-        // Check if the error is correctly reported in the output
-        assert!(processed.contains(r#"Failed to process custom block: Unknown block type: unknown"#), "Expected error message for unknown block type not found");
+/// Returns `line` without its indentation if it is indented by fewer
+/// than four columns, and `None` if it would be an indented code block
+/// line.
+fn strip_block_indent(line: &str) -> Option<&str> {
+    let content = line.trim_start_matches(' ');
+    if line.len() - content.len() < 4 && !content.starts_with('\t') {
+        Some(content)
+    } else {
+        None
     }
+}
 
-    #[test]
-    fn test_process_tables() {
-        let input = r#"<table><tr><td align="center">Center</td><td align="right">Right</td><td>Left</td></tr></table>"#;
+/// Tracks whether the lines of a Markdown document are inside a fenced
+/// code block, following the CommonMark rules: a fence is a run of at
+/// least three backticks or tildes indented by fewer than four columns,
+/// and only a run of the same character, at least as long, closes it.
+#[derive(Debug, Default)]
+struct FenceScanner {
+    /// The character and length of the open fence, if any.
+    open: Option<(char, usize)>,
+}
 
-        let processed = process_tables(input);
+impl FenceScanner {
+    /// Returns whether `line`, the next line of the document, belongs to
+    /// a fenced code block, including the fences themselves.
+    fn is_code(&mut self, line: &str) -> bool {
+        let fence = strip_block_indent(line).and_then(|content| {
+            let marker = content.chars().next()?;
+            if marker != '`' && marker != '~' {
+                return None;
+            }
+            let rest = content.trim_start_matches(marker);
+            let length = content.len() - rest.len();
+            (length >= 3).then(|| (marker, length, rest))
+        });
 
-        assert!(processed.contains(
-            r#"<div class="table-responsive"><table class="table">"#
-        ));
-        assert!(processed.contains(
-            r#"<td align="center" class="text-center">Center</td>"#
-        ));
-        assert!(processed.contains(
-            r#"<td align="right" class="text-right">Right</td>"#
-        ));
-        assert!(
-            processed.contains(r#"<td class="text-left">Left</td>"#)
-        );
-        assert!(processed.contains("</table></div>"));
+        match (self.open, fence) {
+            (
+                Some((open_marker, open_length)),
+                Some((marker, length, rest)),
+            ) => {
+                if marker == open_marker
+                    && length >= open_length
+                    && rest.trim().is_empty()
+                {
+                    self.open = None;
+                }
+                true
+            }
+            (Some(_), None) => true,
+            (None, Some((marker, length, rest))) => {
+                // Backtick fences may not have backticks in their info
+                // string.
+                if marker == '`' && rest.contains('`') {
+                    return false;
+                }
+                self.open = Some((marker, length));
+                true
+            }
+            (None, None) => false,
+        }
+    }
+}
+
+/// Returns the byte offset and info string of the opening fence of each
+/// fenced code block in `markdown`.
+#[cfg(feature = "syntax_highlighting")]
+pub(crate) fn fenced_code_block_starts(
+    markdown: &str,
+) -> Vec<(usize, &str)> {
+    let mut fences = FenceScanner::default();
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in markdown.split_inclusive('\n') {
+        let was_open = fences.open.is_some();
+        if fences.is_code(line) && !was_open {
+            let info = line
+                .trim_start_matches(' ')
+                .trim_start_matches(|c| c == '`' || c == '~')
+                .trim();
+            starts.push((offset, info));
+        }
+        offset += line.len();
+    }
+    starts
+}
+
+lazy_static! {
+    /// Matches the opening line of a fenced custom block, e.g.
+    /// `:::note Optional title`.
+    static ref FENCED_BLOCK_OPEN_REGEX: Regex =
+        Regex::new(r"^:{3,}[ \t]*([A-Za-z][\w-]*)(?:[ \t]+(.*?))?[ \t]*$")
+            .unwrap();
+    /// Matches the closing line of a fenced custom block.
+    static ref FENCED_BLOCK_CLOSE_REGEX: Regex =
+        Regex::new(r"^:{3,}[ \t]*$").unwrap();
+}
+
+/// Rewrites `:::type` ... `:::` fenced blocks as `<div class="type">`
+/// custom blocks, so their content is still rendered as Markdown.
+///
+/// Text after the type on the opening line becomes the block title.
+/// Blocks may be nested, each `:::` closing the innermost open block; a
+/// block left open is closed at the end of the document. Lines inside
+/// fenced code blocks, and lines indented by four or more columns, are
+/// left untouched.
+pub(crate) fn expand_fenced_custom_blocks(markdown: &str) -> String {
+    let mut output = String::with_capacity(markdown.len());
+    let mut open_blocks = 0;
+    let mut fences = FenceScanner::default();
+
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = match strip_block_indent(line) {
+            Some(content) if !fences.is_code(line) => {
+                content.trim_end()
+            }
+            _ => {
+                output.push_str(line);
+                continue;
+            }
+        };
+
+        if let Some(caps) = FENCED_BLOCK_OPEN_REGEX.captures(trimmed) {
+            let title = caps
+                .get(2)
+                .filter(|title| !title.as_str().is_empty())
+                .map(|title| {
+                    format!(
+                        r#" data-title="{}""#,
+                        html_escape::encode_double_quoted_attribute(
+                            title.as_str()
+                        )
+                    )
+                })
+                .unwrap_or_default();
+            output.push_str(&format!(
+                "<div class=\"{}\"{}>\n\n",
+                &caps[1], title
+            ));
+            open_blocks += 1;
+        } else if open_blocks > 0
+            && FENCED_BLOCK_CLOSE_REGEX.is_match(trimmed)
+        {
+            output.push_str("\n</div>\n\n");
+            open_blocks -= 1;
+        } else {
+            output.push_str(line);
+        }
+    }
+
+    if open_blocks > 0 && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    for _ in 0..open_blocks {
+        output.push_str("\n</div>\n");
+    }
+    output
+}
+
+lazy_static! {
+    /// Matches a blank line.
+    static ref BLANK_LINE_REGEX: Regex =
+        Regex::new(r"\n[ \t]*\r?\n").unwrap();
+}
+
+/// Surrounds the content of `<div>` custom blocks that span several
+/// paragraphs with blank lines, so Comrak renders it as Markdown.
+///
+/// Without this, a blank line ends the raw HTML block that the opening
+/// tag starts: the first paragraph stays bare text and the closing
+/// `</div>` can end up inside the last paragraph. Blocks without blank
+/// lines, and blocks whose opening tag does not start a line, are left
+/// untouched, as are lines inside fenced code blocks.
+pub(crate) fn separate_multi_paragraph_blocks(
+    markdown: &str,
+) -> String {
+    let mut fenced = Vec::new();
+    let mut fences = FenceScanner::default();
+    let mut offset = 0;
+    for line in markdown.split_inclusive('\n') {
+        if fences.is_code(line) {
+            fenced.push(offset..offset + line.len());
+        }
+        offset += line.len();
+    }
+    // The ranges are sorted, so only the last one starting at or before
+    // `pos` can contain it.
+    let in_fence = |pos: usize| {
+        let index = fenced.partition_point(|range| range.start <= pos);
+        index > 0 && fenced[index - 1].contains(&pos)
+    };
+
+    let div_pairs = match_div_tags(markdown);
+    let mut insertions = Vec::new();
+    for open in CUSTOM_BLOCK_OPEN_REGEX.find_iter(markdown) {
+        let line_start = markdown[..open.start()]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        if !markdown[line_start..open.start()].trim().is_empty()
+            || in_fence(open.start())
+        {
+            continue;
+        }
+        let close = match div_pairs.get(&open.start()) {
+            Some(&(open_end, close)) if open_end == open.end() => close,
+            _ => find_matching_div_close(markdown, open.end()),
+        };
+        if let Some((body_end, _)) = close {
+            if BLANK_LINE_REGEX
+                .is_match(&markdown[open.end()..body_end])
+            {
+                insertions.push(open.end());
+                insertions.push(body_end);
+            }
+        }
+    }
+    if insertions.is_empty() {
+        return markdown.to_string();
+    }
+    insertions.sort_unstable();
+
+    let mut output =
+        String::with_capacity(markdown.len() + insertions.len() * 2);
+    let mut last = 0;
+    for position in insertions {
+        output.push_str(&markdown[last..position]);
+        output.push_str("\n\n");
+        last = position;
+    }
+    output.push_str(&markdown[last..]);
+    output
+}
+
+lazy_static! {
+    /// Matches an `{{include:path}}` directive.
+    static ref INCLUDE_DIRECTIVE_REGEX: Regex =
+        Regex::new(r"\{\{include:([^{}]+)\}\}").unwrap();
+}
+
+/// Replaces `{{include:path}}` directives with the contents of the named
+/// files, resolved relative to `base_dir`.
+///
+/// Included files may themselves include files, up to `max_depth` levels
+/// deep. Directives inside fenced code blocks are left untouched.
+///
+/// # Errors
+///
+/// Returns `MarkdownError::IoError` if an included file cannot be read,
+/// and `MarkdownError::IncludeError` if a path is absolute, contains
+/// `..` or resolves outside `base_dir` through a symbolic link, if a file
+/// includes itself, directly or through other files, or if includes are
+/// nested more than `max_depth` levels deep.
+pub(crate) fn expand_includes(
+    markdown: &str,
+    base_dir: &Path,
+    max_depth: usize,
+) -> Result<String, MarkdownError> {
+    expand_includes_with_stack(
+        markdown,
+        base_dir,
+        max_depth,
+        &mut Vec::new(),
+    )
+}
+
+/// Expands the includes of `markdown`, where `stack` holds the files
+/// currently being included, outermost first.
+fn expand_includes_with_stack(
+    markdown: &str,
+    base_dir: &Path,
+    max_depth: usize,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, MarkdownError> {
+    let mut output = String::with_capacity(markdown.len());
+    let mut fences = FenceScanner::default();
+
+    for line in markdown.split_inclusive('\n') {
+        if fences.is_code(line) {
+            output.push_str(line);
+            continue;
+        }
+
+        let mut position = 0;
+        for caps in INCLUDE_DIRECTIVE_REGEX.captures_iter(line) {
+            let directive = caps.get(0).unwrap();
+            output.push_str(&line[position..directive.start()]);
+            output.push_str(&include_file(
+                caps[1].trim(),
+                base_dir,
+                max_depth,
+                stack,
+            )?);
+            position = directive.end();
+        }
+        output.push_str(&line[position..]);
+    }
+
+    Ok(output)
+}
+
+/// Reads the file `name` relative to `base_dir` and expands its own
+/// includes. A single trailing newline is dropped, so a directive may be
+/// used inline.
+fn include_file(
+    name: &str,
+    base_dir: &Path,
+    max_depth: usize,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, MarkdownError> {
+    if stack.len() >= max_depth {
+        return Err(MarkdownError::IncludeError(format!(
+            "Including {} exceeds the maximum depth of {}",
+            name, max_depth
+        )));
+    }
+
+    let relative = Path::new(name);
+    if relative.components().any(|component| {
+        !matches!(component, Component::Normal(_) | Component::CurDir)
+    }) {
+        return Err(MarkdownError::IncludeError(format!(
+            "Cannot include {}: paths must be relative and may not contain '..'",
+            name
+        )));
+    }
+
+    let path = base_dir.join(relative);
+    let read_error = |path: &Path, e: std::io::Error| {
+        MarkdownError::IoError(std::io::Error::new(
+            e.kind(),
+            format!("Failed to read {}: {}", path.display(), e),
+        ))
+    };
+    let canonical_base_dir = base_dir
+        .canonicalize()
+        .map_err(|e| read_error(base_dir, e))?;
+    let canonical =
+        path.canonicalize().map_err(|e| read_error(&path, e))?;
+    // Symbolic links may still point outside the base directory.
+    if !canonical.starts_with(&canonical_base_dir) {
+        return Err(MarkdownError::IncludeError(format!(
+            "Cannot include {}: it resolves outside {}",
+            name,
+            base_dir.display()
+        )));
+    }
+    if stack.contains(&canonical) {
+        return Err(MarkdownError::IncludeError(format!(
+            "Circular include of {}",
+            path.display()
+        )));
+    }
+    let content = fs::read_to_string(&canonical)
+        .map_err(|e| read_error(&path, e))?;
+    let content = content
+        .strip_suffix('\n')
+        .map(|content| content.strip_suffix('\r').unwrap_or(content))
+        .unwrap_or(&content);
+
+    stack.push(canonical);
+    let expanded =
+        expand_includes_with_stack(content, base_dir, max_depth, stack);
+    stack.pop();
+    expanded
+}
+
+/// Moves the captions extracted by `extract_table_captions` into the
+/// rendered tables as HTML-escaped `<caption>` elements.
+///
+/// A placeholder that is not followed by a table is restored as a plain
+/// paragraph.
+pub(crate) fn insert_table_captions(
+    html: &str,
+    captions: &[String],
+) -> String {
+    TABLE_CAPTION_MARKER_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            let caption = caps[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| captions.get(index))
+                .map(|caption| html_escape::encode_text(caption))
+                .unwrap_or_default();
+            if caps.get(2).is_some() {
+                format!("<table>\n<caption>{}</caption>", caption)
+            } else {
+                format!("<p>[Table: {}]</p>\n", caption)
+            }
+        })
+        .to_string()
+}
+
+lazy_static! {
+    /// Matches a heading without attributes, capturing its level and content.
+    static ref HEADING_REGEX: Regex =
+        Regex::new(r"(?s)<h([1-6])>(.*?)</h[1-6]>").unwrap();
+    /// Matches any HTML tag.
+    static ref HTML_TAG_REGEX: Regex = Regex::new(r"<[^>]*>").unwrap();
+}
+
+/// Removes all tags from an HTML fragment and decodes its entities.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let text = HTML_TAG_REGEX.replace_all(html, "");
+    html_escape::decode_html_entities(&text).into_owned()
+}
+
+/// Converts heading text into a URL fragment.
+///
+/// Tags are removed, the text is lowercased, runs of whitespace become a
+/// single hyphen, and any other non-alphanumeric characters are dropped.
+/// Unicode letters and digits are kept.
+///
+/// # Example
+///
+/// ```
+/// use mdx_gen::extensions::slugify;
+/// assert_eq!(slugify("Hello, <em>World</em>!"), "hello-world");
+/// ```
+pub fn slugify(text: &str) -> String {
+    let text = html_to_text(text).to_lowercase();
+
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| {
+                    c.is_alphanumeric() || *c == '-' || *c == '_'
+                })
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Adds `id` attributes derived from the heading text to `<h1>`–`<h6>`
+/// elements, optionally followed by a permalink.
+///
+/// Duplicate slugs are disambiguated with `-1`, `-2`, ... suffixes.
+/// Headings that already carry attributes are left unchanged.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `permalinks` - Whether to append an
+///   `<a class="heading-anchor" href="#slug">` link to each heading.
+///
+/// # Returns
+///
+/// The HTML string with heading anchors added.
+pub fn process_heading_anchors(html: &str, permalinks: bool) -> String {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    HEADING_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            let level = &caps[1];
+            let content = &caps[2];
+            let mut slug = slugify(content);
+            if slug.is_empty() {
+                slug = "heading".to_string();
+            }
+
+            let count = seen.entry(slug.clone()).or_insert(0);
+            if *count > 0 {
+                slug = format!("{}-{}", slug, count);
+            }
+            *count += 1;
+
+            let permalink = if permalinks {
+                format!(
+                    r##" <a class="heading-anchor" href="#{}" aria-hidden="true">#</a>"##,
+                    slug
+                )
+            } else {
+                String::new()
+            };
+            format!(
+                r#"<h{} id="{}">{}{}</h{}>"#,
+                level, slug, content, permalink, level
+            )
+        })
+        .to_string()
+}
+
+lazy_static! {
+    /// Matches an opening heading tag with an `id` attribute.
+    static ref HEADING_WITH_ID_REGEX: Regex =
+        Regex::new(r#"<h([1-6])(\s[^>]*\bid="[^"]*"[^>]*)>"#).unwrap();
+    /// Matches a `style` attribute.
+    static ref STYLE_ATTR_REGEX: Regex =
+        Regex::new(r"(?i)(?:^|\s)style\s*=").unwrap();
+}
+
+/// Adds `style="scroll-margin-top:{px}px"` to every heading that has an
+/// `id`, so that anchor jumps clear a sticky header. Headings that
+/// already have a `style` are left unchanged.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `px` - The scroll margin in pixels.
+///
+/// # Returns
+///
+/// The HTML string with the scroll margin added.
+pub fn process_heading_scroll_margin(html: &str, px: u32) -> String {
+    HEADING_WITH_ID_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            if STYLE_ATTR_REGEX.is_match(&caps[2]) {
+                return caps[0].to_string();
+            }
+            format!(
+                r#"<h{}{} style="scroll-margin-top:{}px">"#,
+                &caps[1], &caps[2], px
+            )
+        })
+        .into_owned()
+}
+
+lazy_static! {
+    static ref PRESERVED_ELEMENT_REGEX: Regex = Regex::new(
+        r"(?is)<pre\b.*?</pre>|<code\b.*?</code>|<textarea\b.*?</textarea>|<script\b.*?</script>|<style\b.*?</style>"
+    )
+    .unwrap();
+    static ref WHITESPACE_REGEX: Regex = Regex::new(r"\s+").unwrap();
+    static ref BLOCK_TAG_REGEX: Regex = Regex::new(
+        r"(?i)\s*(</?(?:address|article|aside|blockquote|br|caption|col|colgroup|dd|details|div|dl|dt|figcaption|figure|footer|h[1-6]|header|hr|li|nav|ol|p|section|summary|table|tbody|td|tfoot|th|thead|tr|ul)\b[^>]*>)\s*"
+    )
+    .unwrap();
+}
+
+/// Collapses insignificant whitespace in an HTML document.
+///
+/// Runs of whitespace become a single space, and whitespace next to
+/// block-level tags is removed. The contents of `<pre>`, `<code>`,
+/// `<textarea>`, `<script>` and `<style>` elements are left untouched,
+/// as is the single space separating inline elements and text.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to minify.
+///
+/// # Returns
+///
+/// The minified HTML string.
+pub fn minify_html(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut last = 0;
+    let mut after_pre = false;
+
+    for element in PRESERVED_ELEMENT_REGEX.find_iter(html) {
+        let is_pre = element.as_str()[..4].eq_ignore_ascii_case("<pre");
+        let text = minify_fragment(&html[last..element.start()]);
+        let text = if after_pre { text.trim_start() } else { &text };
+        result.push_str(if is_pre { text.trim_end() } else { text });
+        result.push_str(element.as_str());
+        after_pre = is_pre;
+        last = element.end();
+    }
+
+    let text = minify_fragment(&html[last..]);
+    let text = if after_pre { text.trim_start() } else { &text };
+    result.push_str(text.trim_end());
+    result
+}
+
+/// Normalizes HTML for comparison, e.g. in snapshot tests.
+///
+/// Whitespace that does not affect rendering, such as the newlines
+/// between block-level tags, is removed as by [`minify_html`], as is
+/// leading and trailing whitespace. Two documents that differ only in
+/// such whitespace normalize to the same string, while the contents of
+/// `<pre>` and `<code>` elements are compared exactly.
+///
+/// # Example
+///
+/// ```
+/// use mdx_gen::normalize_html;
+///
+/// assert_eq!(
+///     normalize_html("<ul>\n  <li>One</li>\n</ul>\n"),
+///     normalize_html("<ul><li>One</li></ul>"),
+/// );
+/// ```
+pub fn normalize_html(html: &str) -> String {
+    minify_html(html).trim_start().to_string()
+}
+
+/// Minifies HTML that contains no whitespace-sensitive elements.
+fn minify_fragment(html: &str) -> String {
+    let collapsed = WHITESPACE_REGEX.replace_all(html, " ");
+    BLOCK_TAG_REGEX.replace_all(&collapsed, "$1").into_owned()
+}
+
+lazy_static! {
+    /// Matches an HTML comment, together with its line when the comment
+    /// is the only thing on it.
+    static ref HTML_COMMENT_REGEX: Regex =
+        Regex::new(r"(?m)^[ \t]*<!--(?s:.*?)-->[ \t]*\n|<!--(?s:.*?)-->")
+            .unwrap();
+}
+
+/// Removes HTML comments from an HTML document.
+///
+/// Comments inside `<pre>`, `<code>`, `<textarea>`, `<script>` and
+/// `<style>` elements are kept, as they may be literal content. A
+/// comment alone on its line is removed together with the line.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+///
+/// # Returns
+///
+/// The HTML string without comments.
+pub fn strip_html_comments(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut last = 0;
+
+    for element in PRESERVED_ELEMENT_REGEX.find_iter(html) {
+        result.push_str(
+            &HTML_COMMENT_REGEX
+                .replace_all(&html[last..element.start()], ""),
+        );
+        result.push_str(element.as_str());
+        last = element.end();
+    }
+    result.push_str(&HTML_COMMENT_REGEX.replace_all(&html[last..], ""));
+    result
+}
+
+/// Applies `f` to every run of text in `html` that is outside tags and
+/// outside `<pre>`, `<code>`, `<textarea>`, `<script>` and `<style>`
+/// elements, leaving everything else unchanged.
+pub(crate) fn map_text_outside_code<F>(html: &str, mut f: F) -> String
+where
+    F: FnMut(&str) -> String,
+{
+    let mut result = String::with_capacity(html.len());
+    let mut map_fragment = |fragment: &str, result: &mut String| {
+        let mut last = 0;
+        for tag in HTML_TAG_REGEX.find_iter(fragment) {
+            result.push_str(&f(&fragment[last..tag.start()]));
+            result.push_str(tag.as_str());
+            last = tag.end();
+        }
+        result.push_str(&f(&fragment[last..]));
+    };
+
+    let mut last = 0;
+    for element in PRESERVED_ELEMENT_REGEX.find_iter(html) {
+        map_fragment(&html[last..element.start()], &mut result);
+        result.push_str(element.as_str());
+        last = element.end();
+    }
+    map_fragment(&html[last..], &mut result);
+    result
+}
+
+/// The emoji shortcodes recognised by [`process_emoji_shortcodes`].
+pub const EMOJI_SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("check", "✔️"),
+    ("clap", "👏"),
+    ("construction", "🚧"),
+    ("cry", "😢"),
+    ("eyes", "👀"),
+    ("fire", "🔥"),
+    ("grin", "😁"),
+    ("heart", "❤️"),
+    ("heavy_check_mark", "✔️"),
+    ("hourglass", "⌛"),
+    ("information_source", "ℹ️"),
+    ("joy", "😂"),
+    ("laughing", "😆"),
+    ("link", "🔗"),
+    ("lock", "🔒"),
+    ("memo", "📝"),
+    ("no_entry", "⛔"),
+    ("ok_hand", "👌"),
+    ("package", "📦"),
+    ("pencil", "📝"),
+    ("point_right", "👉"),
+    ("pray", "🙏"),
+    ("question", "❓"),
+    ("rocket", "🚀"),
+    ("sad", "😞"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("sparkles", "✨"),
+    ("star", "⭐"),
+    ("tada", "🎉"),
+    ("thinking", "🤔"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("warning", "⚠️"),
+    ("wave", "👋"),
+    ("white_check_mark", "✅"),
+    ("wink", "😉"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("zap", "⚡"),
+];
+
+lazy_static! {
+    static ref EMOJI_SHORTCODE_REGEX: Regex =
+        Regex::new(r":([a-z0-9_+\-]+):").unwrap();
+    static ref EMOJI_MAP: HashMap<&'static str, &'static str> =
+        EMOJI_SHORTCODES.iter().copied().collect();
+}
+
+/// Replaces GitHub-style emoji shortcodes such as `:rocket:` with the
+/// corresponding emoji.
+///
+/// Shortcodes inside tags and inside `<pre>` or `<code>` elements are left
+/// untouched, as are unknown shortcodes.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+///
+/// # Returns
+///
+/// The HTML string with known shortcodes replaced.
+pub fn process_emoji_shortcodes(html: &str) -> String {
+    map_text_outside_code(html, |text| {
+        EMOJI_SHORTCODE_REGEX
+            .replace_all(text, |caps: &regex::Captures| {
+                EMOJI_MAP.get(&caps[1]).map_or_else(
+                    || caps[0].to_string(),
+                    |e| e.to_string(),
+                )
+            })
+            .into_owned()
+    })
+}
+
+lazy_static! {
+    static ref KBD_SHORTCUT_REGEX: Regex =
+        Regex::new(r"\[\[([^\[\]]+)\]\]").unwrap();
+}
+
+/// Replaces keyboard shortcuts such as `[[Ctrl+C]]` with `<kbd>`
+/// elements, giving `<kbd>Ctrl</kbd>+<kbd>C</kbd>`.
+///
+/// Shortcuts inside tags and inside `<pre>` or `<code>` elements are left
+/// untouched, as are shortcuts with an empty key such as `[[Ctrl+]]`.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+///
+/// # Returns
+///
+/// The HTML string with shortcuts replaced.
+pub fn process_kbd_shortcuts(html: &str) -> String {
+    map_text_outside_code(html, |text| {
+        KBD_SHORTCUT_REGEX
+            .replace_all(text, |caps: &regex::Captures| {
+                let keys: Vec<&str> =
+                    caps[1].split('+').map(str::trim).collect();
+                if keys.iter().any(|key| key.is_empty()) {
+                    return caps[0].to_string();
+                }
+                keys.iter()
+                    .map(|key| format!("<kbd>{}</kbd>", key))
+                    .collect::<Vec<_>>()
+                    .join("+")
+            })
+            .into_owned()
+    })
+}
+
+lazy_static! {
+    static ref ABBREVIATION_SKIP_REGEX: Regex =
+        Regex::new(r"(?is)<a\b.*?</a>|<abbr\b.*?</abbr>").unwrap();
+}
+
+/// Wraps whole-word occurrences of the given abbreviations in `<abbr>`
+/// elements, such as `<abbr title="HyperText Markup Language">HTML</abbr>`.
+///
+/// Only text outside tags is matched; text inside `<pre>`, `<code>`,
+/// links and existing `<abbr>` elements is left untouched. Longer
+/// abbreviations take precedence over shorter ones they contain.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `abbreviations` - A map from each abbreviation to its expansion.
+/// * `every_occurrence` - Whether to wrap every occurrence of an
+///   abbreviation, rather than only the first in the document.
+///
+/// # Returns
+///
+/// The HTML string with abbreviations wrapped.
+pub fn process_abbreviations(
+    html: &str,
+    abbreviations: &HashMap<String, String>,
+    every_occurrence: bool,
+) -> String {
+    // Text in the HTML is already escaped, so match escaped keys.
+    let mut keys: Vec<(String, &str)> = abbreviations
+        .iter()
+        .filter(|(abbr, _)| !abbr.is_empty())
+        .map(|(abbr, title)| {
+            (
+                html_escape::encode_text(abbr).into_owned(),
+                title.as_str(),
+            )
+        })
+        .collect();
+    if keys.is_empty() {
+        return html.to_string();
+    }
+    keys.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(&b.0)));
+    let pattern = keys
+        .iter()
+        .map(|(abbr, _)| regex::escape(abbr))
+        .collect::<Vec<_>>()
+        .join("|");
+    let regex = match Regex::new(&pattern) {
+        Ok(regex) => regex,
+        Err(_) => return html.to_string(),
+    };
+    let titles: HashMap<&str, &str> = keys
+        .iter()
+        .map(|(abbr, title)| (abbr.as_str(), *title))
+        .collect();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut seen = HashSet::new();
+    let mut wrap = |text: &str| -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut last = 0;
+        for found in regex.find_iter(text) {
+            let before = text[..found.start()].chars().next_back();
+            let after = text[found.end()..].chars().next();
+            let whole_word = !before.map_or(false, is_word_char)
+                && !after.map_or(false, is_word_char);
+            if !whole_word
+                || (!every_occurrence
+                    && !seen.insert(found.as_str().to_string()))
+            {
+                continue;
+            }
+            result.push_str(&text[last..found.start()]);
+            result.push_str(&format!(
+                r#"<abbr title="{}">{}</abbr>"#,
+                html_escape::encode_double_quoted_attribute(
+                    titles[found.as_str()]
+                ),
+                found.as_str()
+            ));
+            last = found.end();
+        }
+        result.push_str(&text[last..]);
+        result
+    };
+
+    let mut result = String::with_capacity(html.len());
+    let mut last = 0;
+    for skipped in ABBREVIATION_SKIP_REGEX.find_iter(html) {
+        result.push_str(&map_text_outside_code(
+            &html[last..skipped.start()],
+            &mut wrap,
+        ));
+        result.push_str(skipped.as_str());
+        last = skipped.end();
+    }
+    result.push_str(&map_text_outside_code(&html[last..], &mut wrap));
+    result
+}
+
+lazy_static! {
+    static ref LINK_OPEN_REGEX: Regex =
+        Regex::new(r"(?i)<a(\s[^>]*)?>").unwrap();
+    static ref HREF_ATTR_REGEX: Regex =
+        Regex::new(r#"(?i)\shref\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+            .unwrap();
+    static ref TARGET_OR_REL_REGEX: Regex =
+        Regex::new(r"(?i)\s(?:target|rel)\s*=").unwrap();
+}
+
+/// Returns the lowercase host of an absolute `http`, `https` or
+/// protocol-relative URL, or `None` for any other URL.
+fn external_host(url: &str) -> Option<String> {
+    let lower = url.trim().to_ascii_lowercase();
+    let rest = lower
+        .strip_prefix("https://")
+        .or_else(|| lower.strip_prefix("http://"))
+        .or_else(|| lower.strip_prefix("//"))?;
+    let authority =
+        rest.split(|c| matches!(c, '/' | '?' | '#')).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    Some(host.to_string())
+}
+
+/// Adds `target="_blank"` and `rel="noopener noreferrer"` to links that
+/// point to another site.
+///
+/// A link is external when its `href` is an absolute `http`/`https` or
+/// protocol-relative URL whose host differs from the host of `base_url`.
+/// Relative links, fragment links and links that already have a `target`
+/// or `rel` attribute are left unchanged.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `base_url` - The site's own URL, used to recognise internal
+///   absolute links.
+///
+/// # Returns
+///
+/// The HTML string with external links updated.
+pub fn process_external_links(
+    html: &str,
+    base_url: Option<&str>,
+) -> String {
+    let base_host = base_url.and_then(external_host);
+
+    LINK_OPEN_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            let attrs = caps.get(1).map_or("", |m| m.as_str());
+            let href = HREF_ATTR_REGEX
+                .captures(attrs)
+                .and_then(|href| href.get(1).or_else(|| href.get(2)))
+                .map(|href| href.as_str());
+
+            let is_external = match href.and_then(external_host) {
+                Some(host) => {
+                    base_host.as_deref() != Some(host.as_str())
+                }
+                None => false,
+            };
+            if !is_external || TARGET_OR_REL_REGEX.is_match(attrs) {
+                return caps[0].to_string();
+            }
+
+            format!(
+                r#"<a{} target="_blank" rel="noopener noreferrer">"#,
+                attrs
+            )
+        })
+        .into_owned()
+}
+
+lazy_static! {
+    static ref IMG_TAG_REGEX: Regex =
+        Regex::new(r"(?i)<img\b([^>]*?)(\s*/?)>").unwrap();
+    static ref LOADING_ATTR_REGEX: Regex =
+        Regex::new(r"(?i)\sloading\s*=").unwrap();
+    static ref DECODING_ATTR_REGEX: Regex =
+        Regex::new(r"(?i)\sdecoding\s*=").unwrap();
+}
+
+/// Adds `loading="lazy"` and `decoding="async"` to every `<img>` that
+/// does not already set them.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+///
+/// # Returns
+///
+/// The HTML string with image attributes added.
+pub fn process_lazy_images(html: &str) -> String {
+    IMG_TAG_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            let attrs = &caps[1];
+            let mut extra = String::new();
+            if !LOADING_ATTR_REGEX.is_match(attrs) {
+                extra.push_str(r#" loading="lazy""#);
+            }
+            if !DECODING_ATTR_REGEX.is_match(attrs) {
+                extra.push_str(r#" decoding="async""#);
+            }
+            format!("<img{}{}{}>", attrs, extra, &caps[2])
+        })
+        .into_owned()
+}
+
+lazy_static! {
+    /// Matches an opening `<code>` tag without attributes, and the
+    /// `<pre>` tag directly before it, if any.
+    static ref INLINE_CODE_TAG_REGEX: Regex =
+        Regex::new(r"(<pre>)?<code>").unwrap();
+}
+
+/// Adds `class` to every inline `<code>` element without attributes.
+/// Code blocks, whose `<code>` directly follows a `<pre>`, are left
+/// unchanged.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `class` - The class applied to inline `<code>` elements.
+///
+/// # Returns
+///
+/// The HTML string with inline code classes added.
+pub fn process_inline_code(html: &str, class: &str) -> String {
+    INLINE_CODE_TAG_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            if caps.get(1).is_some() {
+                return caps[0].to_string();
+            }
+            format!(
+                r#"<code class="{}">"#,
+                html_escape::encode_double_quoted_attribute(class)
+            )
+        })
+        .into_owned()
+}
+
+lazy_static! {
+    static ref SRC_ATTR_REGEX: Regex =
+        Regex::new(r#"(?i)(\ssrc\s*=\s*)"([^"]*)""#).unwrap();
+    static ref WIDTH_ATTR_REGEX: Regex =
+        Regex::new(r"(?i)\swidth\s*=").unwrap();
+    static ref HEIGHT_ATTR_REGEX: Regex =
+        Regex::new(r"(?i)\sheight\s*=").unwrap();
+}
+
+/// Query parameters recognised as an image's width.
+const IMAGE_WIDTH_PARAMS: &[&str] = &["w", "width"];
+
+/// Query parameters recognised as an image's height.
+const IMAGE_HEIGHT_PARAMS: &[&str] = &["h", "height"];
+
+/// Adds `width` and `height` attributes to every `<img>` whose `src`
+/// carries them as query parameters, e.g. `photo.jpg?w=800&h=600`.
+///
+/// `w` and `width` set the width, `h` and `height` the height. Values
+/// that are not positive whole numbers are ignored, as are dimensions
+/// the image already sets as attributes.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `strip_params` - Whether to remove the parameters used from the
+///   `src` URL.
+///
+/// # Returns
+///
+/// The HTML string with image dimensions added.
+pub fn process_image_dimensions(
+    html: &str,
+    strip_params: bool,
+) -> String {
+    IMG_TAG_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            image_with_dimensions(&caps[1], &caps[2], strip_params)
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Rebuilds an `<img>` tag from its attributes and closing, adding the
+/// dimensions found in its `src`. Returns `None` if there are none.
+fn image_with_dimensions(
+    attrs: &str,
+    closing: &str,
+    strip_params: bool,
+) -> Option<String> {
+    let src = SRC_ATTR_REGEX.captures(attrs)?;
+    let url = html_escape::decode_html_entities(&src[2]);
+    let (path, rest) = url.split_once('?')?;
+    let (query, fragment) = match rest.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (rest, None),
+    };
+
+    let mut width = None;
+    let mut height = None;
+    let mut kept_params = Vec::new();
+    for param in query.split('&') {
+        let (name, value) =
+            param.split_once('=').unwrap_or((param, ""));
+        let dimension = Some(value)
+            .filter(|value| value.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|value| value.parse::<u32>().ok())
+            .filter(|&value| value > 0);
+        match dimension {
+            Some(value) if IMAGE_WIDTH_PARAMS.contains(&name) => {
+                width.get_or_insert(value);
+            }
+            Some(value) if IMAGE_HEIGHT_PARAMS.contains(&name) => {
+                height.get_or_insert(value);
+            }
+            _ => kept_params.push(param),
+        }
+    }
+    if width.is_none() && height.is_none() {
+        return None;
+    }
+
+    let mut extra = String::new();
+    if let Some(width) =
+        width.filter(|_| !WIDTH_ATTR_REGEX.is_match(attrs))
+    {
+        extra.push_str(&format!(r#" width="{}""#, width));
+    }
+    if let Some(height) =
+        height.filter(|_| !HEIGHT_ATTR_REGEX.is_match(attrs))
+    {
+        extra.push_str(&format!(r#" height="{}""#, height));
+    }
+
+    if !strip_params {
+        return Some(format!("<img{}{}{}>", attrs, extra, closing));
+    }
+    let mut url = path.to_string();
+    if !kept_params.is_empty() {
+        url.push('?');
+        url.push_str(&kept_params.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    let src_attr = src.get(0).unwrap();
+    Some(format!(
+        r#"<img{}{}"{}"{}{}{}>"#,
+        &attrs[..src_attr.start()],
+        &src[1],
+        html_escape::encode_double_quoted_attribute(&url),
+        &attrs[src_attr.end()..],
+        extra,
+        closing
+    ))
+}
+
+lazy_static! {
+    static ref FOOTNOTES_SECTION_REGEX: Regex = Regex::new(
+        r#"(?s)(<section class="footnotes"[^>]*>)\s*(?:<h2\b[^>]*>.*?</h2>\s*)?"#
+    )
+    .unwrap();
+}
+
+/// Places `heading` at the top of the footnotes section.
+///
+/// Any heading already at the top of the section is replaced. HTML
+/// without a `<section class="footnotes">` is returned unchanged.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `heading` - The heading text; it is HTML-escaped.
+///
+/// # Returns
+///
+/// The HTML string with the footnotes heading set.
+pub fn process_footnote_heading(html: &str, heading: &str) -> String {
+    FOOTNOTES_SECTION_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            format!(
+                "{}\n<h2 class=\"footnotes-heading\">{}</h2>\n",
+                &caps[1],
+                html_escape::encode_text(heading)
+            )
+        })
+        .into_owned()
+}
+
+/// The client-side library used to render math.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathRenderer {
+    /// KaTeX, which renders the raw TeX inside `.math` elements.
+    KaTeX,
+    /// MathJax, which finds math by its `\(...\)` and `\[...\]`
+    /// delimiters; these are added around the TeX.
+    MathJax,
+}
+
+lazy_static! {
+    static ref DISPLAY_MATH_PARAGRAPH_REGEX: Regex = Regex::new(
+        r#"(?s)<p><span data-math-style="display">(.*?)</span></p>"#
+    )
+    .unwrap();
+    static ref MATH_SPAN_REGEX: Regex = Regex::new(
+        r#"(?s)<span data-math-style="(inline|display)">(.*?)</span>"#
+    )
+    .unwrap();
+}
+
+/// Rewrites the math emitted by Comrak's `math_dollars` extension into
+/// the markup expected by `renderer`.
+///
+/// Inline math becomes `<span class="math inline">` and display math on
+/// its own line becomes `<div class="math display">`; display math inside
+/// a paragraph becomes `<span class="math display">`. The TeX source is
+/// kept as written.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `renderer` - The library that will render the math.
+///
+/// # Returns
+///
+/// The HTML string with math elements rewritten.
+pub fn process_math(html: &str, renderer: MathRenderer) -> String {
+    let delimit = |style: &str, tex: &str| match (renderer, style) {
+        (MathRenderer::KaTeX, _) => tex.to_string(),
+        (MathRenderer::MathJax, "inline") => format!("\\({}\\)", tex),
+        (MathRenderer::MathJax, _) => format!("\\[{}\\]", tex),
+    };
+
+    let html = DISPLAY_MATH_PARAGRAPH_REGEX.replace_all(
+        html,
+        |caps: &regex::Captures| {
+            format!(
+                r#"<div class="math display">{}</div>"#,
+                delimit("display", &caps[1])
+            )
+        },
+    );
+    MATH_SPAN_REGEX
+        .replace_all(&html, |caps: &regex::Captures| {
+            format!(
+                r#"<span class="math {}">{}</span>"#,
+                &caps[1],
+                delimit(&caps[1], &caps[2])
+            )
+        })
+        .into_owned()
+}
+
+/// Removes dangerous markup from HTML using an allowlist, keeping the
+/// markup this crate generates.
+///
+/// Scripts, event handler attributes and `javascript:` URLs are removed.
+/// Alerts, collapsible blocks, tables, footnotes, task lists, heading
+/// anchors and highlighted code are preserved. Inline `style` attributes
+/// are only kept on `<pre>` and `<span>` elements, and only with the
+/// colour, bold, italic and underline declarations the highlighter
+/// emits; `id` attributes are only kept on headings, footnotes and
+/// footnote references. Links receive `rel="noopener noreferrer"`.
+///
+/// Requires the `sanitize` feature.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to sanitize.
+///
+/// # Returns
+///
+/// The sanitized HTML string.
+#[cfg(feature = "sanitize")]
+pub fn sanitize_html(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tags(&["button", "input", "section", "tfoot"])
+        .add_generic_attributes(&[
+            "class",
+            "dir",
+            "role",
+            "aria-hidden",
+            "aria-label",
+        ])
+        .add_tag_attributes(
+            "a",
+            &[
+                "target",
+                "id",
+                "data-footnote-ref",
+                "data-footnote-backref",
+                "data-footnote-backref-idx",
+            ],
+        )
+        .add_tag_attributes("img", &["loading", "decoding"])
+        .add_tag_attributes("div", &["data-title"])
+        .add_tag_attributes("section", &["data-footnotes"])
+        .add_tag_attributes("li", &["id"])
+        .add_tag_attributes("ol", &["type"])
+        .add_tag_attributes("input", &["checked", "disabled"])
+        .add_tag_attribute_values("input", "type", &["checkbox"])
+        .add_tag_attributes(
+            "pre",
+            &[
+                "style",
+                "data-theme",
+                "data-highlight-error",
+                "spellcheck",
+            ],
+        )
+        .add_tag_attributes("span", &["style"])
+        .add_tag_attributes("h1", &["id"])
+        .add_tag_attributes("h2", &["id"])
+        .add_tag_attributes("h3", &["id"])
+        .add_tag_attributes("h4", &["id"])
+        .add_tag_attributes("h5", &["id"])
+        .add_tag_attributes("h6", &["id"])
+        .attribute_filter(|_, attribute, value| {
+            if attribute == "style" {
+                highlight_style(value).map(Cow::Owned)
+            } else {
+                Some(Cow::Borrowed(value))
+            }
+        })
+        .clean(html)
+        .to_string()
+}
+
+/// Keeps the declarations of a `style` attribute that the highlighter
+/// emits: hex colours, bold, italic and underline. Returns `None` if no
+/// declaration is left.
+#[cfg(feature = "sanitize")]
+fn highlight_style(style: &str) -> Option<String> {
+    let kept: Vec<String> = style
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim();
+            let allowed = match property.as_str() {
+                "color" | "background-color" => value
+                    .strip_prefix('#')
+                    .filter(|hex| matches!(hex.len(), 3 | 4 | 6 | 8))
+                    .map_or(false, |hex| {
+                        hex.chars().all(|c| c.is_ascii_hexdigit())
+                    }),
+                "font-weight" => value == "bold",
+                "font-style" => value == "italic",
+                "text-decoration" => value == "underline",
+                _ => false,
+            };
+            allowed.then(|| format!("{}:{};", property, value))
+        })
+        .collect();
+    if kept.is_empty() {
+        None
+    } else {
+        Some(kept.concat())
+    }
+}
+
+/// Options controlling how tables are enhanced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableOptions {
+    /// The classes applied to each `<table>` element.
+    pub classes: Vec<String>,
+    /// When set, a last body row whose first cell starts with this marker
+    /// is moved into a `<tfoot>`, and the marker is removed from its cells.
+    pub footer_marker: Option<String>,
+    /// Optional text direction, added as a `dir` attribute on the
+    /// `table-responsive` wrapper, or on the table without a wrapper.
+    pub direction: Option<Direction>,
+    /// The class of the `<div>` wrapping each table.
+    pub wrapper_class: String,
+    /// Whether to wrap each table in a `<div>` of class `wrapper_class`.
+    pub responsive_wrapper: bool,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            classes: vec!["table".to_string()],
+            footer_marker: None,
+            direction: None,
+            wrapper_class: "table-responsive".to_string(),
+            responsive_wrapper: true,
+        }
+    }
+}
+
+lazy_static! {
+    /// Matches a table body, capturing its rows.
+    static ref TBODY_REGEX: Regex =
+        Regex::new(r"(?s)<tbody>(.*?)</tbody>").unwrap();
+    /// Matches a body cell, capturing its attributes and content.
+    static ref TD_REGEX: Regex =
+        Regex::new(r"(?s)<td(\s[^>]*)?>(.*?)</td>").unwrap();
+    /// Matches an opening or closing `<table>` tag.
+    static ref TABLE_TAG_REGEX: Regex =
+        Regex::new(r"(?i)<table\b[^>]*>|</table\s*>").unwrap();
+    /// Matches a `class` attribute.
+    static ref CLASS_ATTR_REGEX: Regex =
+        Regex::new(r"(?i)(?:^|\s)class\s*=").unwrap();
+    /// Matches a header or body cell, capturing its tag and attributes.
+    /// Attributes must start with whitespace so `<thead>` is not mistaken
+    /// for a cell.
+    static ref TABLE_CELL_REGEX: Regex =
+        Regex::new(r"<(td|th)(\s[^>]*)?>").unwrap();
+}
+
+/// Moves the last body row of each table into a `<tfoot>` when its first
+/// cell starts with `marker`, stripping the marker from the row's cells.
+fn move_footer_rows(html: &str, marker: &str) -> String {
+    let marker = html_escape::encode_text(marker);
+    TBODY_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            let body = &caps[1];
+            let row_start = match body.rfind("<tr>") {
+                Some(row_start) => row_start,
+                None => return caps[0].to_string(),
+            };
+            let row = &body[row_start..];
+            let is_footer =
+                TD_REGEX.captures(row).map_or(false, |cell| {
+                    cell[2].trim_start().starts_with(marker.as_ref())
+                });
+            if !is_footer {
+                return caps[0].to_string();
+            }
+
+            let row =
+                TD_REGEX.replace_all(row, |cell: &regex::Captures| {
+                    let text = cell[2].trim_start();
+                    format!(
+                        "<td{}>{}</td>",
+                        cell.get(1).map_or("", |m| m.as_str()),
+                        text.strip_prefix(marker.as_ref())
+                            .map_or(text, str::trim_start)
+                    )
+                });
+            format!(
+                "<tbody>{}</tbody>\n<tfoot>\n{}\n</tfoot>",
+                &body[..row_start],
+                row.trim_end()
+            )
+        })
+        .to_string()
+}
+
+/// Classes added to the elements of definition lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionListClasses {
+    /// The classes applied to each `<dl>` element.
+    pub dl: Vec<String>,
+    /// The classes applied to each `<dt>` (term) element.
+    pub dt: Vec<String>,
+    /// The classes applied to each `<dd>` (definition) element.
+    pub dd: Vec<String>,
+}
+
+impl Default for DefinitionListClasses {
+    fn default() -> Self {
+        Self {
+            dl: vec!["dl-horizontal".to_string()],
+            dt: Vec::new(),
+            dd: Vec::new(),
+        }
+    }
+}
+
+/// The numbering style of ordered lists, emitted as a `type` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListStyle {
+    /// Decimal numbers (`type="1"`).
+    Decimal,
+    /// Lowercase letters (`type="a"`).
+    LowerAlpha,
+    /// Uppercase letters (`type="A"`).
+    UpperAlpha,
+    /// Lowercase Roman numerals (`type="i"`).
+    LowerRoman,
+}
+
+impl ListStyle {
+    /// Returns the value of the `type` attribute for this style.
+    pub fn as_type_attr(&self) -> &'static str {
+        match self {
+            ListStyle::Decimal => "1",
+            ListStyle::LowerAlpha => "a",
+            ListStyle::UpperAlpha => "A",
+            ListStyle::LowerRoman => "i",
+        }
+    }
+}
+
+lazy_static! {
+    /// Matches an opening `<ol>` tag.
+    static ref OL_TAG_REGEX: Regex =
+        Regex::new(r"(?i)<ol\b([^>]*)>").unwrap();
+    /// Matches a `type` attribute.
+    static ref TYPE_ATTR_REGEX: Regex =
+        Regex::new(r"(?i)\stype\s*=").unwrap();
+}
+
+/// Sets the numbering style of every ordered list, including nested
+/// lists, that does not already set a `type` attribute.
+///
+/// The list of notes in a footnotes section is left unchanged, so
+/// footnotes stay numbered to match their references.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `style` - The numbering style applied to `<ol>` elements.
+///
+/// # Returns
+///
+/// The HTML string with list styles added.
+pub fn process_ordered_lists(html: &str, style: ListStyle) -> String {
+    let footnote_lists: Vec<usize> = FOOTNOTES_SECTION_REGEX
+        .find_iter(html)
+        .map(|section| section.end())
+        .collect();
+    OL_TAG_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            if TYPE_ATTR_REGEX.is_match(&caps[1])
+                || footnote_lists
+                    .contains(&caps.get(0).unwrap().start())
+            {
+                return caps[0].to_string();
+            }
+            format!(
+                r#"<ol type="{}"{}>"#,
+                style.as_type_attr(),
+                &caps[1]
+            )
+        })
+        .into_owned()
+}
+
+lazy_static! {
+    /// Matches an opening `<dl>`, `<dt>` or `<dd>` tag without attributes.
+    static ref DEFINITION_LIST_TAG_REGEX: Regex =
+        Regex::new(r"<(dl|dt|dd)>").unwrap();
+}
+
+/// Adds classes to definition lists, such as those rendered by Comrak's
+/// `description_lists` extension.
+///
+/// Tags whose class list is empty are left unchanged.
+///
+/// # Arguments
+///
+/// * `html` - The HTML string to process.
+/// * `classes` - The classes applied to `<dl>`, `<dt>` and `<dd>`.
+///
+/// # Returns
+///
+/// The HTML string with definition list classes added.
+pub fn process_definition_lists(
+    html: &str,
+    classes: &DefinitionListClasses,
+) -> String {
+    DEFINITION_LIST_TAG_REGEX
+        .replace_all(html, |caps: &regex::Captures| {
+            let tag = &caps[1];
+            let classes = match tag {
+                "dl" => &classes.dl,
+                "dt" => &classes.dt,
+                _ => &classes.dd,
+            };
+            if classes.is_empty() {
+                return caps[0].to_string();
+            }
+            format!(
+                r#"<{} class="{}">"#,
+                tag,
+                html_escape::encode_double_quoted_attribute(
+                    &classes.join(" ")
+                )
+            )
+        })
+        .into_owned()
+}
+
+/// Returns whether `html` ends with the opening tag of a `<div>` whose
+/// class is `wrapper_class`, ignoring trailing whitespace.
+fn ends_with_wrapper(html: &str, wrapper_class: &str) -> bool {
+    let html = html.trim_end();
+    let prefix = format!(
+        r#"<div class="{}""#,
+        html_escape::encode_double_quoted_attribute(wrapper_class)
+    );
+    html.rfind(&prefix).map_or(false, |start| {
+        let rest = &html[start + prefix.len()..];
+        rest.ends_with('>')
+            && !rest[..rest.len() - 1].contains(['<', '>'])
+    })
+}
+
+/// Wraps each `<table>` without attributes in a div of class
+/// `options.wrapper_class` and gives it `options.classes`. Tables that
+/// already have attributes, such as `class="table"`, or that are already
+/// inside such a div are left unchanged. Without
+/// `options.responsive_wrapper`, tables only get their classes.
+///
+/// Opening and closing tags are paired as they nest, so a table inside
+/// a cell of another table gets its own, correctly closed wrapper.
+fn wrap_tables(html: &str, options: &TableOptions) -> String {
+    let classes = html_escape::encode_double_quoted_attribute(
+        &options.classes.join(" "),
+    )
+    .into_owned();
+    let open_tag = if options.responsive_wrapper {
+        format!(
+            r#"<div class="{}"{}><table class="{}">"#,
+            html_escape::encode_double_quoted_attribute(
+                &options.wrapper_class
+            ),
+            dir_attr(options.direction),
+            classes
+        )
+    } else {
+        format!(
+            r#"<table class="{}"{}>"#,
+            classes,
+            dir_attr(options.direction)
+        )
+    };
+    let mut result = String::with_capacity(html.len());
+    // Whether each currently open table was wrapped
+    let mut wrapped = Vec::new();
+    let mut last = 0;
+
+    for tag in TABLE_TAG_REGEX.find_iter(html) {
+        result.push_str(&html[last..tag.start()]);
+        if tag.as_str().starts_with("</") {
+            result.push_str(tag.as_str());
+            if wrapped.pop().unwrap_or(false) {
+                result.push_str("</div>");
+            }
+        } else if tag.as_str() == "<table>"
+            && !(options.responsive_wrapper
+                && ends_with_wrapper(&result, &options.wrapper_class))
+        {
+            result.push_str(&open_tag);
+            wrapped.push(options.responsive_wrapper);
+        } else {
+            result.push_str(tag.as_str());
+            wrapped.push(false);
+        }
+        last = tag.end();
+    }
+
+    result.push_str(&html[last..]);
+    result
+}
+
+/// Processes tables, enhancing them with responsive design and alignment classes.
+///
+/// # Arguments
+///
+/// * `table_html` - The HTML string representing the table.
+///
+/// # Returns
+///
+/// The enhanced HTML string.
+pub fn process_tables(table_html: &str) -> String {
+    process_tables_with_classes(table_html, &["table".to_string()])
+}
+
+/// Processes tables like [`process_tables`], using `classes` for the
+/// `class` attribute of each table instead of the default `table`.
+///
+/// # Arguments
+///
+/// * `table_html` - The HTML string representing the table.
+/// * `classes` - The classes applied to each `<table>` element.
+///
+/// # Returns
+///
+/// The enhanced HTML string.
+pub fn process_tables_with_classes(
+    table_html: &str,
+    classes: &[String],
+) -> String {
+    process_tables_with_options(
+        table_html,
+        &TableOptions {
+            classes: classes.to_vec(),
+            ..TableOptions::default()
+        },
+    )
+}
+
+lazy_static! {
+    /// Matches an `<!-- align: ... -->` comment and the table right
+    /// after it, capturing the alignment list and the table.
+    static ref ALIGN_COMMENT_REGEX: Regex = Regex::new(
+        r"(?is)<!--\s*align:([^>]*?)-->\s*(<table>.*?</table>)"
+    )
+    .unwrap();
+    /// Matches a table row.
+    static ref TABLE_ROW_REGEX: Regex =
+        Regex::new(r"(?s)<tr\b[^>]*>.*?</tr>").unwrap();
+    /// Matches a complete `align` attribute with its leading whitespace.
+    static ref ALIGN_ATTR_FULL_REGEX: Regex = Regex::new(
+        r#"(?i)\s+align\s*=\s*(?:"[^"]*"|'[^']*'|[^\s>]+)"#
+    )
+    .unwrap();
+}
+
+/// Applies `<!-- align: left,right,center -->` comments placed right
+/// before a table, replacing the `align` attribute of the cells in each
+/// listed column, and removes the comments.
+///
+/// Columns are listed in order; an empty or unknown entry, or a column
+/// past the end of the list, keeps the alignment Comrak gave it, and
+/// entries past the last column are ignored.
+fn apply_alignment_comments(html: &str) -> Cow<'_, str> {
+    ALIGN_COMMENT_REGEX.replace_all(html, |caps: &regex::Captures| {
+        let alignments: Vec<Option<ColumnAlignment>> = caps[1]
+            .split(',')
+            .map(|name| {
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "left" => Some(ColumnAlignment::Left),
+                    "center" => Some(ColumnAlignment::Center),
+                    "right" => Some(ColumnAlignment::Right),
+                    "justify" => Some(ColumnAlignment::Justify),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        TABLE_ROW_REGEX
+            .replace_all(&caps[2], |row: &regex::Captures| {
+                let mut column = 0;
+                TABLE_CELL_REGEX
+                    .replace_all(&row[0], |cell: &regex::Captures| {
+                        let alignment =
+                            alignments.get(column).copied().flatten();
+                        column += 1;
+                        match alignment {
+                            Some(alignment) => format!(
+                                r#"<{}{} align="{}">"#,
+                                &cell[1],
+                                ALIGN_ATTR_FULL_REGEX.replace_all(
+                                    cell.get(2)
+                                        .map_or("", |m| m.as_str()),
+                                    ""
+                                ),
+                                alignment.as_str()
+                            ),
+                            None => cell[0].to_string(),
+                        }
+                    })
+                    .into_owned()
+            })
+            .into_owned()
+    })
+}
+
+/// Processes tables like [`process_tables`], using the given options.
+///
+/// A `<!-- align: left,right,center -->` comment right before a table
+/// overrides the alignment of its columns by position, whatever the
+/// Markdown separator row says. Columns missing from the list, or with
+/// an empty entry, keep their alignment.
+///
+/// # Arguments
+///
+/// * `table_html` - The HTML string representing the table.
+/// * `table_options` - Options controlling how tables are enhanced.
+///
+/// # Returns
+///
+/// The enhanced HTML string.
+pub fn process_tables_with_options(
+    table_html: &str,
+    table_options: &TableOptions,
+) -> String {
+    let table_html = apply_alignment_comments(table_html);
+    let table_html = match &table_options.footer_marker {
+        Some(marker) => move_footer_rows(&table_html, marker),
+        None => table_html.into_owned(),
+    };
+
+    let table_html = wrap_tables(&table_html, table_options);
+
+    // Add alignment classes to header and body cells that have none.
+    let table_html = TABLE_CELL_REGEX.replace_all(
+        &table_html,
+        |caps: &regex::Captures| {
+            let tag = &caps[1];
+            let attrs = caps.get(2).map_or("", |m| m.as_str());
+            if CLASS_ATTR_REGEX.is_match(attrs) {
+                return caps[0].to_string();
+            }
+            format!(
+                r#"<{}{} class="{}">"#,
+                tag,
+                attrs,
+                ColumnAlignment::from_html_attr(attrs).to_css_class()
+            )
+        },
+    );
+
+    table_html.to_string()
+}
+
+/// Processes custom blocks in the Markdown content, such as note, warning, tip, info, important, and caution blocks.
+/// These custom blocks are represented by div elements with specific class names.
+/// The function replaces these div elements with corresponding Bootstrap alert elements.
+///
+/// # Arguments
+///
+/// * `content` - A string containing the Markdown content.
+///
+/// # Returns
+///
+/// A string containing the processed Markdown content with custom blocks replaced by Bootstrap alert elements.
+pub fn process_custom_blocks(content: &str) -> String {
+    process_custom_blocks_with_registry(
+        content,
+        &CustomBlockRegistry::default(),
+    )
+}
+
+/// Processes custom blocks in the Markdown content using the block types
+/// registered in `registry`.
+///
+/// Nested `div` elements are balanced, so a block ends at its own closing
+/// tag and may contain arbitrary markup.
+///
+/// # Arguments
+///
+/// * `content` - A string containing the Markdown content.
+/// * `registry` - The registry used to resolve block class names.
+///
+/// # Returns
+///
+/// A string containing the processed Markdown content with custom blocks replaced by Bootstrap alert elements.
+pub fn process_custom_blocks_with_registry(
+    content: &str,
+    registry: &CustomBlockRegistry,
+) -> String {
+    process_custom_blocks_with_options(
+        content,
+        registry,
+        &CustomBlockOptions::default(),
+    )
+}
+
+/// Processes custom blocks in the Markdown content using the block types
+/// registered in `registry` and the given rendering options.
+///
+/// The content of each block is copied as is, so whitespace inside
+/// `<pre>` and `<code>` elements is preserved byte for byte.
+///
+/// # Arguments
+///
+/// * `content` - A string containing the Markdown content.
+/// * `registry` - The registry used to resolve block class names.
+/// * `block_options` - Options controlling how blocks are rendered.
+///
+/// # Returns
+///
+/// A string containing the processed Markdown content with custom blocks replaced by Bootstrap alert elements.
+pub fn process_custom_blocks_with_options(
+    content: &str,
+    registry: &CustomBlockRegistry,
+    block_options: &CustomBlockOptions,
+) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut position = 0;
+    let div_pairs = match_div_tags(content);
+
+    // Matches any block type (including unknown ones)
+    while let Some(caps) =
+        CUSTOM_BLOCK_OPEN_REGEX.captures_at(content, position)
+    {
+        let open = caps.get(0).unwrap();
+        // Use the precomputed pairing unless the opening tag was split
+        // differently, e.g. by a `>` inside a quoted attribute.
+        let close = match div_pairs.get(&open.start()) {
+            Some(&(open_end, close)) if open_end == open.end() => close,
+            _ => find_matching_div_close(content, open.end()),
+        };
+        match close {
+            Some((body_end, close_end)) => {
+                let class_attr = caps
+                    .get(1)
+                    .or_else(|| caps.get(2))
+                    .or_else(|| caps.get(3))
+                    .map_or("", |class| class.as_str());
+                let title = caps.get(4).or_else(|| caps.get(5));
+                let body = &content[open.end()..body_end];
+                let body = if block_options.escape_content {
+                    html_escape::encode_text(body)
+                } else {
+                    Cow::Borrowed(body)
+                };
+                match render_custom_block(
+                    class_attr,
+                    title.map(|title| title.as_str()),
+                    &body,
+                    registry,
+                    block_options,
+                ) {
+                    Some(block) => {
+                        output
+                            .push_str(&content[position..open.start()]);
+                        output.push_str(&block);
+                        position = close_end;
+                    }
+                    None => {
+                        // Unknown block passed through: keep the opening
+                        // tag and carry on with the blocks it contains
+                        output.push_str(&content[position..open.end()]);
+                        position = open.end();
+                    }
+                }
+            }
+            None => {
+                // Unbalanced block: leave the opening tag untouched
+                output.push_str(&content[position..open.end()]);
+                position = open.end();
+            }
+        }
+    }
+
+    output.push_str(&content[position..]);
+    output
+}
+
+/// Converts GitHub-style alert blockquotes (`> [!NOTE]`, `> [!TIP]`,
+/// `> [!IMPORTANT]`, `> [!WARNING]` and `> [!CAUTION]`) into the same
+/// alert HTML as the equivalent `<div class="...">` custom block.
+///
+/// Blockquotes without a marker are left unchanged.
+///
+/// # Arguments
+///
+/// * `content` - A string containing the rendered HTML.
+/// * `registry` - The registry used to resolve the alert types.
+/// * `block_options` - Options controlling how alerts are rendered.
+///
+/// # Returns
+///
+/// A string containing the HTML with alert blockquotes replaced.
+pub fn process_github_alerts(
+    content: &str,
+    registry: &CustomBlockRegistry,
+    block_options: &CustomBlockOptions,
+) -> String {
+    GITHUB_ALERT_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            // Re-open the paragraph the marker was removed from, dropping
+            // it entirely if the marker was alone on its line.
+            let body = format!("<p>{}", &caps[2]);
+            let body = body.strip_prefix("<p></p>").unwrap_or(&body);
+            render_custom_block(
+                &caps[1],
+                None,
+                body.trim(),
+                registry,
+                block_options,
+            )
+            .unwrap_or_else(|| caps[0].to_string())
+        })
+        .to_string()
+}
+
+/// Pairs every `<div>` in `content` with the `</div>` that closes it, in a
+/// single pass.
+///
+/// Maps the offset of each opening tag to the offset of its end and, if
+/// the div is closed, the start and end offsets of the closing tag.
+fn match_div_tags(
+    content: &str,
+) -> HashMap<usize, (usize, Option<(usize, usize)>)> {
+    let mut pairs = HashMap::new();
+    let mut open_tags = Vec::new();
+    for tag in DIV_TAG_REGEX.captures_iter(content) {
+        let tag_match = tag.get(0).unwrap();
+        if tag[1].is_empty() {
+            open_tags.push((tag_match.start(), tag_match.end()));
+        } else if let Some((start, end)) = open_tags.pop() {
+            pairs.insert(
+                start,
+                (end, Some((tag_match.start(), tag_match.end()))),
+            );
+        }
+    }
+    for (start, end) in open_tags {
+        pairs.insert(start, (end, None));
+    }
+    pairs
+}
+
+/// Returns the lowercase type and the title of the registered custom
+/// block opened by the `<div>` at the start of `html`, if any.
+pub(crate) fn custom_block_type(
+    html: &str,
+    registry: &CustomBlockRegistry,
+) -> Option<(String, Option<String>)> {
+    let caps = CUSTOM_BLOCK_OPEN_REGEX.captures(html.trim_start())?;
+    if caps.get(0)?.start() != 0 {
+        return None;
+    }
+    let class_attr = caps
+        .get(1)
+        .or_else(|| caps.get(2))
+        .or_else(|| caps.get(3))
+        .map_or("", |class| class.as_str());
+    let block_type = class_attr
+        .split_whitespace()
+        .find(|class| registry.get(class).is_some())?;
+    let title = caps.get(4).or_else(|| caps.get(5)).map(|title| {
+        html_escape::decode_html_entities(title.as_str()).into_owned()
+    });
+    Some((block_type.to_lowercase(), title))
+}
+
+/// Finds the `</div>` that closes a div whose body starts at `body_start`,
+/// skipping over nested divs.
+///
+/// Returns the byte offsets of the start and end of the closing tag.
+fn find_matching_div_close(
+    content: &str,
+    body_start: usize,
+) -> Option<(usize, usize)> {
+    let mut depth = 1;
+    for tag in DIV_TAG_REGEX.captures_iter(&content[body_start..]) {
+        if tag[1].is_empty() {
+            depth += 1;
+        } else {
+            depth -= 1;
+            if depth == 0 {
+                let close = tag.get(0).unwrap();
+                return Some((
+                    body_start + close.start(),
+                    body_start + close.end(),
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// Renders a single custom block. If its type is not registered, the
+/// result depends on `block_options.unknown_blocks`; `None` means the
+/// block should be left untouched.
+///
+/// The first class in `class_attr` that is a registered block type
+/// selects the block; a `collapse` class makes this block collapsible and
+/// any other classes are kept on the rendered element. A `title`
+/// overrides the registered title.
+fn render_custom_block(
+    class_attr: &str,
+    title: Option<&str>,
+    block_content: &str,
+    registry: &CustomBlockRegistry,
+    block_options: &CustomBlockOptions,
+) -> Option<String> {
+    let classes: Vec<&str> = class_attr.split_whitespace().collect();
+    let block = classes
+        .iter()
+        .enumerate()
+        .find_map(|(i, class)| registry.get(class).map(|def| (i, def)));
+
+    match block {
+        Some((type_index, definition)) => {
+            let mut collapsible = block_options.collapsible;
+            let mut extra_classes = Vec::new();
+            for (i, class) in classes.iter().enumerate() {
+                if i == type_index {
+                    continue;
+                } else if class.eq_ignore_ascii_case("collapse") {
+                    collapsible = true;
+                } else {
+                    extra_classes.push(*class);
+                }
+            }
+
+            // Decode first so existing entities are not escaped twice
+            let title = title.map(|title| {
+                html_escape::encode_text(
+                    &html_escape::decode_html_entities(title),
+                )
+                .into_owned()
+            });
+            let role = block_options
+                .role_overrides
+                .get(&classes[type_index].to_lowercase())
+                .unwrap_or(&definition.role);
+            Some(generate_custom_block_html(
+                definition,
+                role,
+                &extra_classes,
+                title.as_deref().unwrap_or(&definition.title),
+                block_content,
+                collapsible,
+                block_options,
+            ))
+        }
+        None => match block_options.unknown_blocks {
+            UnknownBlockBehavior::RenderError => Some(format!(
+                r#"<div class="{} {}" role="alert"><strong>Error:</strong> {}</div>"#,
+                alert_prefix(block_options),
+                prefixed_class("alert-danger", block_options),
+                MarkdownError::CustomBlockError(format!(
+                    "Unknown block type: {}",
+                    class_attr
+                ))
+            )),
+            UnknownBlockBehavior::PassThrough => None,
+            UnknownBlockBehavior::Strip => Some(String::new()),
+        },
+    }
+}
+
+/// Returns the HTML-escaped class prefix of custom blocks, `alert` by
+/// default.
+fn alert_prefix(block_options: &CustomBlockOptions) -> Cow<'_, str> {
+    html_escape::encode_double_quoted_attribute(
+        block_options.alert_prefix.as_deref().unwrap_or("alert"),
+    )
+}
+
+/// Replaces the leading `alert` of a class such as `alert-info` with the
+/// configured prefix. Other classes are returned unchanged.
+fn prefixed_class(
+    class: &str,
+    block_options: &CustomBlockOptions,
+) -> String {
+    match class.strip_prefix("alert") {
+        Some(rest) if rest.is_empty() || rest.starts_with('-') => {
+            format!("{}{}", alert_prefix(block_options), rest)
+        }
+        _ => class.to_string(),
+    }
+}
+
+/// Generates the HTML for a custom block based on its definition and content.
+///
+/// # Arguments
+///
+/// * `definition` - The definition of the custom block.
+/// * `role` - The ARIA role of the block element.
+/// * `extra_classes` - Additional classes for the block element.
+/// * `title` - The title to display, already HTML-escaped.
+/// * `block_content` - The content inside the custom block.
+/// * `collapsible` - Whether to render a collapsible `<details>` element.
+/// * `block_options` - Options controlling how the block is rendered.
+///
+/// # Returns
+///
+/// A string containing the HTML for the custom block.
+fn generate_custom_block_html(
+    definition: &CustomBlockDefinition,
+    role: &str,
+    extra_classes: &[&str],
+    title: &str,
+    block_content: &str,
+    collapsible: bool,
+    block_options: &CustomBlockOptions,
+) -> String {
+    let mut class = format!(
+        "{} {}",
+        alert_prefix(block_options),
+        prefixed_class(&definition.alert_class, block_options)
+    );
+    for extra in extra_classes {
+        class.push(' ');
+        class.push_str(&html_escape::encode_double_quoted_attribute(
+            extra,
+        ));
+    }
+
+    let dir = dir_attr(block_options.direction);
+    let icon = if block_options.icons {
+        format!(
+            r#"<span class="{} {}" aria-hidden="true"></span>"#,
+            prefixed_class("alert-icon", block_options),
+            definition.icon_class
+        )
+    } else {
+        String::new()
+    };
+
+    if collapsible {
+        return format!(
+            r#"<details class="{}"{}><summary>{}<strong>{}:</strong></summary> {}</details>"#,
+            class, dir, icon, title, block_content
+        );
+    }
+
+    let element = if block_options.aside_for_notes && role == "note" {
+        "aside"
+    } else {
+        "div"
+    };
+    format!(
+        r#"<{} class="{}" role="{}"{}>{}<strong>{}:</strong> {}</{}>"#,
+        element,
+        class,
+        html_escape::encode_double_quoted_attribute(role),
+        dir,
+        icon,
+        title,
+        block_content,
+        element
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_custom_blocks() {
+        let input = r#"
+            <div class="note">This is a note.</div>
+            <div class="WARNING">This is a warning.</div>
+            <div class="Tip">This is a tip.</div>
+            <div class="INFO">This is an info block.</div>
+            <div class="Important">This is important.</div>
+            <div class="caution">This is a caution.</div>
+        "#;
+
+        let processed = process_custom_blocks(input);
+
+        assert!(processed.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> This is a note.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong> This is a warning.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-success" role="note"><strong>Tip:</strong> This is a tip.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-primary" role="note"><strong>Info:</strong> This is an info block.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-danger" role="alert"><strong>Important:</strong> This is important.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-secondary" role="alert"><strong>Caution:</strong> This is a caution.</div>"#));
+    }
+
+    #[test]
+    fn test_process_adjacent_custom_blocks() {
+        let input = "<div class=\"note\">First.</div>\n<div class=\"tip\">Second\nline.</div>\n<div class=\"warning\">Third.</div>\n<div class=\"note\">A.</div><div class=\"caution\">B.</div>";
+
+        let processed = process_custom_blocks(input);
+
+        assert!(processed.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> First.</div>"#));
+        assert!(processed.contains("<div class=\"alert alert-success\" role=\"note\"><strong>Tip:</strong> Second\nline.</div>"));
+        assert!(processed.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong> Third.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> A.</div><div class="alert alert-secondary" role="alert"><strong>Caution:</strong> B.</div>"#));
+        assert_eq!(processed.matches("role=").count(), 5);
+    }
+
+    #[test]
+    fn test_process_custom_block_with_nested_html() {
+        let input = r#"<div class="note"><div class="inner"><p>Nested</p></div><ul><li>one</li></ul></div><p>After</p>"#;
+
+        let processed = process_custom_blocks(input);
+
+        assert_eq!(
+            processed,
+            r#"<div class="alert alert-info" role="note"><strong>Note:</strong> <div class="inner"><p>Nested</p></div><ul><li>one</li></ul></div><p>After</p>"#
+        );
+    }
+
+    #[test]
+    fn test_process_unbalanced_custom_block() {
+        let input = r#"<div class="note">Never closed"#;
+        assert_eq!(process_custom_blocks(input), input);
+    }
+
+    #[test]
+    fn test_process_github_alerts() {
+        let registry = CustomBlockRegistry::default();
+        for (marker, alert_class, role, title) in [
+            ("NOTE", "alert-info", "note", "Note"),
+            ("TIP", "alert-success", "note", "Tip"),
+            ("IMPORTANT", "alert-danger", "alert", "Important"),
+            ("WARNING", "alert-warning", "alert", "Warning"),
+            ("caution", "alert-secondary", "alert", "Caution"),
+        ] {
+            let input = format!(
+                "<blockquote>\n<p>[!{}]\nBody text</p>\n</blockquote>\n",
+                marker
+            );
+            let processed = process_github_alerts(
+                &input,
+                &registry,
+                &CustomBlockOptions::default(),
+            );
+            assert_eq!(
+                processed,
+                format!(
+                    "<div class=\"alert {}\" role=\"{}\"><strong>{}:</strong> <p>Body text</p></div>\n",
+                    alert_class, role, title
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_process_github_alerts_with_separate_paragraph() {
+        let input =
+            "<blockquote>\n<p>[!TIP]</p>\n<p>para</p>\n</blockquote>";
+        let processed = process_github_alerts(
+            input,
+            &CustomBlockRegistry::default(),
+            &CustomBlockOptions::default(),
+        );
+        assert_eq!(
+            processed,
+            r#"<div class="alert alert-success" role="note"><strong>Tip:</strong> <p>para</p></div>"#
+        );
+    }
+
+    #[test]
+    fn test_process_github_alerts_leaves_plain_blockquotes() {
+        let input = "<blockquote>\n<p>quote</p>\n</blockquote>\n";
+        assert_eq!(
+            process_github_alerts(
+                input,
+                &CustomBlockRegistry::default(),
+                &CustomBlockOptions::default(),
+            ),
+            input
+        );
+    }
+
+    #[test]
+    fn test_process_collapsible_custom_blocks() {
+        let registry = CustomBlockRegistry::default();
+        let collapsible = CustomBlockOptions {
+            collapsible: true,
+            ..CustomBlockOptions::default()
+        };
+
+        let processed = process_custom_blocks_with_options(
+            r#"<div class="warning">Long text.</div>"#,
+            &registry,
+            &collapsible,
+        );
+        assert_eq!(
+            processed,
+            r#"<details class="alert alert-warning"><summary><strong>Warning:</strong></summary> Long text.</details>"#
+        );
+
+        let processed = process_custom_blocks(
+            r#"<div class="note collapse">Hidden.</div><div class="tip">Shown.</div>"#,
+        );
+        assert!(processed.contains(r#"<details class="alert alert-info"><summary><strong>Note:</strong></summary> Hidden.</details>"#));
+        assert!(processed.contains(r#"<div class="alert alert-success" role="note"><strong>Tip:</strong> Shown.</div>"#));
+    }
+
+    #[test]
+    fn test_process_custom_block_with_custom_title() {
+        let processed = process_custom_blocks(
+            r#"<div class="note" data-title="Heads up">Read this.</div>"#,
+        );
+        assert_eq!(
+            processed,
+            r#"<div class="alert alert-info" role="note"><strong>Heads up:</strong> Read this.</div>"#
+        );
+
+        let processed = process_custom_blocks(
+            r#"<div class="tip" data-title='<script>x</script> &amp; co'>Careful.</div>"#,
+        );
+        assert!(processed.contains(
+            "<strong>&lt;script&gt;x&lt;/script&gt; &amp; co:</strong> Careful."
+        ));
+    }
+
+    #[test]
+    fn test_process_custom_block_default_title_unchanged() {
+        assert_eq!(
+            process_custom_blocks(r#"<div class="note">Plain.</div>"#),
+            r#"<div class="alert alert-info" role="note"><strong>Note:</strong> Plain.</div>"#
+        );
+    }
+
+    #[test]
+    fn test_process_custom_blocks_with_icons() {
+        let icons = CustomBlockOptions {
+            icons: true,
+            ..CustomBlockOptions::default()
+        };
+        let processed = process_custom_blocks_with_options(
+            r#"<div class="warning">Hot.</div>"#,
+            &CustomBlockRegistry::default(),
+            &icons,
+        );
+        assert_eq!(
+            processed,
+            r#"<div class="alert alert-warning" role="alert"><span class="alert-icon icon-alert" aria-hidden="true"></span><strong>Warning:</strong> Hot.</div>"#
+        );
+
+        let processed =
+            process_custom_blocks(r#"<div class="warning">Hot.</div>"#);
+        assert!(!processed.contains("alert-icon"));
+    }
+
+    #[test]
+    fn test_unknown_custom_block() {
+        let input = r#"<div class="unknown">This is an unknown block type.</div>"#;
+        let processed = process_custom_blocks(input);
+
+        // Print the processed output to verify the content
+        println!("Processed content: {}", processed);
+
+        // Check if the error is correctly reported in the output
+        assert!(processed.contains(r#"Failed to process custom block: Unknown block type: unknown"#), "Expected error message for unknown block type not found");
+    }
+
+    #[test]
+    fn test_process_custom_blocks_with_registry() {
+        let mut registry = CustomBlockRegistry::new();
+        registry.register("Success", "alert-success", "Success");
+
+        let processed = process_custom_blocks_with_registry(
+            r#"<div class="success">Done.</div><div class="note">Kept.</div>"#,
+            &registry,
+        );
+        assert!(processed.contains(r#"<div class="alert alert-success" role="note"><strong>Success:</strong> Done.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> Kept.</div>"#));
+
+        let processed = process_custom_blocks_with_registry(
+            r#"<div class="note">Gone.</div>"#,
+            &CustomBlockRegistry::empty(),
+        );
+        assert!(processed.contains("Unknown block type: note"));
+    }
+
+    #[test]
+    fn test_process_tables() {
+        let input = r#"<table><tr><td align="center">Center</td><td align="right">Right</td><td>Left</td></tr></table>"#;
+
+        let processed = process_tables(input);
+
+        assert!(processed.contains(
+            r#"<div class="table-responsive"><table class="table">"#
+        ));
+        assert!(processed.contains(
+            r#"<td align="center" class="text-center">Center</td>"#
+        ));
+        assert!(processed.contains(
+            r#"<td align="right" class="text-right">Right</td>"#
+        ));
+        assert!(
+            processed.contains(r#"<td class="text-left">Left</td>"#)
+        );
+        assert!(processed.contains("</table></div>"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_syntax_theme_names() {
+        let themes = list_available_themes();
+        for theme in [
+            SyntaxTheme::Base16OceanDark,
+            SyntaxTheme::Base16OceanLight,
+            SyntaxTheme::InspiredGitHub,
+            SyntaxTheme::SolarizedDark,
+            SyntaxTheme::SolarizedLight,
+        ] {
+            assert!(
+                themes.contains(&theme.as_name().to_string()),
+                "{:?} is not bundled",
+                theme
+            );
+        }
+        assert_eq!(
+            SyntaxTheme::Base16OceanDark.as_name(),
+            DEFAULT_THEME
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_list_available_themes() {
+        let themes = list_available_themes();
+        assert!(themes.contains(&DEFAULT_THEME.to_string()));
+        assert!(themes.contains(&"Solarized (light)".to_string()));
+        assert!(themes.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_list_available_languages() {
+        let languages = list_available_languages();
+        assert!(languages.contains(&"Rust".to_string()));
+        assert!(languages.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_load_theme_from_missing_file() {
+        let result = load_theme_from_file("does/not/exist.tmTheme");
+        assert!(matches!(
+            result,
+            Err(MarkdownError::SyntaxSetError(ref msg)) if msg.contains("does/not/exist.tmTheme")
+        ));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_load_syntax_set_with_missing_dir() {
+        let result = load_syntax_set_with_extras("does/not/exist");
+        assert!(matches!(
+            result,
+            Err(MarkdownError::SyntaxSetError(ref msg)) if msg.contains("does/not/exist")
+        ));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_find_syntax_with_aliases() {
+        let mut aliases = HashMap::new();
+        aliases.insert("rs".to_string(), "Python".to_string());
+
+        let syntax = find_syntax(&SYNTAX_SET, "rs", &aliases).unwrap();
+        assert_eq!(syntax.name, "Python");
+
+        let syntax =
+            find_syntax(&SYNTAX_SET, "shell", &HashMap::new()).unwrap();
+        assert_eq!(syntax.name, "Bourne Again Shell (bash)");
+
+        assert!(find_syntax(&SYNTAX_SET, "nolang", &HashMap::new())
+            .is_none());
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_inline_code() {
+        let html =
+            highlight_inline_code("let x = 1;\n", "rust").unwrap();
+        assert!(html.starts_with(r#"<code class="language-rust">"#));
+        assert!(html.ends_with("</code>"));
+        assert!(!html.contains("<pre"));
+        assert!(!html.contains('\n'));
+        assert!(html.contains("<span style=\"color:"));
+    }
+
+    #[test]
+    fn test_process_tables_header_alignment() {
+        let input = r#"<table><thead><tr><th align="left">L</th><th align="center">C</th><th align="right">R</th><th>N</th></tr></thead></table>"#;
+
+        let processed = process_tables(input);
+
+        assert!(processed.contains("<thead>"));
+        assert!(processed
+            .contains(r#"<th align="left" class="text-left">L</th>"#));
+        assert!(processed.contains(
+            r#"<th align="center" class="text-center">C</th>"#
+        ));
+        assert!(processed.contains(
+            r#"<th align="right" class="text-right">R</th>"#
+        ));
+        assert!(processed.contains(r#"<th class="text-left">N</th>"#));
+    }
+
+    #[test]
+    fn test_process_tables_with_classes() {
+        let processed = process_tables_with_classes(
+            "<table><tr><td>1</td></tr></table>",
+            &["table".to_string(), "table-striped".to_string()],
+        );
+        assert!(processed.contains(
+            r#"<div class="table-responsive"><table class="table table-striped">"#
+        ));
+    }
+
+    #[test]
+    fn test_extract_and_insert_table_captions() {
+        let markdown = "[Table: Q1 <results>]\n| A |\n|---|\n\n```\n[Table: code]\n| x |\n```\n";
+
+        let (rewritten, captions) = extract_table_captions(markdown);
+        assert_eq!(captions, vec!["Q1 <results>".to_string()]);
+        assert!(rewritten
+            .starts_with("<!-- mdx-gen-table-caption:0 -->\n| A |"));
+        assert!(rewritten.contains("[Table: code]"));
+
+        let html = insert_table_captions(
+            "<!-- mdx-gen-table-caption:0 -->\n<table>\n<thead>",
+            &captions,
+        );
+        assert_eq!(
+            html,
+            "<table>\n<caption>Q1 &lt;results&gt;</caption>\n<thead>"
+        );
+    }
+
+    #[test]
+    fn test_process_tables_with_footer_marker() {
+        let input = "<table>\n<tbody>\n<tr>\n<td>Apples</td>\n<td align=\"right\">3</td>\n</tr>\n<tr>\n<td>= Total</td>\n<td align=\"right\">=3</td>\n</tr>\n</tbody>\n</table>";
+        let options = TableOptions {
+            footer_marker: Some("=".to_string()),
+            ..TableOptions::default()
+        };
+
+        let processed = process_tables_with_options(input, &options);
+
+        assert!(processed.contains("<tfoot>\n<tr>\n<td class=\"text-left\">Total</td>\n<td align=\"right\" class=\"text-right\">3</td>\n</tr>\n</tfoot>"));
+        assert!(processed.contains("</tbody>\n<tfoot>"));
+        assert!(
+            processed.contains("<td class=\"text-left\">Apples</td>")
+        );
+    }
+
+    #[test]
+    fn test_process_tables_alignment_comment() {
+        let input = "<!-- align: right,,left,center -->\n<table>\n<thead>\n<tr>\n<th>A</th>\n<th align=\"center\">B</th>\n<th align=\"right\">C</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td>1</td>\n<td align=\"center\">2</td>\n<td align=\"right\">3</td>\n</tr>\n</tbody>\n</table>";
+        let processed = process_tables(input);
+
+        assert!(!processed.contains("<!--"));
+        assert!(processed.starts_with(
+            r#"<div class="table-responsive"><table class="table">"#
+        ));
+        assert!(processed.contains(
+            r#"<th align="right" class="text-right">A</th>"#
+        ));
+        assert!(processed.contains(
+            r#"<td align="right" class="text-right">1</td>"#
+        ));
+        assert!(processed.contains(
+            r#"<td align="center" class="text-center">2</td>"#
+        ));
+        assert!(processed
+            .contains(r#"<td align="left" class="text-left">3</td>"#));
+
+        // Without a comment, Comrak's alignment is kept
+        let plain = process_tables(input.split_once('\n').unwrap().1);
+        assert!(plain.contains(r#"<td class="text-left">1</td>"#));
+        assert!(plain.contains(
+            r#"<td align="right" class="text-right">3</td>"#
+        ));
+    }
+
+    #[test]
+    fn test_process_tables_without_responsive_wrapper() {
+        let input = "<table>\n<tbody>\n<tr>\n<td>1</td>\n</tr>\n</tbody>\n</table>\n<p>After</p>";
+        let options = TableOptions {
+            responsive_wrapper: false,
+            ..TableOptions::default()
+        };
+
+        let processed = process_tables_with_options(input, &options);
+        assert!(processed.starts_with(r#"<table class="table">"#));
+        assert!(processed.contains("</table>\n<p>After</p>"));
+        assert!(!processed.contains("<div"));
+        assert!(!processed.contains("</div>"));
+    }
+
+    #[test]
+    fn test_process_tables_without_footer_row() {
+        let input = "<table>\n<tbody>\n<tr>\n<td>Apples</td>\n</tr>\n</tbody>\n</table>";
+        let options = TableOptions {
+            footer_marker: Some("=".to_string()),
+            ..TableOptions::default()
+        };
+
+        let processed = process_tables_with_options(input, &options);
+        assert!(!processed.contains("<tfoot>"));
+        assert_eq!(processed, process_tables(input));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Getting Started"), "getting-started");
+        assert_eq!(slugify("What's new in v2.0?"), "whats-new-in-v20");
+        assert_eq!(slugify("Ünïcödé  Héading"), "ünïcödé-héading");
+        assert_eq!(slugify("日本語 テキスト"), "日本語-テキスト");
+        assert_eq!(
+            slugify("<code>foo_bar</code> &amp; baz"),
+            "foo_bar-baz"
+        );
+    }
+
+    #[test]
+    fn test_minify_html() {
+        let html = "<h1>Title</h1>\n<p>Some <em>emphasised</em>\n  <strong>text</strong></p>\n<ul>\n<li>one</li>\n</ul>\n";
+        assert_eq!(
+            minify_html(html),
+            "<h1>Title</h1><p>Some <em>emphasised</em> <strong>text</strong></p><ul><li>one</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_minify_html_preserves_code() {
+        let html = "<p>Use <code>a  b</code> here</p>\n<pre><code>fn main() {\n    body();\n}\n</code></pre>\n<p>after</p>\n";
+        assert_eq!(
+            minify_html(html),
+            "<p>Use <code>a  b</code> here</p><pre><code>fn main() {\n    body();\n}\n</code></pre><p>after</p>"
+        );
+    }
+
+    #[test]
+    fn test_normalize_html_ignores_insignificant_whitespace() {
+        let compact = "<div class=\"note\"><p>Some <em>text</em></p><table><tr><td>1</td></tr></table></div>";
+        let spaced = "\n  <div class=\"note\">\n<p>Some\n<em>text</em></p>\n\n<table>\n  <tr>\n    <td>1</td>\n  </tr>\n</table>\n</div>\n";
+        assert_eq!(normalize_html(spaced), normalize_html(compact));
+        assert_eq!(
+            normalize_html(spaced),
+            "<div class=\"note\"><p>Some <em>text</em></p><table><tr><td>1</td></tr></table></div>"
+        );
+    }
+
+    #[test]
+    fn test_normalize_html_keeps_real_differences() {
+        assert_ne!(
+            normalize_html("<p>Some <em>text</em></p>"),
+            normalize_html("<p>Some<em>text</em></p>")
+        );
+        assert_ne!(
+            normalize_html("<p>One</p>\n<p>Two</p>"),
+            normalize_html("<p>One</p>\n<p>Too</p>")
+        );
+        assert_ne!(
+            normalize_html("<pre><code>a\n  b</code></pre>"),
+            normalize_html("<pre><code>a\nb</code></pre>")
+        );
+    }
+
+    #[test]
+    fn test_process_emoji_shortcodes() {
+        assert_eq!(
+            process_emoji_shortcodes("<p>Launch :rocket: now</p>"),
+            "<p>Launch 🚀 now</p>"
+        );
+        assert_eq!(
+            process_emoji_shortcodes(
+                "<p>:not_an_emoji: and :tada:</p>"
+            ),
+            "<p>:not_an_emoji: and 🎉</p>"
+        );
+        assert_eq!(
+            process_emoji_shortcodes(
+                r#"<p><a href="/a:rocket:b">x</a> <code>:rocket:</code></p>"#
+            ),
+            r#"<p><a href="/a:rocket:b">x</a> <code>:rocket:</code></p>"#
+        );
+    }
+
+    #[test]
+    fn test_process_abbreviations() {
+        let abbreviations = HashMap::from([
+            (
+                "HTML".to_string(),
+                "HyperText Markup Language".to_string(),
+            ),
+            (
+                "AT&T".to_string(),
+                "American Telephone & Telegraph".to_string(),
+            ),
+        ]);
+        assert_eq!(
+            process_abbreviations(
+                "<p>HTML and XHTML, then HTML again via AT&amp;T</p>",
+                &abbreviations,
+                false
+            ),
+            r#"<p><abbr title="HyperText Markup Language">HTML</abbr> and XHTML, then HTML again via <abbr title="American Telephone &amp; Telegraph">AT&amp;T</abbr></p>"#
+        );
+        assert_eq!(
+            process_abbreviations(
+                "<p>HTML, HTML</p>",
+                &abbreviations,
+                true
+            ),
+            r#"<p><abbr title="HyperText Markup Language">HTML</abbr>, <abbr title="HyperText Markup Language">HTML</abbr></p>"#
+        );
+        let untouched = r#"<p><a href="/html">HTML</a> <code>HTML</code> <abbr title="x">HTML</abbr></p><pre><code>HTML</code></pre>"#;
+        assert_eq!(
+            process_abbreviations(untouched, &abbreviations, true),
+            untouched
+        );
+    }
+
+    #[test]
+    fn test_process_kbd_shortcuts() {
+        assert_eq!(
+            process_kbd_shortcuts("<p>Press [[Enter]]</p>"),
+            "<p>Press <kbd>Enter</kbd></p>"
+        );
+        assert_eq!(
+            process_kbd_shortcuts("<p>Copy with [[Ctrl + Shift+C]]</p>"),
+            "<p>Copy with <kbd>Ctrl</kbd>+<kbd>Shift</kbd>+<kbd>C</kbd></p>"
+        );
+        assert_eq!(
+            process_kbd_shortcuts(
+                "<p>[[Ctrl+]] <code>[[Esc]]</code></p><pre><code>a[[0]]</code></pre>"
+            ),
+            "<p>[[Ctrl+]] <code>[[Esc]]</code></p><pre><code>a[[0]]</code></pre>"
+        );
+    }
+
+    #[test]
+    fn test_process_external_links() {
+        let html = r##"<a href="https://example.org/page">a</a> <a href="/docs">b</a> <a href="#top">c</a> <a href="https://mysite.com/x">d</a> <a href="http://other.com" rel="me">e</a>"##;
+        assert_eq!(
+            process_external_links(html, Some("https://MySite.com/")),
+            r##"<a href="https://example.org/page" target="_blank" rel="noopener noreferrer">a</a> <a href="/docs">b</a> <a href="#top">c</a> <a href="https://mysite.com/x">d</a> <a href="http://other.com" rel="me">e</a>"##
+        );
+    }
+
+    #[test]
+    fn test_external_host() {
+        assert_eq!(
+            external_host("https://user@Example.com:8080/path"),
+            Some("example.com".to_string())
+        );
+        assert_eq!(
+            external_host("//cdn.example.com"),
+            Some("cdn.example.com".to_string())
+        );
+        assert_eq!(external_host("mailto:me@example.com"), None);
+        assert_eq!(external_host("../relative"), None);
+    }
+
+    #[test]
+    fn test_strip_html_comments() {
+        let html = "<!-- TODO -->\n<p>Text<!-- inline --> here</p>\n<pre><!-- keep --></pre>\n<p><code><!-- keep too --></code></p>";
+        assert_eq!(
+            strip_html_comments(html),
+            "<p>Text here</p>\n<pre><!-- keep --></pre>\n<p><code><!-- keep too --></code></p>"
+        );
+    }
+
+    #[test]
+    fn test_process_ordered_lists() {
+        let html = "<ol start=\"3\">\n<li>One\n<ol>\n<li>Two</li>\n</ol>\n</li>\n</ol>\n<ol type=\"I\"><li>Three</li></ol>\n<ul><li>Four</li></ul>";
+        assert_eq!(
+            process_ordered_lists(html, ListStyle::LowerRoman),
+            "<ol type=\"i\" start=\"3\">\n<li>One\n<ol type=\"i\">\n<li>Two</li>\n</ol>\n</li>\n</ol>\n<ol type=\"I\"><li>Three</li></ol>\n<ul><li>Four</li></ul>"
+        );
+        let footnotes = "<section class=\"footnotes\" data-footnotes>\n<ol>\n<li id=\"fn-1\">\n<ol>\n<li>Nested</li>\n</ol>\n</li>\n</ol>\n</section>";
+        assert_eq!(
+            process_ordered_lists(footnotes, ListStyle::LowerRoman),
+            "<section class=\"footnotes\" data-footnotes>\n<ol>\n<li id=\"fn-1\">\n<ol type=\"i\">\n<li>Nested</li>\n</ol>\n</li>\n</ol>\n</section>"
+        );
+        assert_eq!(ListStyle::Decimal.as_type_attr(), "1");
+        assert_eq!(ListStyle::UpperAlpha.as_type_attr(), "A");
+    }
+
+    #[test]
+    fn test_process_definition_lists() {
+        let html =
+            "<dl>\n<dt>Term</dt>\n<dd>One.</dd>\n<dd>Two.</dd>\n</dl>";
+        assert_eq!(
+            process_definition_lists(
+                html,
+                &DefinitionListClasses::default()
+            ),
+            "<dl class=\"dl-horizontal\">\n<dt>Term</dt>\n<dd>One.</dd>\n<dd>Two.</dd>\n</dl>"
+        );
+
+        let classes = DefinitionListClasses {
+            dl: Vec::new(),
+            dt: vec!["term".to_string()],
+            dd: vec!["def".to_string(), "small".to_string()],
+        };
+        assert_eq!(
+            process_definition_lists(html, &classes),
+            "<dl>\n<dt class=\"term\">Term</dt>\n<dd class=\"def small\">One.</dd>\n<dd class=\"def small\">Two.</dd>\n</dl>"
+        );
+    }
+
+    #[test]
+    fn test_process_footnote_heading() {
+        let html = "<section class=\"footnotes\" data-footnotes>\n<h2>Footnotes</h2>\n<ol>\n</ol>\n</section>";
+        assert_eq!(
+            process_footnote_heading(html, "Notes & refs"),
+            "<section class=\"footnotes\" data-footnotes>\n<h2 class=\"footnotes-heading\">Notes &amp; refs</h2>\n<ol>\n</ol>\n</section>"
+        );
+
+        let html = "<section class=\"intro\"><h2>Intro</h2></section>";
+        assert_eq!(process_footnote_heading(html, "Notas"), html);
+    }
+
+    #[test]
+    fn test_process_inline_code() {
+        assert_eq!(
+            process_inline_code(
+                "<p><code>x</code></p>\n<pre><code>y\n</code></pre>",
+                "code"
+            ),
+            "<p><code class=\"code\">x</code></p>\n<pre><code>y\n</code></pre>"
+        );
+    }
+
+    #[test]
+    fn test_process_lazy_images() {
+        assert_eq!(
+            process_lazy_images(r#"<img src="a.png" alt="" />"#),
+            r#"<img src="a.png" alt="" loading="lazy" decoding="async" />"#
+        );
+        assert_eq!(
+            process_lazy_images(r#"<img src="b.png" loading="eager">"#),
+            r#"<img src="b.png" loading="eager" decoding="async">"#
+        );
+    }
+
+    #[test]
+    fn test_process_image_dimensions() {
+        let html = r#"<img src="a.jpg?w=800&amp;h=600&amp;q=80#top" alt="" />"#;
+        assert_eq!(
+            process_image_dimensions(html, false),
+            r#"<img src="a.jpg?w=800&amp;h=600&amp;q=80#top" alt="" width="800" height="600" />"#
+        );
+        assert_eq!(
+            process_image_dimensions(html, true),
+            r#"<img src="a.jpg?q=80#top" alt="" width="800" height="600" />"#
+        );
+        assert_eq!(
+            process_image_dimensions(
+                r#"<img src="b.png?width=320&amp;h=tall" width="100">"#,
+                true
+            ),
+            r#"<img src="b.png?h=tall" width="100">"#
+        );
+
+        let html = r#"<img src="c.png?w=0&amp;h=-1"><img src="d.png">"#;
+        assert_eq!(process_image_dimensions(html, true), html);
+    }
+
+    #[test]
+    fn test_column_alignment_from_html_attr() {
+        assert_eq!(
+            ColumnAlignment::from_html_attr(r#" align="center""#),
+            ColumnAlignment::Center
+        );
+        assert_eq!(
+            ColumnAlignment::from_html_attr(r#" ALIGN='right'"#),
+            ColumnAlignment::Right
+        );
+        assert_eq!(
+            ColumnAlignment::from_html_attr(r#" align="left""#),
+            ColumnAlignment::Left
+        );
+        assert_eq!(
+            ColumnAlignment::from_html_attr(r#" data-align="center""#),
+            ColumnAlignment::Left
+        );
+        assert_eq!(
+            ColumnAlignment::from_html_attr(""),
+            ColumnAlignment::Left
+        );
+    }
+
+    #[test]
+    fn test_column_alignment_to_css_class() {
+        assert_eq!(ColumnAlignment::Left.to_css_class(), "text-left");
+        assert_eq!(
+            ColumnAlignment::Center.to_css_class(),
+            "text-center"
+        );
+        assert_eq!(ColumnAlignment::Right.to_css_class(), "text-right");
+        assert_eq!(
+            ColumnAlignment::Justify.to_css_class(),
+            "text-justify"
+        );
+    }
+
+    #[test]
+    fn test_direction() {
+        assert_eq!(Direction::Ltr.as_str(), "ltr");
+        assert_eq!(Direction::Rtl.as_str(), "rtl");
+        assert_eq!(Direction::Auto.as_str(), "auto");
+
+        let table = "<table><tr><td>1</td></tr></table>";
+        let options = TableOptions {
+            direction: Some(Direction::Rtl),
+            ..TableOptions::default()
+        };
+        let processed = process_tables_with_options(table, &options);
+        assert!(processed.starts_with(
+            r#"<div class="table-responsive" dir="rtl"><table class="table">"#
+        ));
+        assert_eq!(
+            process_tables_with_options(&processed, &options),
+            processed
+        );
+    }
+
+    #[test]
+    fn test_process_nested_tables() {
+        let html = "<table><tr><td><table><tr><td>Inner</td></tr></table></td></tr></table>\n<table><tr><td>Next</td></tr></table>";
+        let processed = process_tables(html);
+
+        assert_eq!(
+            processed,
+            "<div class=\"table-responsive\"><table class=\"table\"><tr><td class=\"text-left\">\
+             <div class=\"table-responsive\"><table class=\"table\"><tr><td class=\"text-left\">Inner</td></tr></table></div>\
+             </td></tr></table></div>\n\
+             <div class=\"table-responsive\"><table class=\"table\"><tr><td class=\"text-left\">Next</td></tr></table></div>"
+        );
+        assert_eq!(
+            processed.matches("<div").count(),
+            processed.matches("</div>").count()
+        );
+    }
+
+    #[test]
+    fn test_process_tables_is_idempotent() {
+        let html = r#"<table><thead><tr><th align="center">A</th></tr></thead><tbody><tr><td align="right">1</td><td>2</td></tr></tbody></table>"#;
+        let once = process_tables(html);
+        assert_eq!(process_tables(&once), once);
+
+        let wrapped = r#"<div class="table-responsive"><table><tr><td class="custom">1</td></tr></table></div>"#;
+        assert_eq!(process_tables(wrapped), wrapped);
+    }
+
+    #[test]
+    fn test_table_with_attributes_is_not_wrapped() {
+        let html = r#"<table id="raw"><tr><td><table><tr><td>Inner</td></tr></table></td></tr></table>"#;
+        assert_eq!(
+            process_tables(html),
+            r#"<table id="raw"><tr><td class="text-left"><div class="table-responsive"><table class="table"><tr><td class="text-left">Inner</td></tr></table></div></td></tr></table>"#
+        );
+    }
+
+    #[test]
+    fn test_column_alignment_justify() {
+        assert_eq!(
+            ColumnAlignment::from_html_attr(r#" align="justify""#),
+            ColumnAlignment::Justify
+        );
+        let html = r#"<table><tr><td align="justify">Long text</td></tr></table>"#;
+        assert!(process_tables(html).contains(
+            r#"<td align="justify" class="text-justify">Long text</td>"#
+        ));
+    }
+
+    #[test]
+    fn test_process_math() {
+        let html = r#"<p>Inline <span data-math-style="inline">x^2</span></p>
+<p><span data-math-style="display">\sum_i i</span></p>"#;
+        assert_eq!(
+            process_math(html, MathRenderer::KaTeX),
+            r#"<p>Inline <span class="math inline">x^2</span></p>
+<div class="math display">\sum_i i</div>"#
+        );
+        assert_eq!(
+            process_math(html, MathRenderer::MathJax),
+            r#"<p>Inline <span class="math inline">\(x^2\)</span></p>
+<div class="math display">\[\sum_i i\]</div>"#
+        );
+    }
+
+    #[test]
+    fn test_custom_block_class_quoting() {
+        let expected = r#"<div class="alert alert-info" role="note"><strong>Note:</strong> Text</div>"#;
+        assert_eq!(
+            process_custom_blocks(r#"<div class="note">Text</div>"#),
+            expected
+        );
+        assert_eq!(
+            process_custom_blocks(r#"<div class='note'>Text</div>"#),
+            expected
+        );
+        assert_eq!(
+            process_custom_blocks(r#"<div class=note>Text</div>"#),
+            expected
+        );
+        assert_eq!(
+            process_custom_blocks(
+                r#"<div class=note data-title="Heads up">Text</div>"#
+            ),
+            r#"<div class="alert alert-info" role="note"><strong>Heads up:</strong> Text</div>"#
+        );
+    }
+
+    #[test]
+    fn test_custom_block_extra_classes() {
+        assert_eq!(
+            process_custom_blocks(
+                r#"<div class="note extra-class">Text</div>"#
+            ),
+            r#"<div class="alert alert-info extra-class" role="note"><strong>Note:</strong> Text</div>"#
+        );
+        assert_eq!(
+            process_custom_blocks(
+                r#"<div class="mb-3 warning collapse">Text</div>"#
+            ),
+            r#"<details class="alert alert-warning mb-3"><summary><strong>Warning:</strong></summary> Text</details>"#
+        );
+    }
+
+    #[test]
+    fn test_custom_block_type_round_trip() {
+        for block_type in BUILTIN_BLOCK_TYPES {
+            assert_eq!(
+                CustomBlockType::from_str(block_type.as_str()).unwrap(),
+                *block_type
+            );
+            assert_eq!(
+                block_type
+                    .to_string()
+                    .parse::<CustomBlockType>()
+                    .unwrap(),
+                *block_type
+            );
+            assert_eq!(
+                block_type.as_str(),
+                block_type.get_title().to_lowercase()
+            );
+        }
+    }
+
+    #[test]
+    fn test_process_custom_blocks_escape_content() {
+        let escape = CustomBlockOptions {
+            escape_content: true,
+            ..CustomBlockOptions::default()
+        };
+        assert_eq!(
+            process_custom_blocks_with_options(
+                r#"<div class="note"><script>alert("x")</script></div>"#,
+                &CustomBlockRegistry::default(),
+                &escape,
+            ),
+            r#"<div class="alert alert-info" role="note"><strong>Note:</strong> &lt;script&gt;alert("x")&lt;/script&gt;</div>"#
+        );
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_sanitize_html() {
+        assert_eq!(
+            sanitize_html(
+                r#"<p onclick="steal()">Hi<script>alert(1)</script> <a href="javascript:alert(1)">x</a></p>"#
+            ),
+            r#"<p>Hi <a rel="noopener noreferrer">x</a></p>"#
+        );
+        let alert = r#"<div class="alert alert-info" role="note"><span class="alert-icon icon-info" aria-hidden="true"></span><strong>Note:</strong> Text</div>"#;
+        assert_eq!(sanitize_html(alert), alert);
+    }
+
+    #[test]
+    fn test_custom_block_roles() {
+        for block_type in BUILTIN_BLOCK_TYPES {
+            let processed = process_custom_blocks(&format!(
+                r#"<div class="{}">Text</div>"#,
+                block_type
+            ));
+            assert!(processed.contains(&format!(
+                r#" role="{}">"#,
+                block_type.get_role()
+            )));
+        }
+        assert_eq!(CustomBlockType::Note.get_role(), "note");
+        assert_eq!(CustomBlockType::Tip.get_role(), "note");
+        assert_eq!(CustomBlockType::Info.get_role(), "note");
+        assert_eq!(CustomBlockType::Warning.get_role(), "alert");
+        assert_eq!(CustomBlockType::Important.get_role(), "alert");
+        assert_eq!(CustomBlockType::Caution.get_role(), "alert");
+    }
+
+    #[test]
+    fn test_custom_block_role_overrides() {
+        let options = CustomBlockOptions {
+            role_overrides: [
+                ("tip".to_string(), "alert".to_string()),
+                ("warning".to_string(), "status".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            ..CustomBlockOptions::default()
+        };
+        let processed = process_custom_blocks_with_options(
+            r#"<div class="Tip">A</div><div class="warning">B</div><div class="note">C</div>"#,
+            &CustomBlockRegistry::default(),
+            &options,
+        );
+        assert_eq!(
+            processed,
+            r#"<div class="alert alert-success" role="alert"><strong>Tip:</strong> A</div><div class="alert alert-warning" role="status"><strong>Warning:</strong> B</div><div class="alert alert-info" role="note"><strong>Note:</strong> C</div>"#
+        );
+    }
+
+    #[test]
+    fn test_expand_fenced_custom_blocks() {
+        assert_eq!(
+            expand_fenced_custom_blocks(
+                ":::warning Mind the \"gap\"\nText\n:::\nAfter\n"
+            ),
+            "<div class=\"warning\" data-title=\"Mind the &quot;gap&quot;\">\n\nText\n\n</div>\n\nAfter\n"
+        );
+        assert_eq!(
+            expand_fenced_custom_blocks("```\n:::note\n```\n:::\n"),
+            "```\n:::note\n```\n:::\n"
+        );
+        assert_eq!(
+            expand_fenced_custom_blocks("```text\n~~~\n```\n:::note\nHi\n:::\n"),
+            "```text\n~~~\n```\n<div class=\"note\">\n\nHi\n\n</div>\n\n"
+        );
+        let code = "```text\n~~~\n:::note\n```\n\n    :::tip\n";
+        assert_eq!(expand_fenced_custom_blocks(code), code);
+        assert_eq!(
+            expand_fenced_custom_blocks(
+                "````md\n```\n:::note\n````\n:::note\nHi\n:::\n"
+            ),
+            "````md\n```\n:::note\n````\n<div class=\"note\">\n\nHi\n\n</div>\n\n"
+        );
+        assert_eq!(
+            expand_fenced_custom_blocks(":::note\nUnclosed"),
+            "<div class=\"note\">\n\nUnclosed\n\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_separate_multi_paragraph_blocks() {
+        assert_eq!(
+            separate_multi_paragraph_blocks(
+                "<div class=\"note\">One.\n\nTwo.</div>\n"
+            ),
+            "<div class=\"note\">\n\nOne.\n\nTwo.\n\n</div>\n"
+        );
+        assert_eq!(
+            separate_multi_paragraph_blocks(
+                "```text\n~~~\n```\n<div class=\"note\">One.\n\nTwo.</div>\n"
+            ),
+            "```text\n~~~\n```\n<div class=\"note\">\n\nOne.\n\nTwo.\n\n</div>\n"
+        );
+        let untouched = "<div class=\"note\">One line</div>\n\n```html\n<div class=\"tip\">\n\n</div>\n```\n\nText <div class=\"tip\">a\n\nb</div>\n";
+        assert_eq!(
+            separate_multi_paragraph_blocks(untouched),
+            untouched
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_expand_tabs() {
+        assert_eq!(expand_tabs("\tx", 4), "    x");
+        assert_eq!(expand_tabs("ab\tc\n\t\td", 4), "ab  c\n        d");
+        assert_eq!(expand_tabs("no tabs", 2), "no tabs");
+    }
+
+    #[test]
+    fn test_process_heading_anchors() {
+        let html = "<h1>Intro</h1>\n<h2>Intro</h2>\n<h3>Intro</h3>\n<h2 id=\"kept\">Kept</h2>";
+
+        let processed = process_heading_anchors(html, false);
+        assert_eq!(
+            processed,
+            "<h1 id=\"intro\">Intro</h1>\n<h2 id=\"intro-1\">Intro</h2>\n<h3 id=\"intro-2\">Intro</h3>\n<h2 id=\"kept\">Kept</h2>"
+        );
+
+        let processed =
+            process_heading_anchors("<h2>Set up</h2>", true);
+        assert_eq!(
+            processed,
+            r##"<h2 id="set-up">Set up <a class="heading-anchor" href="#set-up" aria-hidden="true">#</a></h2>"##
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_apply_syntax_highlighting_with_unknown_theme() {
+        let result = apply_syntax_highlighting_with_theme(
+            "fn main() {}",
+            "rust",
+            "No Such Theme",
+        );
+        assert!(matches!(
+            result,
+            Err(MarkdownError::SyntaxHighlightError(ref msg)) if msg.contains("No Such Theme")
+        ));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_apply_syntax_highlighting_with_custom_syntax_set() {
+        use syntect::parsing::{SyntaxDefinition, SyntaxSetBuilder};
+
+        let definition = SyntaxDefinition::load_from_str(
+            "name: Toy\nfile_extensions: [toy]\nscope: source.toy\ncontexts:\n  main:\n    - match: '\\bloop\\b'\n      scope: keyword.control.toy\n",
+            true,
+            None,
+        )
+        .unwrap();
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_plain_text_syntax();
+        builder.add(definition);
+        let syntax_set = builder.build();
+        let theme = &THEME_SET.themes["InspiredGitHub"];
+
+        let custom = apply_syntax_highlighting_with(
+            "loop forever",
+            "toy",
+            &syntax_set,
+            theme,
+        )
+        .unwrap();
+        let bundled = apply_syntax_highlighting_with(
+            "loop forever",
+            "toy",
+            &SYNTAX_SET,
+            theme,
+        )
+        .unwrap();
+
+        assert!(custom.contains(">loop</span>"));
+        assert!(!bundled.contains(">loop</span>"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_apply_syntax_highlighting_has_no_trailing_line() {
+        let code = "fn main() {\n\n    run();\n}";
+        let highlighted =
+            apply_syntax_highlighting(code, "rust").unwrap();
+
+        // The only extra newline is the one after `<pre ...>`, which
+        // HTML ignores.
+        assert_eq!(
+            highlighted.matches('\n').count(),
+            code.matches('\n').count() + 1
+        );
+        assert!(highlighted.ends_with("</span></pre>"));
+
+        let with_newline =
+            apply_syntax_highlighting(&format!("{}\n", code), "rust")
+                .unwrap();
+        assert_eq!(with_newline, highlighted);
     }
 }
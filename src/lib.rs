@@ -9,6 +9,9 @@
 #![crate_name = "mdx_gen"]
 #![crate_type = "lib"]
 
+/// The `ast` module converts Markdown into a JSON syntax tree.
+pub mod ast;
+
 /// The `error` module contains error types for Markdown processing.
 pub mod error;
 
@@ -32,14 +35,67 @@
 /// use mdx_gen::apply_syntax_highlighting;
 /// let highlighted = apply_syntax_highlighting("fn main() {}", "rust");
 /// ```
+#[cfg(feature = "syntax_highlighting")]
 pub use extensions::apply_syntax_highlighting;
 
+/// Applies syntax highlighting to a code block using a named theme.
+#[cfg(feature = "syntax_highlighting")]
+pub use extensions::apply_syntax_highlighting_with_theme;
+
+/// Applies syntax highlighting using a caller-provided syntax set and
+/// theme.
+#[cfg(feature = "syntax_highlighting")]
+pub use extensions::apply_syntax_highlighting_with;
+
+/// Applies syntax highlighting to inline code, without a `<pre>` wrapper.
+///
+/// # Example
+/// ```
+/// use mdx_gen::highlight_inline_code;
+/// let html = highlight_inline_code("let x = 1;", "rust").unwrap();
+/// assert!(html.starts_with(r#"<code class="language-rust">"#));
+/// ```
+#[cfg(feature = "syntax_highlighting")]
+pub use extensions::highlight_inline_code;
+
+/// Normalizes HTML whitespace for stable comparisons in tests.
+pub use extensions::normalize_html;
+
 /// Represents different alignment options for table columns in enhanced Markdown tables.
 pub use extensions::ColumnAlignment;
 
+/// The themes bundled with the syntax highlighter.
+pub use extensions::SyntaxTheme;
+
+/// The text direction of generated tables and custom blocks.
+pub use extensions::Direction;
+
+/// The client-side library used to render math.
+pub use extensions::MathRenderer;
+
+/// Class names used in the generated HTML.
+pub use extensions::ClassNames;
+
+/// Options controlling how enhanced tables are rendered.
+pub use extensions::TableOptions;
+
+/// The numbering style of ordered lists.
+pub use extensions::ListStyle;
+
+/// Classes added to definition list elements.
+pub use extensions::DefinitionListClasses;
+
 /// Represents the type of custom block, such as admonitions or custom embedded content.
 pub use extensions::CustomBlockType;
 
+/// A registry of custom block types that can be extended at runtime.
+pub use extensions::{
+    CustomBlockDefinition, CustomBlockOptions, CustomBlockRegistry,
+};
+
+/// What to do with custom blocks whose type is not registered.
+pub use extensions::UnknownBlockBehavior;
+
 /// Processes a Markdown string and converts it into HTML, applying custom blocks and syntax highlighting.
 ///
 /// # Example
@@ -62,9 +118,43 @@
 /// This function will return a `MarkdownError` if the input contains invalid syntax or cannot be parsed.
 pub use markdown::process_markdown;
 
+/// Processes Markdown and returns the HTML together with document metadata.
+pub use markdown::{
+    process_markdown_with_metadata, Heading, RenderedDocument,
+};
+
+/// Processes Markdown into a trimmed HTML fragment.
+pub use markdown::process_markdown_fragment;
+
+/// Renders a Markdown table fragment as an enhanced HTML table.
+pub use markdown::render_table;
+
+/// Processes Markdown and writes the resulting HTML to an `io::Write`.
+pub use markdown::process_markdown_to_writer;
+
+/// Processes Markdown and returns the HTML of each rendering stage.
+pub use markdown::{process_markdown_stages, RenderStages};
+
+/// Processes Markdown into a JSON syntax tree.
+pub use ast::process_markdown_to_ast;
+
+/// Processes Markdown given as UTF-8 bytes.
+pub use markdown::process_markdown_bytes;
+
+/// Processes several Markdown documents, in parallel with the `parallel`
+/// feature.
+pub use markdown::process_markdown_batch;
+
+/// What to do when a code block cannot be highlighted.
+pub use markdown::HighlightFallback;
+
 /// Options for configuring how Markdown is processed, including syntax highlighting and custom block support.
 pub use markdown::MarkdownOptions;
 
+/// A builder for common `MarkdownOptions` setups that configures Comrak
+/// itself.
+pub use markdown::MarkdownOptionsBuilder;
+
 /// Re-export comrak's options for convenience when customizing Markdown processing.
 ///
 /// # Usage
@@ -4,28 +4,234 @@
 //! with support for custom blocks, enhanced tables, and syntax highlighting.
 
 use crate::error::MarkdownError;
+#[cfg(feature = "syntax_highlighting")]
 use crate::extensions::{
-    apply_syntax_highlighting, process_custom_blocks, process_tables,
+    default_syntax_set, expand_tabs, fenced_code_block_starts,
+    find_syntax, highlight_lines_with_theme, highlight_with_theme,
+    load_syntax_set_with_extras, load_theme_from_file, resolve_theme,
+    DEFAULT_THEME,
 };
-use comrak::{markdown_to_html, ComrakOptions};
+use crate::extensions::{
+    expand_fenced_custom_blocks, expand_includes,
+    extract_table_captions, html_to_text, insert_table_captions,
+    minify_html, process_abbreviations,
+    process_custom_blocks_with_options, process_definition_lists,
+    process_emoji_shortcodes, process_external_links,
+    process_footnote_heading, process_github_alerts,
+    process_heading_anchors, process_heading_scroll_margin,
+    process_image_dimensions, process_inline_code,
+    process_kbd_shortcuts, process_lazy_images, process_math,
+    process_ordered_lists, process_tables_with_options,
+    separate_multi_paragraph_blocks, strip_html_comments, ClassNames,
+    CustomBlockOptions, CustomBlockRegistry, DefinitionListClasses,
+    Direction, ListStyle, MathRenderer, SyntaxTheme, TableOptions,
+    UnknownBlockBehavior,
+};
+use comrak::{markdown_to_html, ComrakOptions, URLRewriter};
 use lazy_static::lazy_static;
 use log::{debug, info, warn};
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
+use std::io::Write;
+#[cfg(feature = "syntax_highlighting")]
+use std::ops::RangeInclusive;
+use std::panic::RefUnwindSafe;
+#[cfg(feature = "syntax_highlighting")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(feature = "syntax_highlighting")]
+use syntect::{
+    highlighting::Theme,
+    html::start_highlighted_html_snippet,
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+/// What to do when a code block cannot be highlighted, for example
+/// because its language is unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightFallback {
+    /// Abort processing with a `MarkdownError::ConversionError` naming
+    /// the line of the failing block in the Markdown source, or its byte
+    /// offset in the HTML if includes or the pre-processor changed the
+    /// source.
+    Error,
+    /// Emit the escaped code without colours. Unknown languages are
+    /// highlighted with the plain-text syntax. This is the default.
+    PlainText,
+    /// Leave the code block exactly as Comrak rendered it.
+    SkipHighlighting,
+    /// Guess the language of code blocks that have none from their
+    /// first line, e.g. a `#!/bin/sh` shebang, leaving them as Comrak
+    /// rendered them if that fails. Unknown languages are handled as
+    /// with `PlainText`. Opt-in, as every such block is checked against
+    /// each syntax.
+    Guess,
+}
+
+impl Default for HighlightFallback {
+    fn default() -> Self {
+        HighlightFallback::PlainText
+    }
+}
 
 /// Options for configuring Markdown processing behavior.
 #[derive(Clone)]
 pub struct MarkdownOptions<'a> {
     /// Options for the underlying Comrak Markdown parser.
     pub comrak_options: ComrakOptions<'a>,
+    /// Pass raw HTML in the Markdown through to the output. When false,
+    /// raw HTML is escaped.
+    pub allow_raw_html: bool,
+    /// Maximum size of the Markdown input in bytes. Unlimited when `None`.
+    pub max_input_bytes: Option<usize>,
     /// Enable or disable processing of custom blocks (e.g., note, warning, tip).
     pub enable_custom_blocks: bool,
     /// Enable or disable syntax highlighting for code blocks.
     pub enable_syntax_highlighting: bool,
     /// Enable or disable enhanced table formatting.
     pub enable_enhanced_tables: bool,
+    /// Enable or disable line numbers in highlighted code blocks.
+    pub enable_line_numbers: bool,
+    /// Optional number of columns between tab stops in highlighted code
+    /// blocks. Tabs are expanded to spaces when set.
+    pub tab_width: Option<usize>,
+    /// Enable or disable GitHub-style alerts (`> [!NOTE]`).
+    pub enable_github_alerts: bool,
+    /// Render custom blocks as collapsible `<details>` elements.
+    pub enable_collapsible_blocks: bool,
+    /// Prepend an icon element to custom blocks.
+    pub enable_block_icons: bool,
+    /// Render custom blocks with the `note` role as `<aside>` elements.
+    pub enable_aside_for_notes: bool,
+    /// What to do with custom blocks whose type is not registered.
+    pub unknown_block_behavior: UnknownBlockBehavior,
+    /// Recognise `:::type` ... `:::` fenced custom blocks.
+    pub enable_fenced_custom_blocks: bool,
+    /// HTML-escape the content of `<div>` custom blocks.
+    pub enable_sanitize_custom_blocks: bool,
+    /// ARIA roles keyed by block class name, overriding each block
+    /// type's default role.
+    pub block_role_overrides: HashMap<String, String>,
+    /// Classes applied to tables when enhanced tables are enabled.
+    pub table_classes: Vec<String>,
+    /// Enable or disable `[Table: caption]` lines above tables.
+    pub enable_table_captions: bool,
+    /// Optional marker identifying a last table row to render in `<tfoot>`.
+    pub table_footer_marker: Option<String>,
+    /// Wrap enhanced tables in a `table-responsive` `<div>`.
+    pub enable_table_responsive_wrapper: bool,
+    /// Enable or disable `id` attributes on headings.
+    pub enable_heading_anchors: bool,
+    /// Append a permalink to each heading when heading anchors are enabled.
+    pub enable_heading_permalinks: bool,
+    /// Optional `scroll-margin-top`, in pixels, for headings with anchors.
+    pub heading_scroll_margin: Option<u32>,
+    /// Reading speed used to estimate reading time.
+    pub words_per_minute: u32,
+    /// Replace emoji shortcodes such as `:rocket:` with emoji.
+    pub enable_emoji_shortcodes: bool,
+    /// Replace keyboard shortcuts such as `[[Ctrl+C]]` with `<kbd>`
+    /// elements.
+    pub enable_kbd_shortcuts: bool,
+    /// Abbreviations wrapped in `<abbr>` elements, mapped to their
+    /// expansions.
+    pub abbreviations: HashMap<String, String>,
+    /// Wrap every occurrence of an abbreviation, rather than only the
+    /// first.
+    pub abbreviate_every_occurrence: bool,
+    /// Open external links in a new tab with `rel="noopener noreferrer"`.
+    pub enable_external_link_attributes: bool,
+    /// The site's own URL; absolute links to its host are not external.
+    pub base_url: Option<String>,
+    /// Optional function rewriting the URL of every Markdown link.
+    pub link_url_rewriter: Option<Arc<dyn URLRewriter + 'a>>,
+    /// Optional function rewriting the URL of every Markdown image.
+    pub image_url_rewriter: Option<Arc<dyn URLRewriter + 'a>>,
+    /// Add `loading="lazy"` and `decoding="async"` to images.
+    pub enable_lazy_images: bool,
+    /// Add `width` and `height` attributes to images from the `w`/`width`
+    /// and `h`/`height` query parameters of their URL.
+    pub enable_image_dimensions: bool,
+    /// Remove the query parameters used for image dimensions from the
+    /// image URL.
+    pub strip_image_dimension_params: bool,
+    /// Strip the `<p>` wrapping single-paragraph fragments rendered by
+    /// [`process_markdown_fragment`].
+    pub strip_fragment_paragraph: bool,
+    /// Optional heading placed at the top of the footnotes section.
+    pub footnote_heading: Option<String>,
+    /// Optional text direction of tables and custom blocks.
+    pub text_direction: Option<Direction>,
+    /// Optional numbering style of ordered lists.
+    pub ordered_list_style: Option<ListStyle>,
+    /// Optional classes added to definition list elements.
+    pub definition_list_classes: Option<DefinitionListClasses>,
+    /// Render `$...$` and `$$...$$` math for the given renderer.
+    pub math_renderer: Option<MathRenderer>,
+    /// Run the final HTML through an allowlist sanitizer. Requires the
+    /// `sanitize` feature.
+    pub enable_sanitize_html: bool,
+    /// Remove HTML comments outside `<pre>` and `<code>` elements.
+    pub enable_strip_comments: bool,
+    /// Collapse insignificant whitespace in the final HTML.
+    pub enable_minify_output: bool,
+    /// Optional directory `{{include:path}}` directives are resolved
+    /// against. Include directives are left as written when `None`.
+    pub include_base_dir: Option<PathBuf>,
+    /// The maximum nesting depth of included files.
+    pub max_include_depth: usize,
+    /// Optional function applied to the raw Markdown before it is
+    /// parsed.
+    pub pre_processor:
+        Option<Arc<dyn Fn(String) -> String + Send + Sync + 'a>>,
+    /// Optional function applied to the final HTML, after every other
+    /// pass.
+    pub post_processor:
+        Option<Arc<dyn Fn(String) -> String + Send + Sync + 'a>>,
+    /// Wrap highlighted code blocks with a copy-to-clipboard button.
+    pub enable_code_copy_button: bool,
+    /// Label highlighted code blocks with their language for screen
+    /// readers.
+    pub enable_code_aria_labels: bool,
+    /// Add `spellcheck="false"` to highlighted code blocks.
+    pub disable_spellcheck_in_code: bool,
+    /// Record why highlighting fell back to plain text in a
+    /// `data-highlight-error` attribute.
+    pub enable_highlight_error_attribute: bool,
     /// Optional custom theme for syntax highlighting.
     pub syntax_theme: Option<String>,
+    /// Optional theme loaded from a `.tmTheme` file. Takes precedence
+    /// over `syntax_theme` when set.
+    #[cfg(feature = "syntax_highlighting")]
+    pub custom_theme: Option<Theme>,
+    /// Optional pair of `(light, dark)` theme names. When set, each code
+    /// block is rendered once per theme and takes precedence over
+    /// `syntax_theme` and `custom_theme`.
+    pub theme_pair: Option<(String, String)>,
+    /// Theme names for specific languages, keyed by lowercase code block
+    /// token. These take precedence over every other theme setting.
+    pub language_themes: HashMap<String, String>,
+    /// Optional syntax set extended with user-supplied syntaxes. The
+    /// bundled default syntaxes are used when this is `None`.
+    #[cfg(feature = "syntax_highlighting")]
+    pub syntax_set: Option<SyntaxSet>,
+    /// Additional language aliases, mapping a lowercase code block token
+    /// to a syntax name. These take precedence over the built-in aliases.
+    pub language_aliases: HashMap<String, String>,
+    /// Code block languages emitted un-highlighted as
+    /// `<pre class="{language}">`, for client-side renderers such as
+    /// Mermaid.
+    pub passthrough_languages: Vec<String>,
+    /// What to do when a code block cannot be highlighted.
+    pub highlight_fallback: HighlightFallback,
+    /// The custom block types recognised when custom blocks are enabled.
+    pub custom_block_registry: CustomBlockRegistry,
+    /// Class names used for code blocks, inline code, custom blocks and
+    /// table wrappers. Table classes are set by `table_classes`.
+    pub class_names: ClassNames,
 }
 
 impl<'a> Default for MarkdownOptions<'a> {
@@ -34,10 +240,69 @@ impl<'a> Default for MarkdownOptions<'a> {
     fn default() -> Self {
         Self {
             comrak_options: ComrakOptions::default(),
+            allow_raw_html: true,
+            max_input_bytes: None,
             enable_custom_blocks: true,
             enable_syntax_highlighting: true,
             enable_enhanced_tables: true,
+            enable_line_numbers: false,
+            tab_width: None,
+            enable_github_alerts: false,
+            enable_collapsible_blocks: false,
+            enable_block_icons: false,
+            enable_aside_for_notes: false,
+            unknown_block_behavior: UnknownBlockBehavior::default(),
+            enable_fenced_custom_blocks: false,
+            enable_sanitize_custom_blocks: false,
+            block_role_overrides: HashMap::new(),
+            table_classes: vec!["table".to_string()],
+            enable_table_captions: false,
+            table_footer_marker: None,
+            enable_table_responsive_wrapper: true,
+            enable_heading_anchors: false,
+            enable_heading_permalinks: false,
+            heading_scroll_margin: None,
+            words_per_minute: 200,
+            enable_emoji_shortcodes: false,
+            enable_kbd_shortcuts: false,
+            abbreviations: HashMap::new(),
+            abbreviate_every_occurrence: false,
+            enable_external_link_attributes: false,
+            base_url: None,
+            link_url_rewriter: None,
+            image_url_rewriter: None,
+            enable_lazy_images: false,
+            enable_image_dimensions: false,
+            strip_image_dimension_params: false,
+            strip_fragment_paragraph: false,
+            footnote_heading: None,
+            text_direction: None,
+            ordered_list_style: None,
+            definition_list_classes: None,
+            math_renderer: None,
+            enable_sanitize_html: false,
+            enable_strip_comments: false,
+            enable_minify_output: false,
+            include_base_dir: None,
+            max_include_depth: 8,
+            pre_processor: None,
+            post_processor: None,
+            enable_code_copy_button: false,
+            enable_code_aria_labels: false,
+            disable_spellcheck_in_code: false,
+            enable_highlight_error_attribute: false,
             syntax_theme: None, // Default: no custom theme
+            #[cfg(feature = "syntax_highlighting")]
+            custom_theme: None,
+            theme_pair: None,
+            language_themes: HashMap::new(),
+            #[cfg(feature = "syntax_highlighting")]
+            syntax_set: None,
+            language_aliases: HashMap::new(),
+            passthrough_languages: vec!["mermaid".to_string()],
+            highlight_fallback: HighlightFallback::default(),
+            custom_block_registry: CustomBlockRegistry::default(),
+            class_names: ClassNames::default(),
         }
     }
 }
@@ -48,403 +313,4960 @@ pub fn new() -> Self {
         Self::default()
     }
 
-    /// Enables or disables custom blocks.
-    pub fn with_custom_blocks(mut self, enable: bool) -> Self {
-        self.enable_custom_blocks = enable;
+    /// Returns a builder for common setups that configures Comrak
+    /// itself, so callers never need to use `ComrakOptions` directly.
+    ///
+    /// The builder starts from [`preset_commonmark`](Self::preset_commonmark),
+    /// with every extension off.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mdx_gen::{process_markdown, MarkdownOptions};
+    ///
+    /// let options = MarkdownOptions::builder()
+    ///     .gfm()
+    ///     .enhanced_tables()
+    ///     .syntax_highlighting()
+    ///     .build();
+    /// assert!(options.validate().is_ok());
+    ///
+    /// let html = process_markdown("| A |\n|---|\n| 1 |", &options).unwrap();
+    /// assert!(html.contains(r#"<table class="table">"#));
+    /// ```
+    pub fn builder() -> MarkdownOptionsBuilder<'a> {
+        MarkdownOptionsBuilder {
+            options: Self::preset_commonmark(),
+        }
+    }
+
+    /// Creates options for GitHub-flavored Markdown.
+    ///
+    /// Enables Comrak's tables, strikethrough, task lists, autolinks and
+    /// tag filter, along with enhanced tables, custom blocks and syntax
+    /// highlighting.
+    pub fn preset_github() -> Self {
+        let mut comrak_options = ComrakOptions::default();
+        comrak_options.extension.tagfilter = true;
+
+        Self::default()
+            .with_comrak_options(comrak_options)
+            .with_tables(true)
+            .with_strikethrough(true)
+            .with_tasklist(true)
+            .with_autolink(true)
+            .with_enhanced_tables(true)
+            .with_custom_blocks(true)
+            .with_syntax_highlighting(true)
+    }
+
+    /// Creates options for strict CommonMark.
+    ///
+    /// All Comrak extensions are left off, as are enhanced tables,
+    /// custom blocks and syntax highlighting.
+    pub fn preset_commonmark() -> Self {
+        Self::default()
+            .with_comrak_options(ComrakOptions::default())
+            .with_enhanced_tables(false)
+            .with_custom_blocks(false)
+            .with_syntax_highlighting(false)
+    }
+
+    /// Sets the classes applied to enhanced tables, e.g.
+    /// `vec!["table".into(), "table-striped".into()]`. Defaults to `table`.
+    pub fn with_table_classes(mut self, classes: Vec<String>) -> Self {
+        self.table_classes = classes;
         self
     }
 
-    /// Enables or disables syntax highlighting for code blocks.
-    pub fn with_syntax_highlighting(mut self, enable: bool) -> Self {
-        self.enable_syntax_highlighting = enable;
+    /// Sets the class names used in the generated HTML, for CSS
+    /// frameworks other than Bootstrap.
+    ///
+    /// Highlighted code blocks get `<pre class="{code_pre}">`, inline
+    /// code `<code class="{code_inline}">`, custom blocks and GitHub
+    /// alerts use `alert_prefix` in place of `alert`, and enhanced tables
+    /// are wrapped in `<div class="{table_wrapper}">`. The classes of the
+    /// tables themselves come only from
+    /// [`with_table_classes`](Self::with_table_classes).
+    /// [`ClassNames::default()`] reproduces the Bootstrap classes.
+    pub fn with_class_names(mut self, class_names: ClassNames) -> Self {
+        self.class_names = class_names;
         self
     }
 
-    /// Enables or disables enhanced table formatting.
-    pub fn with_enhanced_tables(mut self, enable: bool) -> Self {
-        self.enable_enhanced_tables = enable;
+    /// Enables or disables table captions.
+    ///
+    /// A `[Table: caption]` line immediately above a table becomes an
+    /// HTML-escaped `<caption>` inside that table.
+    pub fn with_table_captions(mut self, enable: bool) -> Self {
+        self.enable_table_captions = enable;
         self
     }
 
-    /// Sets a custom theme for syntax highlighting.
-    pub fn with_custom_theme(mut self, theme: String) -> Self {
-        self.syntax_theme = Some(theme);
+    /// Sets a marker (e.g. `=`) identifying table footer rows.
+    ///
+    /// When the first cell of a table's last body row starts with the
+    /// marker, that row is moved into a `<tfoot>` and the marker is
+    /// removed from its cells.
+    pub fn with_table_footer_marker(mut self, marker: String) -> Self {
+        self.table_footer_marker = Some(marker);
         self
     }
 
-    /// Sets custom Comrak options.
-    pub fn with_comrak_options(
+    /// Enables or disables the `<div class="table-responsive">` wrapper
+    /// around enhanced tables. Enabled by default.
+    ///
+    /// Disable it for layouts that handle table overflow themselves; the
+    /// tables still get their classes.
+    pub fn with_table_responsive_wrapper(
         mut self,
-        options: ComrakOptions<'a>,
+        enable: bool,
     ) -> Self {
-        self.comrak_options = options;
+        self.enable_table_responsive_wrapper = enable;
         self
     }
 
-    /// Validates the `MarkdownOptions` to ensure they are consistent and compatible.
-    pub fn validate(&self) -> Result<(), String> {
-        if self.enable_enhanced_tables
-            && !self.comrak_options.extension.table
-        {
-            return Err("Enhanced tables are enabled, but Comrak table extension is disabled.".to_string());
-        }
-        Ok(())
+    /// Enables or disables heading anchors.
+    ///
+    /// Each `<h1>`–`<h6>` receives an `id` derived from its text (see
+    /// [`slugify`](crate::extensions::slugify)); duplicates are suffixed
+    /// with `-1`, `-2`, ...
+    pub fn with_heading_anchors(mut self, enable: bool) -> Self {
+        self.enable_heading_anchors = enable;
+        self
     }
-}
 
-impl fmt::Debug for MarkdownOptions<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("MarkdownOptions")
-            .field("enable_custom_blocks", &self.enable_custom_blocks)
-            .field(
-                "enable_syntax_highlighting",
-                &self.enable_syntax_highlighting,
-            )
-            .field(
-                "enable_enhanced_tables",
-                &self.enable_enhanced_tables,
-            )
-            .field("syntax_theme", &self.syntax_theme)
-            .finish()
+    /// Enables or disables heading permalinks.
+    ///
+    /// Requires heading anchors; appends
+    /// `<a class="heading-anchor" href="#slug">` to each heading.
+    pub fn with_heading_permalinks(mut self, enable: bool) -> Self {
+        self.enable_heading_permalinks = enable;
+        self
     }
-}
 
-/// Creates a new instance of `MarkdownOptions` with default values.
-pub fn default_markdown_options() -> MarkdownOptions<'static> {
-    MarkdownOptions::new()
-        .with_custom_blocks(true)
-        .with_syntax_highlighting(true)
-        .with_enhanced_tables(true)
-        .with_comrak_options({
-            let mut opts = ComrakOptions::default();
-            opts.extension.table = true;
-            opts
-        })
-}
+    /// Sets a `scroll-margin-top` for headings, so anchor jumps are not
+    /// hidden under a sticky header.
+    ///
+    /// Requires heading anchors; each heading with an `id` receives
+    /// `style="scroll-margin-top:{px}px"`.
+    pub fn with_heading_scroll_margin(mut self, px: u32) -> Self {
+        self.heading_scroll_margin = Some(px);
+        self
+    }
 
-/// Processes the input Markdown content and converts it into HTML.
-/// Applies custom blocks, syntax highlighting, and enhanced tables based on the provided options.
-pub fn process_markdown(
-    content: &str,
-    options: &MarkdownOptions,
-) -> Result<String, MarkdownError> {
-    info!("Starting markdown processing");
-    debug!("Markdown options: {:?}", options);
+    /// Enables or disables emoji shortcodes.
+    ///
+    /// Known shortcodes such as `:rocket:` are replaced with the matching
+    /// emoji outside of code; unknown shortcodes are left as written.
+    pub fn with_emoji_shortcodes(mut self, enable: bool) -> Self {
+        self.enable_emoji_shortcodes = enable;
+        self
+    }
 
-    // Validate options
-    if let Err(msg) = options.validate() {
-        warn!("Invalid MarkdownOptions: {}", msg);
-        return Err(MarkdownError::InvalidOptionsError(msg));
+    /// Enables or disables keyboard shortcuts.
+    ///
+    /// When enabled, `[[Ctrl+C]]` renders as
+    /// `<kbd>Ctrl</kbd>+<kbd>C</kbd>` outside of code.
+    pub fn with_kbd_shortcuts(mut self, enable: bool) -> Self {
+        self.enable_kbd_shortcuts = enable;
+        self
     }
 
-    // Clone Comrak options and enable unsafe rendering
-    let mut comrak_opts = options.comrak_options.clone();
-    comrak_opts.render.unsafe_ = true;
+    /// Sets abbreviations to wrap in `<abbr>` elements, mapping each
+    /// abbreviation to its expansion, e.g. `"HTML"` to
+    /// `"HyperText Markup Language"`.
+    ///
+    /// Only whole words in prose are matched, not text inside code,
+    /// links or existing tags. By default only the first occurrence of
+    /// each abbreviation in the document is wrapped; see
+    /// [`with_abbreviate_every_occurrence`](Self::with_abbreviate_every_occurrence).
+    pub fn with_abbreviations(
+        mut self,
+        abbreviations: HashMap<String, String>,
+    ) -> Self {
+        self.abbreviations = abbreviations;
+        self
+    }
 
-    // Convert Markdown to initial HTML
-    debug!("Converting markdown to HTML using Comrak");
-    let mut html = markdown_to_html(content, &comrak_opts);
-    debug!("Initial HTML conversion result: {}", html);
+    /// Enables or disables wrapping every occurrence of an abbreviation,
+    /// rather than only the first in the document.
+    pub fn with_abbreviate_every_occurrence(
+        mut self,
+        enable: bool,
+    ) -> Self {
+        self.abbreviate_every_occurrence = enable;
+        self
+    }
 
-    // Apply syntax highlighting if enabled
-    if options.enable_syntax_highlighting {
-        debug!("Applying syntax highlighting");
-        match highlight_code_blocks(&html) {
-            Ok(highlighted) => html = highlighted,
-            Err(e) => {
-                warn!("Error during syntax highlighting: {:?}", e);
-                return Err(e);
-            }
-        }
+    /// Enables or disables attributes on external links.
+    ///
+    /// Absolute `http`/`https` links whose host differs from the
+    /// [base URL](Self::with_base_url) receive `target="_blank"` and
+    /// `rel="noopener noreferrer"`. Relative and fragment links are left
+    /// unchanged.
+    pub fn with_external_link_attributes(
+        mut self,
+        enable: bool,
+    ) -> Self {
+        self.enable_external_link_attributes = enable;
+        self
     }
 
-    // Process enhanced tables if enabled
-    if options.enable_enhanced_tables {
-        debug!("Processing enhanced tables");
-        html = process_tables(&html);
+    /// Sets the site's own URL, e.g. `https://example.com/`. Absolute
+    /// links to the same host are treated as internal.
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = Some(base_url);
+        self
     }
 
-    // Process custom blocks (e.g., note, warning, tip) if enabled
-    if options.enable_custom_blocks {
-        debug!("Processing custom blocks");
-        html = process_custom_blocks(&html);
+    /// Sets a function that rewrites the URL of every Markdown link, e.g.
+    /// to prefix `/docs/...` paths with a CDN host.
+    ///
+    /// The rewriter runs while the Markdown is parsed, before any of the
+    /// HTML post-processing passes, so later passes such as
+    /// [external link attributes](Self::with_external_link_attributes)
+    /// see the rewritten URLs. Links written as raw HTML are not rewritten.
+    pub fn with_link_url_rewriter<F>(mut self, rewriter: F) -> Self
+    where
+        F: Fn(&str) -> String + RefUnwindSafe + Send + Sync + 'a,
+    {
+        self.link_url_rewriter = Some(Arc::new(rewriter));
+        self
     }
 
-    info!("Markdown processing completed successfully");
-    Ok(html)
-}
+    /// Sets a function that rewrites the `src` of every Markdown image.
+    ///
+    /// Runs at the same stage as
+    /// [`with_link_url_rewriter`](Self::with_link_url_rewriter).
+    pub fn with_image_url_rewriter<F>(mut self, rewriter: F) -> Self
+    where
+        F: Fn(&str) -> String + RefUnwindSafe + Send + Sync + 'a,
+    {
+        self.image_url_rewriter = Some(Arc::new(rewriter));
+        self
+    }
 
-lazy_static! {
-    static ref CODE_BLOCK_RE: Regex = Regex::new(
-        r#"(?s)<pre><code class="language-(.*?)">(.*?)</code></pre>"#
-    )
-    .unwrap();
-}
+    /// Enables or disables lazy loading of images.
+    ///
+    /// Every `<img>`, including images inside custom blocks, receives
+    /// `loading="lazy"` and `decoding="async"` unless it already sets
+    /// those attributes.
+    pub fn with_lazy_images(mut self, enable: bool) -> Self {
+        self.enable_lazy_images = enable;
+        self
+    }
 
-fn highlight_code_blocks(html: &str) -> Result<String, MarkdownError> {
-    debug!("Highlighting code blocks");
+    /// Enables or disables image dimensions taken from the image URL.
+    ///
+    /// An image such as `![Photo](photo.jpg?w=800&h=600)` receives
+    /// `width="800" height="600"`, reducing layout shift while it loads.
+    /// `width` and `height` are recognised as well as `w` and `h`.
+    /// Values that are not positive whole numbers are ignored, and
+    /// attributes the image already sets are kept. The parameters stay
+    /// in the URL unless
+    /// [`with_strip_image_dimension_params`](Self::with_strip_image_dimension_params)
+    /// is enabled.
+    pub fn with_image_dimensions_from_query(
+        mut self,
+        enable: bool,
+    ) -> Self {
+        self.enable_image_dimensions = enable;
+        self
+    }
 
-    let mut highlighted_html = String::new();
-    let mut last_end = 0;
+    /// Enables or disables removing the query parameters used for image
+    /// dimensions from the image URL.
+    ///
+    /// Requires image dimensions from the query.
+    pub fn with_strip_image_dimension_params(
+        mut self,
+        enable: bool,
+    ) -> Self {
+        self.strip_image_dimension_params = enable;
+        self
+    }
 
-    for cap in CODE_BLOCK_RE.captures_iter(html) {
-        highlighted_html
-            .push_str(&html[last_end..cap.get(0).unwrap().start()]);
+    /// Enables or disables stripping the `<p>` tags around fragments
+    /// rendered by [`process_markdown_fragment`].
+    ///
+    /// The tags are only stripped when the fragment is exactly one
+    /// top-level paragraph; stripping them from multi-block content
+    /// would leave bare text beside other blocks.
+    pub fn with_strip_fragment_paragraph(
+        mut self,
+        enable: bool,
+    ) -> Self {
+        self.strip_fragment_paragraph = enable;
+        self
+    }
 
-        let (lang, code) = extract_code_block(&cap)?;
-        let highlighted_code = highlight_code(lang, &code)?;
+    /// Sets the heading of the footnotes section, e.g. `"Notas"`.
+    ///
+    /// Requires Comrak's `footnotes` extension. When the document has
+    /// footnotes, an HTML-escaped `<h2 class="footnotes-heading">` is
+    /// inserted at the top of `<section class="footnotes">`, replacing
+    /// any heading already there.
+    pub fn with_footnote_heading(mut self, heading: String) -> Self {
+        self.footnote_heading = Some(heading);
+        self
+    }
 
-        highlighted_html.push_str(&format_highlighted_code(
-            lang,
-            &highlighted_code,
-        ));
-        last_end = cap.get(0).unwrap().end();
+    /// Sets the text direction of generated tables and custom blocks.
+    ///
+    /// A `dir` attribute is added to each `table-responsive` wrapper and
+    /// to each custom block and GitHub alert element. By default no
+    /// attribute is added.
+    pub fn with_text_direction(mut self, direction: Direction) -> Self {
+        self.text_direction = Some(direction);
+        self
     }
 
-    highlighted_html.push_str(&html[last_end..]);
-    Ok(highlighted_html)
-}
+    /// Sets the numbering style of ordered lists, e.g.
+    /// [`ListStyle::LowerRoman`] for i, ii, iii.
+    ///
+    /// A `type` attribute is added to every `<ol>`, including nested
+    /// lists; lists that already set one, and the list of footnotes, are
+    /// left unchanged. Start numbers are kept. By default no attribute is
+    /// added.
+    pub fn with_ordered_list_style(mut self, style: ListStyle) -> Self {
+        self.ordered_list_style = Some(style);
+        self
+    }
 
-fn extract_code_block<'a>(
-    cap: &'a regex::Captures<'a>,
-) -> Result<(&'a str, String), MarkdownError> {
-    let lang = &cap[1];
-    let code = html_escape::decode_html_entities(&cap[2]);
-    Ok((lang, code.to_string()))
-}
+    /// Sets the classes added to definition lists, e.g.
+    /// [`DefinitionListClasses::default()`] for `<dl class="dl-horizontal">`.
+    ///
+    /// Requires Comrak's `description_lists` extension.
+    pub fn with_definition_list_classes(
+        mut self,
+        classes: DefinitionListClasses,
+    ) -> Self {
+        self.definition_list_classes = Some(classes);
+        self
+    }
 
-fn highlight_code(
-    lang: &str,
-    code: &str,
-) -> Result<String, MarkdownError> {
-    debug!(
-        "Attempting to highlight code block with language: {}",
-        lang
-    );
-    apply_syntax_highlighting(code, lang).map_err(|e| {
-        MarkdownError::ConversionError(format!(
-            "Failed to highlight code block in language '{}': {}",
-            lang, e
-        ))
-    })
-}
+    /// Enables `$...$` inline and `$$...$$` display math.
+    ///
+    /// Inline math is emitted as `<span class="math inline">` and display
+    /// math as `<div class="math display">`, with the TeX left inside for
+    /// client-side rendering. Dollar signs in code, and amounts such as
+    /// `$5`, are left alone.
+    pub fn with_math(mut self, renderer: MathRenderer) -> Self {
+        self.math_renderer = Some(renderer);
+        self
+    }
 
-fn format_highlighted_code(
-    lang: &str,
-    highlighted_code: &str,
-) -> String {
-    format!(
-        "<pre><code class=\"language-{}\">{}</code></pre>",
-        lang, highlighted_code
-    )
-}
+    /// Sets the code block languages that skip syntax highlighting and are
+    /// emitted as `<pre class="{language}">` with the escaped source, so a
+    /// client-side library can render them. Defaults to `mermaid`.
+    pub fn with_passthrough_languages(
+        mut self,
+        languages: Vec<String>,
+    ) -> Self {
+        self.passthrough_languages = languages;
+        self
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Enables or disables a copy-to-clipboard button on code blocks.
+    ///
+    /// Each highlighted block is wrapped in `<div class="code-block">`
+    /// with a `<button class="copy-btn" aria-label="Copy">` placed before,
+    /// not inside, the `<pre>`. The button needs client-side script to
+    /// do the copying.
+    pub fn with_code_copy_button(mut self, enable: bool) -> Self {
+        self.enable_code_copy_button = enable;
+        self
+    }
 
-    #[test]
-    fn test_process_markdown_with_all_features() {
-        let markdown = r#"
-# Test Markdown
+    /// Enables or disables language labels on code blocks.
+    ///
+    /// Highlighted blocks get an `aria-label` naming their language in
+    /// human-readable form, such as `<pre aria-label="Rust code">`.
+    /// Blocks without a known language are left unlabelled.
+    pub fn with_code_aria_labels(mut self, enable: bool) -> Self {
+        self.enable_code_aria_labels = enable;
+        self
+    }
 
-Here's a table:
+    /// Enables or disables `spellcheck="false"` on code blocks.
+    ///
+    /// The attribute is added to the `<pre>` of each highlighted block,
+    /// and inherited by its `<code>`, so that browsers do not underline
+    /// identifiers as misspelled words when the content is editable.
+    pub fn with_disable_spellcheck_in_code(
+        mut self,
+        enable: bool,
+    ) -> Self {
+        self.disable_spellcheck_in_code = enable;
+        self
+    }
 
-| Left | Center | Right |
-|:-----|:------:|------:|
-| 1    |   2    |     3 |
+    /// Enables or disables the `data-highlight-error` attribute.
+    ///
+    /// When a code block falls back to plain text, e.g. because its
+    /// language is unknown, the reason is recorded, HTML-escaped, in a
+    /// `data-highlight-error` attribute on its `<pre>`. This makes blocks
+    /// that silently degraded easy to find in the output.
+    pub fn with_highlight_error_attribute(
+        mut self,
+        enable: bool,
+    ) -> Self {
+        self.enable_highlight_error_attribute = enable;
+        self
+    }
 
-```rust
-fn main() {
-    println!("Hello, world!");
-}
-```
+    /// Enables or disables sanitization of the final HTML.
+    ///
+    /// Raw HTML is always passed through by Comrak, so use this when
+    /// rendering untrusted Markdown: scripts, event handler attributes
+    /// and `javascript:` URLs are removed, while the alerts, tables and
+    /// highlighted code this crate generates are kept. Links also receive
+    /// `rel="noopener noreferrer"`. See
+    /// [`sanitize_html`](crate::extensions::sanitize_html).
+    ///
+    /// Requires the `sanitize` feature; without it, processing fails with
+    /// `MarkdownError::InvalidOptionsError`.
+    pub fn with_sanitize_html(mut self, enable: bool) -> Self {
+        self.enable_sanitize_html = enable;
+        self
+    }
 
-<div class="note">This is a note.</div>
+    /// Enables or disables removal of HTML comments.
+    ///
+    /// Comments such as `<!-- TODO -->` are removed from the final
+    /// HTML; comments inside `<pre>` and `<code>` elements are kept.
+    pub fn with_strip_comments(mut self, enable: bool) -> Self {
+        self.enable_strip_comments = enable;
+        self
+    }
 
-<div class="warning">This is a warning.</div>
+    /// Enables or disables minification of the final HTML.
+    ///
+    /// Whitespace between tags is collapsed; the contents of `<pre>` and
+    /// `<code>` elements are left byte-for-byte unchanged.
+    pub fn with_minify_output(mut self, enable: bool) -> Self {
+        self.enable_minify_output = enable;
+        self
+    }
 
-<div class="tip">This is a tip.</div>
-"#;
+    /// Enables `{{include:path}}` directives, replacing each with the
+    /// contents of the file at `path`, relative to `base_dir`.
+    ///
+    /// Includes are expanded after the
+    /// [pre-processor](Self::with_pre_processor) and before Comrak parses
+    /// the content, so included files are rendered as part of the
+    /// document. Directives inside fenced code blocks are left as
+    /// written. Processing fails with `MarkdownError::IoError` if an
+    /// included file cannot be read, and with
+    /// `MarkdownError::IncludeError` if a path is absolute, contains `..`
+    /// or resolves outside `base_dir`, or if includes are circular or
+    /// nested deeper than
+    /// [`with_max_include_depth`](Self::with_max_include_depth). The
+    /// expanded Markdown is subject to
+    /// [`with_max_input_bytes`](Self::with_max_input_bytes).
+    pub fn with_include_base_dir(mut self, base_dir: PathBuf) -> Self {
+        self.include_base_dir = Some(base_dir);
+        self
+    }
 
-        let options = default_markdown_options();
+    /// Sets how deeply included files may include other files. Defaults
+    /// to 8.
+    pub fn with_max_include_depth(mut self, depth: usize) -> Self {
+        self.max_include_depth = depth;
+        self
+    }
 
-        let result = process_markdown(markdown, &options);
-        assert!(result.is_ok(), "Markdown processing failed");
+    /// Sets a function that receives the raw Markdown and returns the
+    /// Markdown to render, e.g. to expand `{{include:...}}` macros.
+    ///
+    /// It runs once per document as the very first step, after the input
+    /// size check and before Comrak parses the content. Frontmatter is
+    /// extracted by Comrak, so the pre-processor sees it still in place
+    /// at the top of the document.
+    pub fn with_pre_processor(
+        mut self,
+        pre_processor: Arc<dyn Fn(String) -> String + Send + Sync + 'a>,
+    ) -> Self {
+        self.pre_processor = Some(pre_processor);
+        self
+    }
 
-        let html = result.unwrap();
+    /// Sets a function that receives the final HTML and returns the HTML
+    /// to output, for tweaks the built-in passes do not cover.
+    ///
+    /// It runs once per document as the very last step, after
+    /// [sanitization](Self::with_sanitize_html) and
+    /// [minification](Self::with_minify_output), so its output is
+    /// returned unchanged.
+    pub fn with_post_processor(
+        mut self,
+        post_processor: Arc<
+            dyn Fn(String) -> String + Send + Sync + 'a,
+        >,
+    ) -> Self {
+        self.post_processor = Some(post_processor);
+        self
+    }
 
-        assert!(
-            html.contains(
-                r#"<div class="table-responsive"><table class="table">"#
-            ),
-            "Table not processed correctly"
-        );
-        assert!(
-            html.contains(r#"<pre><code class="language-rust">"#),
-            "Syntax highlighting not applied"
-        );
-        assert!(html.contains(r#"<div class="alert alert-info" role="alert"><strong>Note:</strong>"#), "Note block not processed");
-        assert!(html.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong>"#), "Warning block not processed");
-        assert!(html.contains(r#"<div class="alert alert-success" role="alert"><strong>Tip:</strong>"#), "Tip block not processed");
+    /// Sets the reading speed used to estimate reading time. Defaults to
+    /// 200 words per minute.
+    pub fn with_words_per_minute(
+        mut self,
+        words_per_minute: u32,
+    ) -> Self {
+        self.words_per_minute = words_per_minute;
+        self
     }
 
-    #[test]
-    fn test_process_markdown_without_custom_blocks() {
-        let markdown = "# Test Markdown\n<div class=\"note\">This is a note.</div>";
-        let options = MarkdownOptions::new()
-            .with_custom_blocks(false)
-            .with_comrak_options({
-                let mut opts = ComrakOptions::default();
-                opts.extension.table = true; // Enable table extension if you have tables
-                opts
-            });
+    /// Allows or escapes raw HTML in the Markdown. Defaults to `true`,
+    /// which overrides Comrak's `render.unsafe_` setting.
+    ///
+    /// When disabled, raw HTML such as `<div>` is escaped and shown as
+    /// text. Custom blocks rely on raw HTML surviving Comrak, so they are
+    /// not processed, and enabling table captions or fenced custom blocks
+    /// fails validation; GitHub alerts still work.
+    pub fn with_allow_raw_html(mut self, allow: bool) -> Self {
+        self.allow_raw_html = allow;
+        self
+    }
 
-        let result = process_markdown(markdown, &options);
-        assert!(
-            result.is_ok(),
-            "Markdown processing failed: {:?}",
-            result
-        );
-        let html = result.unwrap();
+    /// Rejects Markdown input larger than `max_bytes` with a
+    /// `MarkdownError::ParseError`, before any parsing is done. By
+    /// default the input size is unlimited.
+    ///
+    /// The post-processing passes use the `regex` crate, which matches in
+    /// linear time, so this limit bounds the total work done per call.
+    pub fn with_max_input_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_input_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Enables or disables custom blocks.
+    pub fn with_custom_blocks(mut self, enable: bool) -> Self {
+        self.enable_custom_blocks = enable;
+        self
+    }
+
+    /// Registers an additional custom block type, e.g.
+    /// `with_custom_block("success", "alert-success", "Success")`.
+    ///
+    /// Registering a built-in name replaces its rendering.
+    pub fn with_custom_block(
+        mut self,
+        name: impl Into<String>,
+        alert_class: impl Into<String>,
+        title: impl Into<String>,
+    ) -> Self {
+        self.custom_block_registry
+            .register(name, alert_class, title);
+        self
+    }
+
+    /// Enables or disables syntax highlighting for code blocks.
+    ///
+    /// Has no effect unless the `syntax_highlighting` feature is
+    /// enabled; code blocks are then left as Comrak rendered them.
+    pub fn with_syntax_highlighting(mut self, enable: bool) -> Self {
+        self.enable_syntax_highlighting = enable;
+        self
+    }
+
+    /// Enables or disables enhanced table formatting.
+    pub fn with_enhanced_tables(mut self, enable: bool) -> Self {
+        self.enable_enhanced_tables = enable;
+        self
+    }
+
+    /// Enables or disables line numbers in highlighted code blocks.
+    ///
+    /// Each line is prefixed with a `<span class="line-number">`, counting
+    /// from 1 in every code block. Blank lines, including trailing blank
+    /// lines inside the fence, are counted; the final line terminator
+    /// does not start a new line.
+    pub fn with_line_numbers(mut self, enable: bool) -> Self {
+        self.enable_line_numbers = enable;
+        self
+    }
+
+    /// Sets the number of columns between tab stops in highlighted code
+    /// blocks, e.g. `4`.
+    ///
+    /// Tabs are expanded to spaces before highlighting, so code renders
+    /// the same whatever the browser's tab size, which defaults to 8.
+    /// Comrak has no tab width setting of its own, so Markdown outside
+    /// code blocks is unaffected.
+    pub fn with_tab_width(mut self, width: usize) -> Self {
+        self.tab_width = Some(width);
+        self
+    }
+
+    /// Enables or disables GitHub-style alerts.
+    ///
+    /// Blockquotes starting with `[!NOTE]`, `[!TIP]`, `[!IMPORTANT]`,
+    /// `[!WARNING]` or `[!CAUTION]` are rendered like the equivalent
+    /// custom block.
+    pub fn with_github_alerts(mut self, enable: bool) -> Self {
+        self.enable_github_alerts = enable;
+        self
+    }
+
+    /// Enables or disables collapsible custom blocks.
+    ///
+    /// When enabled, every custom block is rendered as a `<details>`
+    /// element whose `<summary>` shows the block title. Individual blocks
+    /// can opt in with a `collapse` class instead.
+    pub fn with_collapsible_blocks(mut self, enable: bool) -> Self {
+        self.enable_collapsible_blocks = enable;
+        self
+    }
+
+    /// Enables or disables icons in custom blocks.
+    ///
+    /// When enabled, each block starts with
+    /// `<span class="alert-icon ..." aria-hidden="true"></span>`, using the
+    /// icon class of the block type (e.g. `icon-info` for notes).
+    pub fn with_block_icons(mut self, enable: bool) -> Self {
+        self.enable_block_icons = enable;
+        self
+    }
+
+    /// Enables or disables `<aside>` elements for non-critical custom
+    /// blocks.
+    ///
+    /// When enabled, blocks with the `note` role (note, tip and info by
+    /// default) and the matching GitHub alerts are rendered as
+    /// `<aside class="alert ..." role="note">`. Blocks with the `alert`
+    /// role, such as warning, important and caution, stay `<div>`
+    /// elements.
+    pub fn with_aside_for_notes(mut self, enable: bool) -> Self {
+        self.enable_aside_for_notes = enable;
+        self
+    }
+
+    /// Enables or disables fenced custom blocks.
+    ///
+    /// A block opened by a `:::note` line (optionally followed by a title,
+    /// `:::note Heads up`) and closed by a `:::` line renders like
+    /// `<div class="note">`, and its content is rendered as Markdown.
+    /// Blocks may be nested; an unclosed block runs to the end of the
+    /// document. Requires custom blocks and raw HTML to be enabled.
+    pub fn with_fenced_custom_blocks(mut self, enable: bool) -> Self {
+        self.enable_fenced_custom_blocks = enable;
+        self
+    }
+
+    /// Enables or disables escaping of custom block content.
+    ///
+    /// When enabled, everything between a `<div class="...">` custom
+    /// block's tags is HTML-escaped before it is wrapped, so untrusted
+    /// markup such as `<script>` is shown as text. This includes any
+    /// Markdown Comrak rendered inside the block, whose tags will then
+    /// appear literally. GitHub alerts are not affected.
+    pub fn with_sanitize_custom_blocks(mut self, enable: bool) -> Self {
+        self.enable_sanitize_custom_blocks = enable;
+        self
+    }
+
+    /// Overrides the ARIA role of custom blocks, keyed by block class name,
+    /// e.g. `{"tip": "alert"}`.
+    ///
+    /// By default `warning`, `important`, `caution` and `danger` blocks use
+    /// `role="alert"`, which screen readers announce immediately, and all
+    /// other blocks use `role="note"`.
+    pub fn with_block_role_overrides(
+        mut self,
+        overrides: HashMap<String, String>,
+    ) -> Self {
+        self.block_role_overrides = overrides;
+        self
+    }
+
+    /// Sets what happens to a `<div class="...">` whose class is not a
+    /// registered custom block type. Defaults to
+    /// [`UnknownBlockBehavior::RenderError`].
+    pub fn with_unknown_block_behavior(
+        mut self,
+        behavior: UnknownBlockBehavior,
+    ) -> Self {
+        self.unknown_block_behavior = behavior;
+        self
+    }
+
+    /// Sets a custom theme for syntax highlighting.
+    ///
+    /// For the bundled themes,
+    /// [`with_syntax_theme`](Self::with_syntax_theme) avoids mistyped
+    /// names.
+    pub fn with_custom_theme(mut self, theme: String) -> Self {
+        self.syntax_theme = Some(theme);
+        self
+    }
+
+    /// Sets one of the bundled themes for syntax highlighting.
+    pub fn with_syntax_theme(mut self, theme: SyntaxTheme) -> Self {
+        self.syntax_theme = Some(theme.as_name().to_string());
+        self
+    }
+
+    /// Sets a light and a dark theme so highlighted code can follow the
+    /// reader's colour scheme.
+    ///
+    /// Every code block then contains two `<pre>` elements, marked
+    /// `data-theme="light"` and `data-theme="dark"`. Hide one of them with
+    /// CSS, for example:
+    ///
+    /// ```css
+    /// pre[data-theme="dark"] { display: none; }
+    /// @media (prefers-color-scheme: dark) {
+    ///     pre[data-theme="light"] { display: none; }
+    ///     pre[data-theme="dark"] { display: block; }
+    /// }
+    /// ```
+    pub fn with_theme_pair(
+        mut self,
+        light: String,
+        dark: String,
+    ) -> Self {
+        self.theme_pair = Some((light, dark));
+        self
+    }
+
+    /// Highlights code blocks tagged `lang` (e.g. `"bash"`) with the
+    /// theme named `theme`, instead of the global theme.
+    ///
+    /// Processing fails with `MarkdownError::SyntaxHighlightError` if the
+    /// theme does not exist.
+    pub fn with_theme_for_language(
+        mut self,
+        lang: impl Into<String>,
+        theme: impl Into<String>,
+    ) -> Self {
+        self.language_themes
+            .insert(lang.into().to_lowercase(), theme.into());
+        self
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    /// Loads a syntax highlighting theme from a `.tmTheme` file.
+    ///
+    /// The loaded theme takes precedence over any theme set with
+    /// `with_custom_theme`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MarkdownError::SyntaxSetError` if the file cannot be
+    /// read or parsed.
+    pub fn with_theme_from_file(
+        mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, MarkdownError> {
+        self.custom_theme = Some(load_theme_from_file(path)?);
+        Ok(self)
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    /// Adds the `.sublime-syntax` files found in `path` to the default
+    /// syntaxes used for highlighting.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MarkdownError::SyntaxSetError` if the directory cannot be
+    /// read or a syntax fails to compile.
+    pub fn with_extra_syntaxes_dir(
+        mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, MarkdownError> {
+        self.syntax_set = Some(load_syntax_set_with_extras(path)?);
+        Ok(self)
+    }
+
+    /// Adds a language alias so that code blocks tagged `from` are
+    /// highlighted with the syntax named `to` (e.g. `"ts"` to
+    /// `"TypeScript"`).
+    pub fn with_language_alias(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> Self {
+        self.language_aliases
+            .insert(from.into().to_lowercase(), to.into());
+        self
+    }
+
+    /// Sets what happens when a code block cannot be highlighted.
+    pub fn with_highlight_fallback(
+        mut self,
+        fallback: HighlightFallback,
+    ) -> Self {
+        self.highlight_fallback = fallback;
+        self
+    }
+
+    /// Sets custom Comrak options.
+    pub fn with_comrak_options(
+        mut self,
+        options: ComrakOptions<'a>,
+    ) -> Self {
+        self.comrak_options = options;
+        self
+    }
+
+    /// Enables or disables Comrak's table extension, which enhanced
+    /// tables require.
+    pub fn with_tables(mut self, enable: bool) -> Self {
+        self.comrak_options.extension.table = enable;
+        self
+    }
+
+    /// Enables or disables Comrak's `~~strikethrough~~` extension.
+    pub fn with_strikethrough(mut self, enable: bool) -> Self {
+        self.comrak_options.extension.strikethrough = enable;
+        self
+    }
+
+    /// Enables or disables Comrak's `- [ ]` task list extension.
+    pub fn with_tasklist(mut self, enable: bool) -> Self {
+        self.comrak_options.extension.tasklist = enable;
+        self
+    }
+
+    /// Enables or disables Comrak's extension linking bare URLs.
+    pub fn with_autolink(mut self, enable: bool) -> Self {
+        self.comrak_options.extension.autolink = enable;
+        self
+    }
+
+    /// Enables or disables Comrak's `[^1]` footnote extension.
+    pub fn with_footnotes(mut self, enable: bool) -> Self {
+        self.comrak_options.extension.footnotes = enable;
+        self
+    }
+
+    /// Returns the options used when rendering custom blocks and GitHub
+    /// alerts, for calling
+    /// [`process_custom_blocks_with_options`](crate::extensions::process_custom_blocks_with_options)
+    /// directly.
+    pub fn custom_block_options(&self) -> CustomBlockOptions {
+        CustomBlockOptions {
+            collapsible: self.enable_collapsible_blocks,
+            icons: self.enable_block_icons,
+            aside_for_notes: self.enable_aside_for_notes,
+            unknown_blocks: self.unknown_block_behavior,
+            escape_content: self.enable_sanitize_custom_blocks,
+            role_overrides: self
+                .block_role_overrides
+                .iter()
+                .map(|(name, role)| (name.to_lowercase(), role.clone()))
+                .collect(),
+            direction: self.text_direction,
+            alert_prefix: Some(self.class_names.alert_prefix.clone()),
+        }
+    }
+
+    /// Returns the options used when enhancing tables, for calling
+    /// [`process_tables_with_options`](crate::extensions::process_tables_with_options)
+    /// directly.
+    pub fn table_options(&self) -> TableOptions {
+        TableOptions {
+            classes: self.table_classes.clone(),
+            footer_marker: self.table_footer_marker.clone(),
+            direction: self.text_direction,
+            wrapper_class: self.class_names.table_wrapper.clone(),
+            responsive_wrapper: self.enable_table_responsive_wrapper,
+        }
+    }
+
+    /// Layers `overrides` on top of these options, e.g. per-page options
+    /// over a site-wide base.
+    ///
+    /// A field of `overrides` takes precedence when it is set, and the
+    /// value from `self` is kept otherwise:
+    ///
+    /// - `Option` fields, such as `syntax_theme`, `custom_theme` and the
+    ///   URL rewriters, are set when they are `Some`;
+    /// - block role overrides, language themes and language aliases are
+    ///   combined, with the entries of `overrides` winning on conflict;
+    /// - `comrak_options` are merged one by one with the same rules,
+    ///   against `ComrakOptions::default()`;
+    /// - every other field is set when it differs from its value in
+    ///   [`MarkdownOptions::default()`].
+    ///
+    /// As a consequence, `overrides` cannot restore a default value that
+    /// `self` has changed, such as turning enhanced tables back on.
+    pub fn merge(
+        self,
+        overrides: MarkdownOptions<'a>,
+    ) -> MarkdownOptions<'a> {
+        self.merge_fields(overrides)
+    }
+
+    /// Lists the fields that differ between these options and `other`,
+    /// to help find out why two renders differ.
+    ///
+    /// Each entry reads `field: old -> new`, e.g.
+    /// `enable_enhanced_tables: true -> false`. Comrak
+    /// options are compared one by one, e.g.
+    /// `comrak_options.extension.table: true -> false`. Functions, such
+    /// as URL rewriters, are only compared by whether they are set, and a
+    /// custom theme by its name.
+    pub fn describe_diff(&self, other: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        self.diff_fields(other, &mut changes);
+        changes
+    }
+
+    /// Validates the `MarkdownOptions` to ensure they are consistent and compatible.
+    ///
+    /// The following combinations are rejected:
+    ///
+    /// - enhanced tables or table captions without Comrak's table
+    ///   extension;
+    /// - definition list classes without Comrak's description list
+    ///   extension;
+    /// - table captions or fenced custom blocks when raw HTML is not
+    ///   allowed, as both are carried through Comrak as raw HTML;
+    /// - fenced custom blocks when custom blocks are disabled;
+    /// - heading permalinks without heading anchors;
+    /// - stripping image dimension parameters without image dimensions
+    ///   from the query;
+    /// - line numbers, a code copy button, disabled spellcheck in code or
+    ///   a tab width without syntax highlighting;
+    /// - a tab width of zero;
+    /// - HTML sanitization without the `sanitize` feature.
+    ///
+    /// GitHub alerts are built from blockquotes, so they work whether or
+    /// not raw HTML is allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MarkdownError::InvalidOptionsError` describing the first
+    /// inconsistency found.
+    pub fn validate(&self) -> Result<(), MarkdownError> {
+        if self.enable_enhanced_tables
+            && !self.comrak_options.extension.table
+        {
+            return Err(MarkdownError::InvalidOptionsError("Enhanced tables are enabled, but Comrak table extension is disabled.".to_string()));
+        }
+        if self.definition_list_classes.is_some()
+            && !self.comrak_options.extension.description_lists
+        {
+            return Err(MarkdownError::InvalidOptionsError("Definition list classes are set, but Comrak description list extension is disabled.".to_string()));
+        }
+        if self.enable_table_captions {
+            if !self.comrak_options.extension.table {
+                return Err(MarkdownError::InvalidOptionsError("Table captions are enabled, but Comrak table extension is disabled.".to_string()));
+            }
+            if !self.allow_raw_html {
+                return Err(MarkdownError::InvalidOptionsError("Table captions are enabled, but raw HTML is not allowed.".to_string()));
+            }
+        }
+        if self.enable_fenced_custom_blocks {
+            if !self.enable_custom_blocks {
+                return Err(MarkdownError::InvalidOptionsError("Fenced custom blocks are enabled, but custom blocks are disabled.".to_string()));
+            }
+            if !self.allow_raw_html {
+                return Err(MarkdownError::InvalidOptionsError("Fenced custom blocks are enabled, but raw HTML is not allowed.".to_string()));
+            }
+        }
+        if self.enable_heading_permalinks
+            && !self.enable_heading_anchors
+        {
+            return Err(MarkdownError::InvalidOptionsError("Heading permalinks are enabled, but heading anchors are disabled.".to_string()));
+        }
+        if self.strip_image_dimension_params
+            && !self.enable_image_dimensions
+        {
+            return Err(MarkdownError::InvalidOptionsError("Stripping image dimension parameters is enabled, but image dimensions from the query are disabled.".to_string()));
+        }
+        if !self.enable_syntax_highlighting {
+            if self.enable_line_numbers {
+                return Err(MarkdownError::InvalidOptionsError("Line numbers are enabled, but syntax highlighting is disabled.".to_string()));
+            }
+            if self.enable_code_copy_button {
+                return Err(MarkdownError::InvalidOptionsError("The code copy button is enabled, but syntax highlighting is disabled.".to_string()));
+            }
+            if self.disable_spellcheck_in_code {
+                return Err(MarkdownError::InvalidOptionsError("Disabling spellcheck in code is enabled, but syntax highlighting is disabled.".to_string()));
+            }
+            if self.tab_width.is_some() {
+                return Err(MarkdownError::InvalidOptionsError("A tab width is set, but syntax highlighting is disabled.".to_string()));
+            }
+        }
+        if self.tab_width == Some(0) {
+            return Err(MarkdownError::InvalidOptionsError(
+                "The tab width must be greater than zero.".to_string(),
+            ));
+        }
+        if self.enable_sanitize_html && !cfg!(feature = "sanitize") {
+            return Err(MarkdownError::InvalidOptionsError("HTML sanitization is enabled, but the `sanitize` feature is disabled.".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Returns `overrides` if it differs from `default`, and `base`
+/// otherwise.
+fn pick<T: PartialEq>(base: T, overrides: T, default: &T) -> T {
+    if overrides != *default {
+        overrides
+    } else {
+        base
+    }
+}
+
+/// Combines two maps, with the entries of `overrides` winning on
+/// conflict.
+fn merge_maps(
+    mut base: HashMap<String, String>,
+    overrides: HashMap<String, String>,
+) -> HashMap<String, String> {
+    base.extend(overrides);
+    base
+}
+
+/// Records `field: old -> new` in `changes` if `old` and `new` differ.
+fn diff<T: fmt::Debug + PartialEq>(
+    changes: &mut Vec<String>,
+    field: &str,
+    old: &T,
+    new: &T,
+) {
+    if old != new {
+        changes.push(format!("{}: {:?} -> {:?}", field, old, new));
+    }
+}
+
+/// Passes the Comrak options compared by value, as `section.field`
+/// paths, to `$callback` after `$args`.
+///
+/// `ListStyleType`, which does not implement `PartialEq`, and the
+/// Comrak callbacks are handled separately.
+macro_rules! comrak_fields {
+    ($callback:ident!($($args:tt)*)) => {
+        $callback!(
+            $($args)*;
+            extension.strikethrough,
+            extension.tagfilter,
+            extension.table,
+            extension.autolink,
+            extension.tasklist,
+            extension.superscript,
+            extension.header_ids,
+            extension.footnotes,
+            extension.description_lists,
+            extension.front_matter_delimiter,
+            extension.multiline_block_quotes,
+            extension.math_dollars,
+            extension.math_code,
+            extension.wikilinks_title_after_pipe,
+            extension.wikilinks_title_before_pipe,
+            extension.underline,
+            extension.subscript,
+            extension.spoiler,
+            extension.greentext,
+            parse.smart,
+            parse.default_info_string,
+            parse.relaxed_tasklist_matching,
+            parse.relaxed_autolinks,
+            render.hardbreaks,
+            render.github_pre_lang,
+            render.full_info_string,
+            render.width,
+            render.unsafe_,
+            render.escape,
+            render.sourcepos,
+            render.experimental_inline_sourcepos,
+            render.escaped_char_spans,
+            render.ignore_setext,
+            render.ignore_empty_links,
+            render.gfm_quirks,
+            render.prefer_fenced,
+            render.figure_with_caption,
+            render.tasklist_classes,
+            render.ol_width
+        )
+    };
+}
+
+macro_rules! pick_comrak_fields {
+    ($base:ident, $overrides:ident, $defaults:ident;
+     $($section:ident.$field:ident),*) => {
+        $(
+            $base.$section.$field = pick(
+                $base.$section.$field,
+                $overrides.$section.$field,
+                &$defaults.$section.$field,
+            );
+        )*
+    };
+}
+
+macro_rules! diff_comrak_fields {
+    ($changes:ident, $old:ident, $new:ident;
+     $($section:ident.$field:ident),*) => {
+        $(
+            diff(
+                $changes,
+                concat!(
+                    "comrak_options.",
+                    stringify!($section),
+                    ".",
+                    stringify!($field)
+                ),
+                &$old.$section.$field,
+                &$new.$section.$field,
+            );
+        )*
+    };
+}
+
+/// Layers the Comrak options of `overrides` on top of `base`, option by
+/// option, following the rules of [`MarkdownOptions::merge`].
+fn merge_comrak_options<'c>(
+    mut base: ComrakOptions<'c>,
+    overrides: ComrakOptions<'c>,
+) -> ComrakOptions<'c> {
+    let defaults = ComrakOptions::default();
+    comrak_fields!(pick_comrak_fields!(base, overrides, defaults));
+    if overrides.render.list_style as u8
+        != defaults.render.list_style as u8
+    {
+        base.render.list_style = overrides.render.list_style;
+    }
+    base.extension.image_url_rewriter = overrides
+        .extension
+        .image_url_rewriter
+        .or(base.extension.image_url_rewriter);
+    base.extension.link_url_rewriter = overrides
+        .extension
+        .link_url_rewriter
+        .or(base.extension.link_url_rewriter);
+    base.parse.broken_link_callback = overrides
+        .parse
+        .broken_link_callback
+        .or(base.parse.broken_link_callback);
+    base
+}
+
+/// Records the Comrak options that differ between `old` and `new`.
+fn diff_comrak_options(
+    changes: &mut Vec<String>,
+    old: &ComrakOptions<'_>,
+    new: &ComrakOptions<'_>,
+) {
+    comrak_fields!(diff_comrak_fields!(changes, old, new));
+    diff(
+        changes,
+        "comrak_options.render.list_style",
+        &(old.render.list_style as u8 as char),
+        &(new.render.list_style as u8 as char),
+    );
+    diff(
+        changes,
+        "comrak_options.extension.image_url_rewriter",
+        &old.extension.image_url_rewriter.is_some(),
+        &new.extension.image_url_rewriter.is_some(),
+    );
+    diff(
+        changes,
+        "comrak_options.extension.link_url_rewriter",
+        &old.extension.link_url_rewriter.is_some(),
+        &new.extension.link_url_rewriter.is_some(),
+    );
+    diff(
+        changes,
+        "comrak_options.parse.broken_link_callback",
+        &old.parse.broken_link_callback.is_some(),
+        &new.parse.broken_link_callback.is_some(),
+    );
+}
+
+/// Generates the per-field work of [`MarkdownOptions::merge`],
+/// [`MarkdownOptions::describe_diff`] and the `Debug` implementation
+/// from one list of fields, so that a new option is covered by all
+/// three; `merge` fails to compile if a field is missing. Each field is tagged with how it is handled:
+///
+/// - `value` fields are merged with [`pick`] and compared by value;
+/// - `option` fields are set by `overrides` when `Some`;
+/// - `map` fields are combined with [`merge_maps`];
+/// - `function` fields are set by `overrides` when `Some`, and only
+///   compared and printed by whether they are set;
+/// - `theme` fields are compared and printed by theme name;
+/// - `comrak` fields are merged and compared option by option.
+macro_rules! markdown_option_fields {
+    ($($(#[$attr:meta])* $field:ident: $kind:ident,)*) => {
+        impl<'a> MarkdownOptions<'a> {
+            fn merge_fields(
+                self,
+                overrides: MarkdownOptions<'a>,
+            ) -> MarkdownOptions<'a> {
+                let defaults = MarkdownOptions::default();
+                MarkdownOptions {
+                    $(
+                        $(#[$attr])*
+                        $field: merge_field!(
+                            $kind,
+                            self.$field,
+                            overrides.$field,
+                            defaults.$field
+                        ),
+                    )*
+                }
+            }
+
+            fn diff_fields(&self, other: &Self, changes: &mut Vec<String>) {
+                $(
+                    $(#[$attr])*
+                    diff_field!(
+                        $kind,
+                        changes,
+                        stringify!($field),
+                        self.$field,
+                        other.$field
+                    );
+                )*
+            }
+        }
+
+        impl fmt::Debug for MarkdownOptions<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut debug = f.debug_struct("MarkdownOptions");
+                $(
+                    $(#[$attr])*
+                    debug.field(
+                        stringify!($field),
+                        &debug_field!($kind, self.$field),
+                    );
+                )*
+                debug.finish()
+            }
+        }
+    };
+}
+
+macro_rules! merge_field {
+    (value, $base:expr, $overrides:expr, $default:expr) => {
+        pick($base, $overrides, &$default)
+    };
+    (map, $base:expr, $overrides:expr, $default:expr) => {
+        merge_maps($base, $overrides)
+    };
+    (comrak, $base:expr, $overrides:expr, $default:expr) => {
+        merge_comrak_options($base, $overrides)
+    };
+    ($kind:ident, $base:expr, $overrides:expr, $default:expr) => {
+        $overrides.or($base)
+    };
+}
+
+macro_rules! diff_field {
+    (comrak, $changes:expr, $name:expr, $old:expr, $new:expr) => {
+        diff_comrak_options($changes, &$old, &$new)
+    };
+    ($kind:ident, $changes:expr, $name:expr, $old:expr, $new:expr) => {
+        diff(
+            $changes,
+            $name,
+            &debug_field!($kind, $old),
+            &debug_field!($kind, $new),
+        )
+    };
+}
+
+macro_rules! debug_field {
+    (function, $value:expr) => {
+        $value.is_some()
+    };
+    (theme, $value:expr) => {
+        $value.as_ref().map(|theme| &theme.name)
+    };
+    ($kind:ident, $value:expr) => {
+        &$value
+    };
+}
+
+markdown_option_fields! {
+    comrak_options: comrak,
+    allow_raw_html: value,
+    max_input_bytes: option,
+    enable_custom_blocks: value,
+    enable_syntax_highlighting: value,
+    enable_enhanced_tables: value,
+    enable_line_numbers: value,
+    tab_width: option,
+    enable_github_alerts: value,
+    enable_collapsible_blocks: value,
+    enable_block_icons: value,
+    enable_aside_for_notes: value,
+    unknown_block_behavior: value,
+    enable_fenced_custom_blocks: value,
+    enable_sanitize_custom_blocks: value,
+    block_role_overrides: map,
+    table_classes: value,
+    enable_table_captions: value,
+    table_footer_marker: option,
+    enable_table_responsive_wrapper: value,
+    enable_heading_anchors: value,
+    enable_heading_permalinks: value,
+    heading_scroll_margin: option,
+    words_per_minute: value,
+    enable_emoji_shortcodes: value,
+    enable_kbd_shortcuts: value,
+    abbreviations: map,
+    abbreviate_every_occurrence: value,
+    enable_external_link_attributes: value,
+    base_url: option,
+    link_url_rewriter: function,
+    image_url_rewriter: function,
+    enable_lazy_images: value,
+    enable_image_dimensions: value,
+    strip_image_dimension_params: value,
+    strip_fragment_paragraph: value,
+    footnote_heading: option,
+    text_direction: option,
+    ordered_list_style: option,
+    definition_list_classes: option,
+    math_renderer: option,
+    enable_sanitize_html: value,
+    enable_strip_comments: value,
+    enable_minify_output: value,
+    include_base_dir: option,
+    max_include_depth: value,
+    pre_processor: function,
+    post_processor: function,
+    enable_code_copy_button: value,
+    enable_code_aria_labels: value,
+    disable_spellcheck_in_code: value,
+    enable_highlight_error_attribute: value,
+    syntax_theme: option,
+    #[cfg(feature = "syntax_highlighting")]
+    custom_theme: theme,
+    theme_pair: option,
+    language_themes: map,
+    #[cfg(feature = "syntax_highlighting")]
+    syntax_set: function,
+    language_aliases: map,
+    passthrough_languages: value,
+    highlight_fallback: value,
+    custom_block_registry: value,
+    class_names: value,
+}
+
+/// A builder for [`MarkdownOptions`] that hides the Comrak options each
+/// feature depends on. Created by [`MarkdownOptions::builder`].
+///
+/// Options not covered here can still be set on the built value with
+/// the `with_*` methods, including
+/// [`with_comrak_options`](MarkdownOptions::with_comrak_options).
+#[derive(Clone, Debug)]
+pub struct MarkdownOptionsBuilder<'a> {
+    options: MarkdownOptions<'a>,
+}
+
+impl<'a> MarkdownOptionsBuilder<'a> {
+    /// Enables GitHub-flavored Markdown: tables, strikethrough, task
+    /// lists, autolinks and the tag filter.
+    pub fn gfm(mut self) -> Self {
+        self.options.comrak_options.extension.tagfilter = true;
+        self.options = self
+            .options
+            .with_tables(true)
+            .with_strikethrough(true)
+            .with_tasklist(true)
+            .with_autolink(true);
+        self
+    }
+
+    /// Enables enhanced tables, along with the Comrak table extension
+    /// they require.
+    pub fn enhanced_tables(mut self) -> Self {
+        self.options =
+            self.options.with_tables(true).with_enhanced_tables(true);
+        self
+    }
+
+    /// Enables syntax highlighting of code blocks.
+    pub fn syntax_highlighting(mut self) -> Self {
+        self.options = self.options.with_syntax_highlighting(true);
+        self
+    }
+
+    /// Enables custom blocks such as `<div class="note">`, along with
+    /// the raw HTML they are carried through Comrak as.
+    pub fn custom_blocks(mut self) -> Self {
+        self.options = self
+            .options
+            .with_custom_blocks(true)
+            .with_allow_raw_html(true);
+        self
+    }
+
+    /// Enables GitHub-style alerts such as `> [!NOTE]`.
+    pub fn github_alerts(mut self) -> Self {
+        self.options = self.options.with_github_alerts(true);
+        self
+    }
+
+    /// Enables `[^1]` footnotes.
+    pub fn footnotes(mut self) -> Self {
+        self.options = self.options.with_footnotes(true);
+        self
+    }
+
+    /// Returns the configured options.
+    pub fn build(self) -> MarkdownOptions<'a> {
+        self.options
+    }
+}
+
+/// Creates a new instance of `MarkdownOptions` with default values.
+pub fn default_markdown_options() -> MarkdownOptions<'static> {
+    MarkdownOptions::new()
+        .with_custom_blocks(true)
+        .with_syntax_highlighting(true)
+        .with_enhanced_tables(true)
+        .with_comrak_options({
+            let mut opts = ComrakOptions::default();
+            opts.extension.table = true;
+            opts
+        })
+}
+
+/// A heading found in a rendered document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// The heading level, from 1 to 6.
+    pub level: u8,
+    /// The plain text of the heading.
+    pub text: String,
+    /// The heading's `id` attribute, if it has one.
+    pub id: Option<String>,
+}
+
+/// The HTML produced by [`process_markdown_with_metadata`], together with
+/// metadata gathered from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedDocument {
+    /// The rendered HTML.
+    pub html: String,
+    /// The number of words in the rendered text, ignoring HTML tags.
+    pub word_count: usize,
+    /// The estimated reading time in minutes, at least 1.
+    pub reading_time_minutes: u32,
+    /// The document's headings, in order of appearance.
+    pub headings: Vec<Heading>,
+}
+
+lazy_static! {
+    static ref HEADING_OUTLINE_RE: Regex =
+        Regex::new(r"(?s)<h([1-6])(\s[^>]*)?>(.*?)</h[1-6]>").unwrap();
+    static ref ID_ATTR_RE: Regex =
+        Regex::new(r#"\bid="([^"]*)""#).unwrap();
+    static ref PERMALINK_RE: Regex =
+        Regex::new(r#"(?s)<a class="heading-anchor"[^>]*>.*?</a>"#)
+            .unwrap();
+}
+
+impl RenderedDocument {
+    fn from_html(html: String, words_per_minute: u32) -> Self {
+        let word_count = html_to_text(&html).split_whitespace().count();
+        let headings = HEADING_OUTLINE_RE
+            .captures_iter(&html)
+            .map(|caps| Heading {
+                level: caps[1].parse().unwrap_or(1),
+                text: html_to_text(
+                    &PERMALINK_RE.replace_all(&caps[3], ""),
+                )
+                .trim()
+                .to_string(),
+                id: caps
+                    .get(2)
+                    .and_then(|attrs| {
+                        ID_ATTR_RE.captures(attrs.as_str())
+                    })
+                    .map(|id| id[1].to_string()),
+            })
+            .collect();
+
+        Self {
+            reading_time_minutes: reading_time_minutes(
+                word_count,
+                words_per_minute,
+            ),
+            word_count,
+            headings,
+            html,
+        }
+    }
+}
+
+/// Converts a word count into minutes at the given reading speed,
+/// rounding up to at least one minute.
+fn reading_time_minutes(
+    word_count: usize,
+    words_per_minute: u32,
+) -> u32 {
+    let words_per_minute = words_per_minute.max(1) as usize;
+    let minutes =
+        (word_count + words_per_minute - 1) / words_per_minute;
+    minutes.max(1) as u32
+}
+
+/// Estimates how many minutes it takes to read a Markdown document.
+///
+/// Words are counted from the Markdown source with HTML tags and code
+/// fence markers removed; the contents of fenced code blocks are
+/// counted. The result is rounded up to at least one minute.
+///
+/// # Example
+/// ```
+/// use mdx_gen::markdown::estimate_reading_time;
+/// assert_eq!(estimate_reading_time("A short note.", 200), 1);
+/// ```
+pub fn estimate_reading_time(
+    content: &str,
+    words_per_minute: u32,
+) -> u32 {
+    estimate_reading_time_with_options(content, words_per_minute, false)
+}
+
+/// Estimates reading time like [`estimate_reading_time`], optionally
+/// leaving the contents of fenced code blocks out of the word count.
+pub fn estimate_reading_time_with_options(
+    content: &str,
+    words_per_minute: u32,
+    exclude_code: bool,
+) -> u32 {
+    let mut fence: Option<&str> = None;
+    let mut word_count = 0;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let marker = if trimmed.starts_with("```") {
+            Some("```")
+        } else if trimmed.starts_with("~~~") {
+            Some("~~~")
+        } else {
+            None
+        };
+
+        match (fence, marker) {
+            (None, Some(marker)) => {
+                fence = Some(marker);
+                continue;
+            }
+            (Some(open), Some(marker)) if open == marker => {
+                fence = None;
+                continue;
+            }
+            (Some(_), _) if exclude_code => continue,
+            _ => {}
+        }
+
+        word_count += html_to_text(line).split_whitespace().count();
+    }
+
+    reading_time_minutes(word_count, words_per_minute)
+}
+
+/// Processes the input Markdown content and converts it into HTML.
+/// Applies custom blocks, syntax highlighting, and enhanced tables based on the provided options.
+///
+/// The content may be any string type, such as `&str`, `String` or
+/// `Cow<str>`.
+pub fn process_markdown<S: AsRef<str>>(
+    content: S,
+    options: &MarkdownOptions,
+) -> Result<String, MarkdownError> {
+    process_markdown_with_metadata(content.as_ref(), options)
+        .map(|document| document.html)
+}
+
+/// Processes Markdown content into an HTML fragment for embedding in an
+/// existing page.
+///
+/// Leading and trailing whitespace is trimmed from the result. With
+/// `MarkdownOptions::strip_fragment_paragraph` set, the `<p>` tags are
+/// also removed when the result is exactly one top-level paragraph, so
+/// `"Hello *world*"` renders as `Hello <em>world</em>`. Content with
+/// several blocks keeps its tags, since stripping only the outer ones
+/// would produce mismatched HTML.
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_markdown`].
+pub fn process_markdown_fragment<S: AsRef<str>>(
+    content: S,
+    options: &MarkdownOptions,
+) -> Result<String, MarkdownError> {
+    let html = process_markdown(content, options)?;
+    let fragment = html.trim();
+    if options.strip_fragment_paragraph {
+        if let Some(inner) = fragment
+            .strip_prefix("<p>")
+            .and_then(|rest| rest.strip_suffix("</p>"))
+            .filter(|inner| !inner.contains("</p>"))
+        {
+            return Ok(inner.to_string());
+        }
+    }
+    Ok(fragment.to_string())
+}
+
+/// Renders a Markdown table fragment as an enhanced HTML table.
+///
+/// The Comrak table extension is enabled whatever `options` say, and
+/// the result goes through the same table pass as
+/// [`process_markdown`], using the table settings of `options`. The
+/// input limit, URL rewriters and HTML sanitizing of `options` apply as
+/// they do in [`process_markdown`]; no other pass runs, so this suits
+/// fragments such as spreadsheet exports that have no surrounding
+/// document.
+///
+/// Returns an empty string, and logs a warning, if `options` are
+/// invalid or the input exceeds `options.max_input_bytes`.
+///
+/// # Example
+///
+/// ```
+/// use mdx_gen::{render_table, MarkdownOptions};
+///
+/// let html = render_table("| A | B |\n|---|--:|\n| 1 | 2 |", &MarkdownOptions::default());
+/// assert!(html.starts_with(r#"<div class="table-responsive"><table class="table">"#));
+/// ```
+pub fn render_table(
+    markdown_table: &str,
+    options: &MarkdownOptions,
+) -> String {
+    let mut options = options.clone();
+    options.comrak_options.extension.table = true;
+    if check_input(markdown_table, &options).is_err() {
+        return String::new();
+    }
+    let comrak_opts = comrak_options_for(&options);
+    let html = markdown_to_html(markdown_table, &comrak_opts);
+    let html =
+        process_tables_with_options(&html, &options.table_options());
+
+    #[cfg(feature = "sanitize")]
+    if options.enable_sanitize_html {
+        return crate::extensions::sanitize_html(&html);
+    }
+
+    html
+}
+
+/// Processes Markdown content given as raw bytes, such as a file buffer.
+///
+/// # Errors
+///
+/// Returns `MarkdownError::ParseError` if the bytes are not valid UTF-8,
+/// in addition to the errors returned by [`process_markdown`].
+pub fn process_markdown_bytes(
+    content: &[u8],
+    options: &MarkdownOptions,
+) -> Result<String, MarkdownError> {
+    let content = std::str::from_utf8(content).map_err(|e| {
+        MarkdownError::ParseError(format!("Invalid UTF-8 input: {}", e))
+    })?;
+    process_markdown(content, options)
+}
+
+/// Processes the input Markdown content like [`process_markdown`], and
+/// also returns the word count, estimated reading time and heading
+/// outline of the result.
+///
+/// The reading time uses `MarkdownOptions::words_per_minute`.
+pub fn process_markdown_with_metadata(
+    content: &str,
+    options: &MarkdownOptions,
+) -> Result<RenderedDocument, MarkdownError> {
+    let html = render_html(content, options, None)?;
+    Ok(RenderedDocument::from_html(html, options.words_per_minute))
+}
+
+/// Processes the input Markdown content like [`process_markdown`] and
+/// writes the resulting HTML to `out`.
+///
+/// # Errors
+///
+/// Returns `MarkdownError::IoError` if writing to `out` fails, in
+/// addition to the errors returned by [`process_markdown`].
+pub fn process_markdown_to_writer<W: Write>(
+    content: &str,
+    options: &MarkdownOptions,
+    out: &mut W,
+) -> Result<(), MarkdownError> {
+    let html = render_html(content, options, None)?;
+    out.write_all(html.as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Validates `options` and rejects input larger than
+/// `options.max_input_bytes`, before any work is done.
+pub(crate) fn check_input(
+    content: &str,
+    options: &MarkdownOptions,
+) -> Result<(), MarkdownError> {
+    // Validate options
+    if let Err(err) = options.validate() {
+        warn!("Invalid MarkdownOptions: {}", err);
+        return Err(err);
+    }
+
+    check_input_size(content, options)
+}
+
+/// Rejects `content` if it is larger than `options.max_input_bytes`.
+fn check_input_size(
+    content: &str,
+    options: &MarkdownOptions,
+) -> Result<(), MarkdownError> {
+    if let Some(max_bytes) = options.max_input_bytes {
+        if content.len() > max_bytes {
+            warn!("Markdown input exceeds {} bytes", max_bytes);
+            return Err(MarkdownError::ParseError(format!(
+                "Input is {} bytes, exceeding the limit of {} bytes",
+                content.len(),
+                max_bytes
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the Comrak options used to parse and render Markdown with
+/// `options`, adjusted for the features that depend on them.
+pub(crate) fn comrak_options_for<'a>(
+    options: &MarkdownOptions<'a>,
+) -> ComrakOptions<'a> {
+    // Clone Comrak options and enable unsafe rendering, or escape raw
+    // HTML when it is not allowed
+    let mut comrak_opts = options.comrak_options.clone();
+    comrak_opts.render.unsafe_ = options.allow_raw_html;
+    if !options.allow_raw_html {
+        comrak_opts.render.escape = true;
+    }
+    // Keep the full info string so line highlight specs reach the highlighter
+    if options.enable_syntax_highlighting
+        && cfg!(feature = "syntax_highlighting")
+    {
+        comrak_opts.render.full_info_string = true;
+    }
+    if options.math_renderer.is_some() {
+        comrak_opts.extension.math_dollars = true;
+    }
+    if let Some(rewriter) = &options.link_url_rewriter {
+        comrak_opts.extension.link_url_rewriter =
+            Some(rewriter.clone());
+    }
+    if let Some(rewriter) = &options.image_url_rewriter {
+        comrak_opts.extension.image_url_rewriter =
+            Some(rewriter.clone());
+    }
+    comrak_opts
+}
+
+/// Applies `options.pre_processor`, if set, to the raw Markdown, then
+/// expands include directives if `options.include_base_dir` is set.
+///
+/// The expanded content is checked against `options.max_input_bytes`
+/// again, so includes cannot be used to exceed the limit.
+pub(crate) fn pre_process<'c>(
+    content: &'c str,
+    options: &MarkdownOptions,
+) -> Result<Cow<'c, str>, MarkdownError> {
+    let content = match &options.pre_processor {
+        Some(pre_processor) => {
+            debug!("Running pre-processor");
+            Cow::Owned(pre_processor(content.to_string()))
+        }
+        None => Cow::Borrowed(content),
+    };
+    match &options.include_base_dir {
+        Some(base_dir) => {
+            debug!("Expanding includes");
+            let expanded = expand_includes(
+                &content,
+                base_dir,
+                options.max_include_depth,
+            )?;
+            check_input_size(&expanded, options)?;
+            Ok(Cow::Owned(expanded))
+        }
+        None => Ok(content),
+    }
+}
+
+/// Rewrites the pre-processed Markdown `content` into the form Comrak
+/// parses, returning it with the table captions taken out of it.
+///
+/// Fenced custom blocks are rewritten as the `<div>` form, custom blocks
+/// with several paragraphs are split so their content is parsed as
+/// Markdown, and `[Table: ...]` captions are replaced with placeholders
+/// Comrak passes through. Captions and custom blocks are carried through
+/// Comrak as raw HTML, so these passes only run when it is allowed.
+pub(crate) fn prepare_for_comrak<'c>(
+    content: Cow<'c, str>,
+    options: &MarkdownOptions,
+) -> (Cow<'c, str>, Vec<String>) {
+    let custom_blocks =
+        options.enable_custom_blocks && options.allow_raw_html;
+
+    // Rewrite fenced custom blocks as the `<div>` form
+    let content =
+        if custom_blocks && options.enable_fenced_custom_blocks {
+            Cow::Owned(expand_fenced_custom_blocks(&content))
+        } else {
+            content
+        };
+
+    // Let custom blocks with several paragraphs be parsed as Markdown
+    let content = if custom_blocks {
+        Cow::Owned(separate_multi_paragraph_blocks(&content))
+    } else {
+        content
+    };
+
+    // Replace table captions with placeholders Comrak will pass through
+    if options.enable_table_captions && options.allow_raw_html {
+        let (content, captions) = extract_table_captions(&content);
+        (Cow::Owned(content), captions)
+    } else {
+        (content, Vec::new())
+    }
+}
+
+/// The HTML at several points of the rendering pipeline, returned by
+/// [`process_markdown_stages`] to help pinpoint which pass changed it.
+///
+/// A stage whose pass is disabled holds the same HTML as the stage
+/// before it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderStages {
+    /// The HTML produced by Comrak, before any post-processing.
+    pub comrak_html: String,
+    /// The HTML after custom blocks, GitHub alerts and syntax
+    /// highlighting.
+    pub after_highlight: String,
+    /// The HTML after enhanced tables.
+    pub after_tables: String,
+    /// The final HTML, as returned by [`process_markdown`].
+    pub final_html: String,
+}
+
+/// Processes the input Markdown content like [`process_markdown`],
+/// returning the intermediate HTML of each stage as well as the final
+/// HTML.
+///
+/// # Errors
+///
+/// Returns the same errors as [`process_markdown`].
+pub fn process_markdown_stages(
+    content: &str,
+    options: &MarkdownOptions,
+) -> Result<RenderStages, MarkdownError> {
+    let mut stages = RenderStages::default();
+    stages.final_html =
+        render_html(content, options, Some(&mut stages))?;
+    Ok(stages)
+}
+
+/// Processes several Markdown documents, returning one result per input
+/// in the same order.
+///
+/// With the `parallel` feature enabled the documents are rendered
+/// concurrently using rayon; otherwise they are rendered one after the
+/// other.
+#[cfg(feature = "parallel")]
+pub fn process_markdown_batch(
+    inputs: &[&str],
+    options: &MarkdownOptions,
+) -> Vec<Result<String, MarkdownError>> {
+    use rayon::prelude::*;
+
+    inputs
+        .par_iter()
+        .map(|content| process_markdown(content, options))
+        .collect()
+}
+
+/// Processes several Markdown documents, returning one result per input
+/// in the same order.
+///
+/// With the `parallel` feature enabled the documents are rendered
+/// concurrently using rayon; otherwise they are rendered one after the
+/// other.
+#[cfg(not(feature = "parallel"))]
+pub fn process_markdown_batch(
+    inputs: &[&str],
+    options: &MarkdownOptions,
+) -> Vec<Result<String, MarkdownError>> {
+    inputs
+        .iter()
+        .map(|content| process_markdown(content, options))
+        .collect()
+}
+
+// Substrings that every match of a rendering pass starts with. A pass
+// is skipped when its marker is absent, so plain prose does not pay for
+// the regex scans.
+#[cfg(feature = "syntax_highlighting")]
+const CODE_BLOCK_MARKER: &str = "<pre><code";
+const CUSTOM_BLOCK_MARKER: &str = "<div";
+const GITHUB_ALERT_MARKER: &str = "<blockquote>";
+
+/// Returns whether `html` may contain a table or table cell. Table tags
+/// are matched case-insensitively, so this checks for any tag starting
+/// with `t`.
+fn may_contain_table(html: &str) -> bool {
+    html.contains("<t") || html.contains("<T")
+}
+
+fn render_html(
+    content: &str,
+    options: &MarkdownOptions,
+    mut stages: Option<&mut RenderStages>,
+) -> Result<String, MarkdownError> {
+    info!("Starting markdown processing");
+    debug!("Markdown options: {:?}", options);
+
+    check_input(content, options)?;
+
+    let comrak_opts = comrak_options_for(options);
+    let custom_blocks =
+        options.enable_custom_blocks && options.allow_raw_html;
+
+    #[cfg(feature = "syntax_highlighting")]
+    let source = content;
+    let content = pre_process(content, options)?;
+    // Lines of the source only locate code blocks if the pre-processor
+    // and includes left it unchanged
+    #[cfg(feature = "syntax_highlighting")]
+    let source = (*content == *source).then(|| source);
+    let (content, captions) = prepare_for_comrak(content, options);
+
+    // Convert Markdown to initial HTML
+    debug!("Converting markdown to HTML using Comrak");
+    let mut html = markdown_to_html(&content, &comrak_opts);
+    debug!("Initial HTML conversion result: {}", html);
+    if let Some(stages) = stages.as_deref_mut() {
+        stages.comrak_html = html.clone();
+    }
+
+    if !captions.is_empty() {
+        debug!("Inserting table captions");
+        html = insert_table_captions(&html, &captions);
+    }
+
+    let block_options = options.custom_block_options();
+
+    // Process custom blocks (e.g., note, warning, tip) if enabled. This
+    // runs before highlighting and table processing so the code block and
+    // responsive table wrappers are not mistaken for custom blocks.
+    if custom_blocks && html.contains(CUSTOM_BLOCK_MARKER) {
+        debug!("Processing custom blocks");
+        html = process_custom_blocks_with_options(
+            &html,
+            &options.custom_block_registry,
+            &block_options,
+        );
+    }
+
+    // Convert GitHub-style alert blockquotes if enabled
+    if options.enable_github_alerts
+        && html.contains(GITHUB_ALERT_MARKER)
+    {
+        debug!("Processing GitHub alerts");
+        html = process_github_alerts(
+            &html,
+            &options.custom_block_registry,
+            &block_options,
+        );
+    }
+
+    // Apply syntax highlighting if enabled. Without the
+    // `syntax_highlighting` feature code blocks are left as Comrak
+    // rendered them.
+    #[cfg(feature = "syntax_highlighting")]
+    if options.enable_syntax_highlighting {
+        debug!("Applying syntax highlighting");
+        match highlight_code_blocks(&html, source, options) {
+            Ok(Cow::Owned(highlighted)) => html = highlighted,
+            Ok(Cow::Borrowed(_)) => {}
+            Err(e) => {
+                warn!("Error during syntax highlighting: {:?}", e);
+                return Err(e);
+            }
+        }
+    }
+    if let Some(stages) = stages.as_deref_mut() {
+        stages.after_highlight = html.clone();
+    }
+
+    // Add the inline code class if set
+    if !options.class_names.code_inline.is_empty() {
+        debug!("Adding inline code classes");
+        html = process_inline_code(
+            &html,
+            &options.class_names.code_inline,
+        );
+    }
+
+    // Process enhanced tables if enabled
+    if options.enable_enhanced_tables && may_contain_table(&html) {
+        debug!("Processing enhanced tables");
+        html = process_tables_with_options(
+            &html,
+            &options.table_options(),
+        );
+    }
+    if let Some(stages) = stages {
+        stages.after_tables = html.clone();
+    }
+
+    // Set the ordered list style if set
+    if let Some(style) = options.ordered_list_style {
+        debug!("Processing ordered lists");
+        html = process_ordered_lists(&html, style);
+    }
+
+    // Add definition list classes if set
+    if let Some(classes) = &options.definition_list_classes {
+        debug!("Processing definition lists");
+        html = process_definition_lists(&html, classes);
+    }
+
+    // Wrap math for client-side rendering if enabled. This runs after
+    // custom blocks, which would otherwise claim the display math `<div>`.
+    if let Some(renderer) = options.math_renderer {
+        debug!("Processing math");
+        html = process_math(&html, renderer);
+    }
+
+    // Replace emoji shortcodes if enabled
+    if options.enable_emoji_shortcodes {
+        debug!("Replacing emoji shortcodes");
+        html = process_emoji_shortcodes(&html);
+    }
+
+    // Replace keyboard shortcuts if enabled
+    if options.enable_kbd_shortcuts {
+        debug!("Replacing keyboard shortcuts");
+        html = process_kbd_shortcuts(&html);
+    }
+
+    // Wrap abbreviations if any are set
+    if !options.abbreviations.is_empty() {
+        debug!("Wrapping abbreviations");
+        html = process_abbreviations(
+            &html,
+            &options.abbreviations,
+            options.abbreviate_every_occurrence,
+        );
+    }
+
+    // Mark external links if enabled
+    if options.enable_external_link_attributes {
+        debug!("Adding external link attributes");
+        html =
+            process_external_links(&html, options.base_url.as_deref());
+    }
+
+    // Add image dimensions from the image URL if enabled
+    if options.enable_image_dimensions {
+        debug!("Adding image dimensions");
+        html = process_image_dimensions(
+            &html,
+            options.strip_image_dimension_params,
+        );
+    }
+
+    // Lazy-load images if enabled
+    if options.enable_lazy_images {
+        debug!("Adding lazy loading attributes to images");
+        html = process_lazy_images(&html);
+    }
+
+    // Label the footnotes section if a heading is set
+    if let Some(heading) = &options.footnote_heading {
+        debug!("Adding footnote heading");
+        html = process_footnote_heading(&html, heading);
+    }
+
+    // Add heading ids and permalinks if enabled
+    if options.enable_heading_anchors {
+        debug!("Adding heading anchors");
+        html = process_heading_anchors(
+            &html,
+            options.enable_heading_permalinks,
+        );
+        if let Some(px) = options.heading_scroll_margin {
+            html = process_heading_scroll_margin(&html, px);
+        }
+    }
+
+    // Sanitize the output if enabled
+    #[cfg(feature = "sanitize")]
+    if options.enable_sanitize_html {
+        debug!("Sanitizing HTML");
+        html = crate::extensions::sanitize_html(&html);
+    }
+
+    // Remove HTML comments if enabled
+    if options.enable_strip_comments {
+        debug!("Stripping HTML comments");
+        html = strip_html_comments(&html);
+    }
+
+    if options.enable_minify_output {
+        html = minify_html(&html);
+    }
+
+    // Hand the final HTML to the user-supplied post-processor, if any
+    if let Some(post_processor) = &options.post_processor {
+        debug!("Running post-processor");
+        html = post_processor(html);
+    }
+
+    info!("Markdown processing completed successfully");
+    Ok(html)
+}
+
+/// Code block languages rendered verbatim, without syntax highlighting.
+#[cfg(feature = "syntax_highlighting")]
+const UNHIGHLIGHTED_LANGUAGES: &[&str] =
+    &["text", "plain", "nohighlight", "no-highlight"];
+
+#[cfg(feature = "syntax_highlighting")]
+lazy_static! {
+    // Comrak does not guarantee attribute order, so `data-meta` may
+    // appear either before or after the language class. Blocks without
+    // a language have no class.
+    static ref CODE_BLOCK_RE: Regex = Regex::new(
+        r#"(?s)<pre><code(?: data-meta="([^"]*)")?(?: class="language-([^"]*)")?(?: data-meta="([^"]*)")?>(.*?)</code></pre>"#
+    )
+    .unwrap();
+}
+
+#[cfg(feature = "syntax_highlighting")]
+/// Highlights every fenced code block in `html`, rendered from the
+/// Markdown `source` if it is known.
+///
+/// Returns the input unchanged, without copying it, when it contains no
+/// code blocks.
+fn highlight_code_blocks<'h>(
+    html: &'h str,
+    source: Option<&str>,
+    options: &MarkdownOptions,
+) -> Result<Cow<'h, str>, MarkdownError> {
+    debug!("Highlighting code blocks");
+
+    // Each entry pairs an optional `data-theme` label with a theme; a
+    // theme pair renders every block once per entry.
+    let themes: Vec<(Option<&str>, &Theme)> = match &options.theme_pair
+    {
+        Some((light, dark)) => vec![
+            (Some("light"), resolve_theme(light)?),
+            (Some("dark"), resolve_theme(dark)?),
+        ],
+        None => {
+            let theme = match &options.custom_theme {
+                Some(theme) => theme,
+                None => resolve_theme(
+                    options
+                        .syntax_theme
+                        .as_deref()
+                        .unwrap_or(DEFAULT_THEME),
+                )?,
+            };
+            vec![(None, theme)]
+        }
+    };
+    let language_themes: HashMap<&str, Vec<(Option<&str>, &Theme)>> =
+        options
+            .language_themes
+            .iter()
+            .map(|(lang, name)| {
+                let theme = resolve_theme(name).map_err(|_| {
+                    MarkdownError::SyntaxHighlightError(format!(
+                        "Unknown theme '{}' for language '{}'",
+                        name, lang
+                    ))
+                })?;
+                Ok((lang.as_str(), vec![(None, theme)]))
+            })
+            .collect::<Result<HashMap<_, _>, MarkdownError>>()?;
+    let syntax_set = options
+        .syntax_set
+        .as_ref()
+        .unwrap_or_else(|| default_syntax_set());
+
+    if !html.contains(CODE_BLOCK_MARKER)
+        || !CODE_BLOCK_RE.is_match(html)
+    {
+        return Ok(Cow::Borrowed(html));
+    }
+
+    let mut highlighted_html = String::with_capacity(html.len());
+    let mut last_end = 0;
+    // Identical blocks render identically within a call, so each
+    // distinct block is only highlighted once.
+    let mut cache: HashMap<&str, String> = HashMap::new();
+
+    for cap in CODE_BLOCK_RE.captures_iter(html) {
+        let block = cap.get(0).unwrap();
+        highlighted_html.push_str(&html[last_end..block.start()]);
+        last_end = block.end();
+
+        if let Some(cached) = cache.get(block.as_str()) {
+            highlighted_html.push_str(cached);
+            continue;
+        }
+
+        let (lang, meta, code) = extract_code_block(&cap)?;
+        let code = match options.tab_width {
+            Some(width) if width > 0 => expand_tabs(&code, width),
+            _ => code,
+        };
+        let guessed_lang;
+        let mut fallback_reason = None;
+        let (lang, syntax) = if lang.is_empty() {
+            // Blocks without a language are left as Comrak rendered
+            // them, unless their language can be guessed.
+            let guess = (options.highlight_fallback
+                == HighlightFallback::Guess)
+                .then(|| syntax_set.find_syntax_by_first_line(&code))
+                .flatten();
+            match guess {
+                Some(syntax) => {
+                    debug!(
+                        "Guessed code block language: {}",
+                        syntax.name
+                    );
+                    guessed_lang = syntax
+                        .file_extensions
+                        .first()
+                        .cloned()
+                        .unwrap_or_else(|| syntax.name.to_lowercase());
+                    (guessed_lang.as_str(), Some(syntax))
+                }
+                None => {
+                    highlighted_html.push_str(block.as_str());
+                    continue;
+                }
+            }
+        } else {
+            if options.passthrough_languages.iter().any(|passthrough| {
+                passthrough.eq_ignore_ascii_case(lang)
+            }) {
+                highlighted_html.push_str(&format!(
+                    r#"<pre class="{}">{}</pre>"#,
+                    html_escape::encode_double_quoted_attribute(lang),
+                    html_escape::encode_text(&code)
+                ));
+                continue;
+            }
+            if UNHIGHLIGHTED_LANGUAGES
+                .iter()
+                .any(|plain| plain.eq_ignore_ascii_case(lang))
+            {
+                highlighted_html.push_str(&format_highlighted_code(
+                    lang,
+                    None,
+                    None,
+                    &html_escape::encode_text(&code),
+                    options,
+                ));
+                continue;
+            }
+            let found = find_syntax(
+                syntax_set,
+                lang,
+                &options.language_aliases,
+            );
+            // Unknown languages are highlighted as plain text unless the
+            // fallback asks for something else.
+            let syntax = found.or_else(|| {
+                matches!(
+                    options.highlight_fallback,
+                    HighlightFallback::PlainText
+                        | HighlightFallback::Guess
+                )
+                .then(|| syntax_set.find_syntax_plain_text())
+            });
+            if found.is_none() && syntax.is_some() {
+                fallback_reason =
+                    Some(unknown_language_error(lang).to_string());
+            }
+            (lang, syntax)
+        };
+        let block_themes = language_themes
+            .get(lang.to_lowercase().as_str())
+            .unwrap_or(&themes);
+        let result = match syntax {
+            Some(syntax) => block_themes
+                .iter()
+                .map(|(label, theme)| {
+                    let html = highlight_block(
+                        lang, meta, &code, syntax, syntax_set, theme,
+                        options,
+                    )?;
+                    Ok(match label {
+                        Some(label) => html.replacen(
+                            "<pre ",
+                            &format!("<pre data-theme=\"{}\" ", label),
+                            1,
+                        ),
+                        None => html,
+                    })
+                })
+                .collect::<Result<String, MarkdownError>>(),
+            None => Err(unknown_language_error(lang)),
+        };
+
+        let rendered = match result {
+            Ok(highlighted_code) => format_highlighted_code(
+                lang,
+                syntax
+                    .map(|syntax| syntax.name.as_str())
+                    .filter(|name| *name != "Plain Text"),
+                fallback_reason.as_deref(),
+                &highlighted_code,
+                options,
+            ),
+            Err(e) => match options.highlight_fallback {
+                HighlightFallback::Error => {
+                    return Err(error_at(e, source, html, &cap))
+                }
+                HighlightFallback::PlainText
+                | HighlightFallback::Guess => {
+                    warn!("Falling back to plain text: {}", e);
+                    format_highlighted_code(
+                        lang,
+                        None,
+                        Some(&e.to_string()),
+                        &html_escape::encode_text(&code),
+                        options,
+                    )
+                }
+                HighlightFallback::SkipHighlighting => {
+                    debug!("Skipping highlighting: {}", e);
+                    block.as_str().to_string()
+                }
+            },
+        };
+        highlighted_html.push_str(&rendered);
+        cache.insert(block.as_str(), rendered);
+    }
+
+    highlighted_html.push_str(&html[last_end..]);
+    Ok(Cow::Owned(highlighted_html))
+}
+
+#[cfg(feature = "syntax_highlighting")]
+fn highlight_block(
+    lang: &str,
+    meta: &str,
+    code: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    options: &MarkdownOptions,
+) -> Result<String, MarkdownError> {
+    let highlighted_lines = parse_highlight_spec(meta);
+    if options.enable_line_numbers || !highlighted_lines.is_empty() {
+        highlight_code_by_line(
+            lang,
+            code,
+            syntax,
+            syntax_set,
+            theme,
+            options.enable_line_numbers,
+            &highlighted_lines,
+        )
+    } else {
+        highlight_code(lang, code, syntax, syntax_set, theme)
+    }
+}
+
+#[cfg(feature = "syntax_highlighting")]
+fn extract_code_block<'a>(
+    cap: &'a regex::Captures<'a>,
+) -> Result<(&'a str, &'a str, String), MarkdownError> {
+    let lang = cap.get(2).map_or("", |m| m.as_str());
+    let meta =
+        cap.get(1).or_else(|| cap.get(3)).map_or("", |m| m.as_str());
+    let code = html_escape::decode_html_entities(&cap[4]);
+    Ok((lang, meta, code.to_string()))
+}
+
+#[cfg(feature = "syntax_highlighting")]
+fn highlight_code(
+    lang: &str,
+    code: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+) -> Result<String, MarkdownError> {
+    debug!(
+        "Attempting to highlight code block with language: {}",
+        lang
+    );
+    highlight_with_theme(code, syntax, syntax_set, theme)
+        .map_err(|e| highlight_error(lang, e))
+}
+
+#[cfg(feature = "syntax_highlighting")]
+fn highlight_code_by_line(
+    lang: &str,
+    code: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    line_numbers: bool,
+    highlighted_lines: &[RangeInclusive<usize>],
+) -> Result<String, MarkdownError> {
+    debug!(
+        "Attempting to highlight code block line by line with language: {}",
+        lang
+    );
+    let lines =
+        highlight_lines_with_theme(code, syntax, syntax_set, theme)
+            .map_err(|e| highlight_error(lang, e))?;
+
+    let (mut output, _) = start_highlighted_html_snippet(theme);
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        let mut line_html = line.clone();
+        if line_numbers {
+            line_html = format!(
+                "<span class=\"line-number\">{}</span>{}",
+                line_number, line_html
+            );
+        }
+        if highlighted_lines
+            .iter()
+            .any(|range| range.contains(&line_number))
+        {
+            line_html = format!(
+                "<span class=\"highlighted-line\">{}</span>",
+                line_html
+            );
+        }
+        output.push_str(&line_html);
+    }
+    output.push_str("</pre>");
+    Ok(output)
+}
+
+#[cfg(feature = "syntax_highlighting")]
+/// Returns the 1-based line of `text` that contains the byte `offset`.
+fn line_of_offset(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+#[cfg(feature = "syntax_highlighting")]
+/// Adds the position of the failing code block `block` of `html` to a
+/// conversion error.
+///
+/// The block is located in the Markdown `source` as the fenced code
+/// block with the same language and the same number of such blocks
+/// before it, and reported by its line there. If `source` is unknown,
+/// e.g. because includes or the pre-processor changed the Markdown, or
+/// its fenced code blocks do not match those of `html`, the block is
+/// reported by its byte offset in the HTML instead.
+fn error_at(
+    error: MarkdownError,
+    source: Option<&str>,
+    html: &str,
+    block: &regex::Captures<'_>,
+) -> MarkdownError {
+    let message = match error {
+        MarkdownError::ConversionError(message) => message,
+        error => return error,
+    };
+    let offset = block.get(0).unwrap().start();
+    let language = |cap: &regex::Captures<'_>| {
+        html_escape::decode_html_entities(
+            cap.get(2).map_or("", |m| m.as_str()),
+        )
+        .into_owned()
+    };
+    let lang = language(block);
+    let blocks = CODE_BLOCK_RE
+        .captures_iter(html)
+        .filter(|cap| language(cap) == lang)
+        .count();
+    let preceding = CODE_BLOCK_RE
+        .captures_iter(&html[..offset])
+        .filter(|cap| language(cap) == lang)
+        .count();
+    let line = source.and_then(|source| {
+        let fences: Vec<usize> = fenced_code_block_starts(source)
+            .into_iter()
+            .filter(|(_, info)| {
+                info.split_whitespace().next().unwrap_or("") == lang
+            })
+            .map(|(start, _)| line_of_offset(source, start))
+            .collect();
+        if fences.len() == blocks {
+            fences.get(preceding).copied()
+        } else {
+            None
+        }
+    });
+    let position = match line {
+        Some(line) => format!("line {} of the Markdown source", line),
+        None => format!("byte {} of the rendered HTML", offset),
+    };
+    MarkdownError::ConversionError(format!(
+        "{} (at {})",
+        message, position
+    ))
+}
+
+#[cfg(feature = "syntax_highlighting")]
+fn unknown_language_error(lang: &str) -> MarkdownError {
+    MarkdownError::ConversionError(format!(
+        "Unknown code block language '{}'",
+        lang
+    ))
+}
+
+#[cfg(feature = "syntax_highlighting")]
+fn highlight_error(lang: &str, error: MarkdownError) -> MarkdownError {
+    MarkdownError::ConversionError(format!(
+        "Failed to highlight code block in language '{}': {}",
+        lang, error
+    ))
+}
+
+#[cfg(feature = "syntax_highlighting")]
+/// Parses a line highlight specification such as `{2,4-5}` from a code
+/// block info string.
+///
+/// Returns the 1-based line ranges to highlight. Malformed entries are
+/// skipped.
+fn parse_highlight_spec(info: &str) -> Vec<RangeInclusive<usize>> {
+    let spec = match (info.find('{'), info.find('}')) {
+        (Some(start), Some(end)) if start < end => {
+            &info[start + 1..end]
+        }
+        _ => return Vec::new(),
+    };
+
+    spec.split(',')
+        .filter_map(|part| {
+            let (from, to) =
+                part.split_once('-').unwrap_or((part, part));
+            let from = from.trim().parse::<usize>().ok()?;
+            let to = to.trim().parse::<usize>().ok()?;
+            if from > 0 && from <= to {
+                Some(from..=to)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "syntax_highlighting")]
+fn format_highlighted_code(
+    lang: &str,
+    language_name: Option<&str>,
+    fallback_reason: Option<&str>,
+    highlighted_code: &str,
+    options: &MarkdownOptions,
+) -> String {
+    let mut pre = String::from("<pre");
+    let pre_class = &options.class_names.code_pre;
+    if !pre_class.is_empty() {
+        pre.push_str(&format!(
+            r#" class="{}""#,
+            html_escape::encode_double_quoted_attribute(pre_class)
+        ));
+    }
+    if let Some(name) =
+        language_name.filter(|_| options.enable_code_aria_labels)
+    {
+        pre.push_str(&format!(
+            r#" aria-label="{} code""#,
+            html_escape::encode_double_quoted_attribute(name)
+        ));
+    }
+    if options.disable_spellcheck_in_code {
+        pre.push_str(r#" spellcheck="false""#);
+    }
+    if let Some(reason) = fallback_reason
+        .filter(|_| options.enable_highlight_error_attribute)
+    {
+        pre.push_str(&format!(
+            r#" data-highlight-error="{}""#,
+            html_escape::encode_double_quoted_attribute(reason)
+        ));
+    }
+    pre.push('>');
+    let block = format!(
+        "{}<code class=\"language-{}\">{}</code></pre>",
+        pre, lang, highlighted_code
+    );
+    if options.enable_code_copy_button {
+        format!(
+            r#"<div class="code-block"><button class="copy-btn" aria-label="Copy">Copy</button>{}</div>"#,
+            block
+        )
+    } else {
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "syntax_highlighting")]
+    use crate::extensions::apply_syntax_highlighting;
+    use comrak::ListStyleType;
+
+    #[test]
+    fn test_process_markdown_with_all_features() {
+        let markdown = r#"
+# Test Markdown
+
+Here's a table:
+
+| Left | Center | Right |
+|:-----|:------:|------:|
+| 1    |   2    |     3 |
+
+```rust
+fn main() {
+    println!("Hello, world!");
+}
+```
+
+<div class="note">This is a note.</div>
+
+<div class="warning">This is a warning.</div>
+
+<div class="tip">This is a tip.</div>
+"#;
+
+        let options = default_markdown_options();
+
+        let result = process_markdown(markdown, &options);
+        assert!(result.is_ok(), "Markdown processing failed");
+
+        let html = result.unwrap();
+
+        assert!(
+            html.contains(
+                r#"<div class="table-responsive"><table class="table">"#
+            ),
+            "Table not processed correctly"
+        );
+        assert!(
+            html.contains(r#"<pre><code class="language-rust">"#),
+            "Syntax highlighting not applied"
+        );
+        assert!(html.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong>"#), "Note block not processed");
+        assert!(html.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong>"#), "Warning block not processed");
+        assert!(html.contains(r#"<div class="alert alert-success" role="note"><strong>Tip:</strong>"#), "Tip block not processed");
+    }
+
+    #[test]
+    fn test_process_markdown_without_custom_blocks() {
+        let markdown = "# Test Markdown\n<div class=\"note\">This is a note.</div>";
+        let options = MarkdownOptions::new()
+            .with_custom_blocks(false)
+            .with_comrak_options({
+                let mut opts = ComrakOptions::default();
+                opts.extension.table = true; // Enable table extension if you have tables
+                opts
+            });
+
+        let result = process_markdown(markdown, &options);
+        assert!(
+            result.is_ok(),
+            "Markdown processing failed: {:?}",
+            result
+        );
+        let html = result.unwrap();
+        assert!(
+            html.contains("<div class=\"note\">This is a note.</div>"),
+            "Custom block should not be processed when disabled"
+        );
+    }
+
+    #[test]
+    fn test_process_markdown_without_enhanced_tables() {
+        let markdown = r#"
+# Test Markdown
+
+| Left | Center | Right |
+|:-----|:------:|------:|
+| 1    |   2    |     3 |
+"#;
+
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_comrak_options({
+                let mut opts = ComrakOptions::default();
+                opts.extension.table = true;
+                opts
+            });
+
+        let result = process_markdown(markdown, &options);
+        assert!(result.is_ok(), "Markdown processing failed");
+
+        let html = result.unwrap();
+        assert!(
+            !html.contains(
+                r#"<div class="table-responsive"><table class="table">"#
+            ),
+            "Enhanced table processing applied when disabled"
+        );
+        assert!(
+            html.contains("<table>"),
+            "Basic table should still be present"
+        );
+    }
+
+    fn assert_invalid(options: MarkdownOptions, message: &str) {
+        match options.validate() {
+            Err(MarkdownError::InvalidOptionsError(msg)) => {
+                assert_eq!(msg, message)
+            }
+            other => {
+                panic!("Expected InvalidOptionsError, got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_table_captions() {
+        let options =
+            default_markdown_options().with_table_captions(true);
+        assert!(options.validate().is_ok());
+
+        assert_invalid(
+            MarkdownOptions::new()
+                .with_enhanced_tables(false)
+                .with_table_captions(true),
+            "Table captions are enabled, but Comrak table extension is disabled.",
+        );
+        assert_invalid(
+            default_markdown_options()
+                .with_table_captions(true)
+                .with_allow_raw_html(false),
+            "Table captions are enabled, but raw HTML is not allowed.",
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = default_markdown_options()
+            .with_syntax_highlighting(true)
+            .with_line_numbers(true)
+            .with_custom_theme("InspiredGitHub".to_string())
+            .with_language_alias("ts", "TypeScript");
+        let overrides = MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_heading_anchors(true)
+            .with_language_alias("js", "JavaScript");
+
+        let merged = base.merge(overrides);
+        assert!(merged.enable_syntax_highlighting);
+        assert!(merged.enable_line_numbers);
+        assert!(!merged.enable_enhanced_tables);
+        assert!(merged.enable_heading_anchors);
+        assert!(merged.enable_custom_blocks);
+        assert!(merged.comrak_options.extension.table);
+        assert_eq!(
+            merged.syntax_theme.as_deref(),
+            Some("InspiredGitHub")
+        );
+        assert_eq!(merged.language_aliases.len(), 2);
+        assert!(merged.validate().is_ok());
+
+        let merged = merged.merge(
+            MarkdownOptions::new()
+                .with_custom_theme("Solarized (dark)".to_string())
+                .with_strikethrough(true),
+        );
+        assert_eq!(
+            merged.syntax_theme.as_deref(),
+            Some("Solarized (dark)")
+        );
+        // Comrak options are merged one by one
+        assert!(merged.comrak_options.extension.strikethrough);
+        assert!(merged.comrak_options.extension.table);
+        assert!(merged.validate().is_ok());
+    }
+
+    #[test]
+    fn test_describe_diff() {
+        let options = default_markdown_options();
+        let other = options.clone().with_enhanced_tables(false);
+
+        assert_eq!(
+            options.describe_diff(&other),
+            vec!["enable_enhanced_tables: true -> false".to_string()]
+        );
+        assert!(options.describe_diff(&options.clone()).is_empty());
+
+        let other = options.clone().with_strikethrough(true);
+        assert_eq!(
+            options.describe_diff(&other),
+            vec![
+                "comrak_options.extension.strikethrough: false -> true"
+                    .to_string()
+            ]
+        );
+
+        let mut other = options.clone();
+        other.comrak_options.render.sourcepos = true;
+        other.comrak_options.render.list_style = ListStyleType::Star;
+        assert_eq!(
+            options.describe_diff(&other),
+            vec![
+                "comrak_options.render.sourcepos: false -> true"
+                    .to_string(),
+                "comrak_options.render.list_style: '-' -> '*'"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_fenced_custom_blocks() {
+        let options =
+            default_markdown_options().with_fenced_custom_blocks(true);
+        assert!(options.validate().is_ok());
+
+        assert_invalid(
+            default_markdown_options()
+                .with_fenced_custom_blocks(true)
+                .with_custom_blocks(false),
+            "Fenced custom blocks are enabled, but custom blocks are disabled.",
+        );
+        assert_invalid(
+            default_markdown_options()
+                .with_fenced_custom_blocks(true)
+                .with_allow_raw_html(false),
+            "Fenced custom blocks are enabled, but raw HTML is not allowed.",
+        );
+    }
+
+    #[test]
+    fn test_validate_github_alerts_without_raw_html() {
+        let options = default_markdown_options()
+            .with_github_alerts(true)
+            .with_allow_raw_html(false);
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_heading_permalinks() {
+        assert_invalid(
+            default_markdown_options().with_heading_permalinks(true),
+            "Heading permalinks are enabled, but heading anchors are disabled.",
+        );
+        assert!(default_markdown_options()
+            .with_heading_anchors(true)
+            .with_heading_permalinks(true)
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validate_code_options_require_highlighting() {
+        assert_invalid(
+            default_markdown_options()
+                .with_syntax_highlighting(false)
+                .with_line_numbers(true),
+            "Line numbers are enabled, but syntax highlighting is disabled.",
+        );
+        assert_invalid(
+            default_markdown_options()
+                .with_syntax_highlighting(false)
+                .with_code_copy_button(true),
+            "The code copy button is enabled, but syntax highlighting is disabled.",
+        );
+        assert_invalid(
+            default_markdown_options()
+                .with_syntax_highlighting(false)
+                .with_disable_spellcheck_in_code(true),
+            "Disabling spellcheck in code is enabled, but syntax highlighting is disabled.",
+        );
+        assert_invalid(
+            default_markdown_options()
+                .with_syntax_highlighting(false)
+                .with_tab_width(4),
+            "A tab width is set, but syntax highlighting is disabled.",
+        );
+        assert_invalid(
+            default_markdown_options().with_tab_width(0),
+            "The tab width must be greater than zero.",
+        );
+    }
+
+    #[test]
+    fn test_markdown_options_validation() {
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(true)
+            .with_comrak_options({
+                let mut opts = ComrakOptions::default();
+                opts.extension.table = false;
+                opts
+            });
+
+        assert!(
+            matches!(
+                options.validate(),
+                Err(MarkdownError::InvalidOptionsError(_))
+            ),
+            "Validation should fail when enhanced tables are enabled but Comrak table extension is disabled"
+        );
+
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(true)
+            .with_comrak_options({
+                let mut opts = ComrakOptions::default();
+                opts.extension.table = true;
+                opts
+            });
+
+        assert!(
+            options.validate().is_ok(),
+            "Validation should pass when options are consistent"
+        );
+    }
+
+    #[test]
+    fn test_markdown_options_builder() {
+        let options = MarkdownOptions::new()
+            .with_custom_blocks(false)
+            .with_syntax_highlighting(true)
+            .with_enhanced_tables(false);
+
+        assert!(!options.enable_custom_blocks);
+        assert!(options.enable_syntax_highlighting);
+        assert!(!options.enable_enhanced_tables);
+    }
+
+    #[test]
+    fn test_process_markdown_with_invalid_options() {
+        let markdown = "# Test\n\n| Column 1 | Column 2 |\n| -------- | -------- |\n| Value 1  | Value 2  |";
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(true)
+            .with_comrak_options({
+                let mut opts = ComrakOptions::default();
+                opts.extension.table = false;
+                opts
+            });
+        let result = process_markdown(markdown, &options);
+        assert!(result.is_err());
+        assert!(matches!(
+            result,
+            Err(MarkdownError::InvalidOptionsError(_))
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_with_empty_content() {
+        let markdown = "";
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(false) // No need for enhanced tables in an empty document
+            .with_comrak_options({
+                let mut opts = ComrakOptions::default();
+                opts.extension.table = false; // Disable table extension
+                opts
+            });
+
+        let result = process_markdown(markdown, &options);
+        assert!(
+            result.is_ok(),
+            "Markdown processing failed for empty content: {:?}",
+            result
+        );
+        assert_eq!(result.unwrap().trim(), "");
+    }
+
+    #[test]
+    fn test_process_markdown_with_only_custom_blocks() {
+        let markdown = "<div class=\"note\">This is a note.</div>";
+        let options = MarkdownOptions::new()
+            .with_custom_blocks(true)
+            .with_enhanced_tables(false) // Disable enhanced tables since they're not used here
+            .with_comrak_options({
+                let mut opts = ComrakOptions::default();
+                opts.extension.table = false; // Ensure table extension is disabled
+                opts
+            });
+
+        let result = process_markdown(markdown, &options);
+        assert!(
+            result.is_ok(),
+            "Markdown processing failed for custom blocks: {:?}",
+            result
+        );
+        let html = result.unwrap();
+        assert!(html.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong>"#), "Custom block not processed correctly");
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_custom_theme() {
+        let markdown = "```rust\nfn main() {}\n```";
+        let dark =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        let light = process_markdown(
+            markdown,
+            &default_markdown_options()
+                .with_custom_theme("Solarized (light)".to_string()),
+        )
+        .unwrap();
+
+        assert!(dark.contains("style=\"color:"));
+        assert!(light.contains("style=\"color:"));
+        assert_ne!(
+            dark, light,
+            "Theme change should alter the colours"
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_unknown_theme() {
+        let markdown = "```rust\nfn main() {}\n```";
+        let options = default_markdown_options()
+            .with_custom_theme("Missing Theme".to_string());
+
+        let result = process_markdown(markdown, &options);
+        assert!(matches!(
+            result,
+            Err(MarkdownError::SyntaxHighlightError(ref msg)) if msg.contains("Missing Theme")
+        ));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_line_numbers() {
+        let markdown = "```rust\nfn main() {\n\n    let x = 1;\n}\n```";
+        let options =
+            default_markdown_options().with_line_numbers(true);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(r#"<pre><code class="language-rust">"#));
+        assert_eq!(
+            html.matches(r#"<span class="line-number">"#).count(),
+            4
+        );
+        assert!(html.contains(r#"<span class="line-number">1</span>"#));
+        assert!(html.contains(r#"<span class="line-number">4</span>"#));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_line_numbers_restart_per_block() {
+        let markdown =
+            "```rust\nlet a = 1;\n```\n\n```rust\nlet b = 2;\n```";
+        let options =
+            default_markdown_options().with_line_numbers(true);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert_eq!(
+            html.matches(r#"<span class="line-number">1</span>"#)
+                .count(),
+            2
+        );
+        assert!(!html.contains(r#"<span class="line-number">2</span>"#));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_parse_highlight_spec() {
+        assert_eq!(parse_highlight_spec("{3}"), vec![3..=3]);
+        assert_eq!(parse_highlight_spec("{2-4}"), vec![2..=4]);
+        assert_eq!(
+            parse_highlight_spec("{1, 4-5,2}"),
+            vec![1..=1, 4..=5, 2..=2]
+        );
+        assert!(parse_highlight_spec("{0,x,3-,5-4}").is_empty());
+        assert!(parse_highlight_spec("title=main.rs").is_empty());
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_single_highlighted_line() {
+        let markdown =
+            "```rust {2}\nlet a = 1;\nlet b = 2;\nlet c = 3;\n```";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+
+        assert!(html.contains(r#"<pre><code class="language-rust">"#));
+        assert!(!html.contains("data-meta"));
+        assert_eq!(
+            html.matches(r#"<span class="highlighted-line">"#).count(),
+            1
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_highlighted_ranges() {
+        let markdown = "```rust {1,3-4,9}\na\nb\nc\nd\ne\n```";
+        let options =
+            default_markdown_options().with_line_numbers(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        // Line 9 is out of range and is ignored
+        assert_eq!(
+            html.matches(r#"<span class="highlighted-line">"#).count(),
+            3
+        );
+        assert!(html.contains(r#"<span class="highlighted-line"><span class="line-number">3</span>"#));
+        assert!(html.contains(r#"<span class="highlighted-line"><span class="line-number">4</span>"#));
+        assert!(!html.contains(r#"<span class="highlighted-line"><span class="line-number">2</span>"#));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_builtin_language_aliases() {
+        let options = default_markdown_options();
+        for (lang, code) in [
+            ("js", "let x = 1;"),
+            ("shell", "echo $HOME"),
+            ("yml", "key: 1"),
+            ("c++", "int main() { return 0; }"),
+            ("cpp", "int main() { return 0; }"),
+            ("csharp", "public class Program { }"),
+            ("c#", "public class Program { }"),
+        ] {
+            let markdown = format!("```{}\n{}\n```", lang, code);
+            let html = process_markdown(&markdown, &options).unwrap();
+            assert!(
+                html.matches("<span style=\"color:").count() > 1,
+                "`{}` should produce coloured spans: {}",
+                lang,
+                html
+            );
+        }
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_custom_language_alias() {
+        let markdown = "```rustlang\nfn main() {}\n```";
+        let plain =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        let aliased = process_markdown(
+            markdown,
+            &default_markdown_options()
+                .with_language_alias("RustLang", "Rust"),
+        )
+        .unwrap();
+
+        assert!(aliased
+            .contains(r#"<pre><code class="language-rustlang">"#));
+        assert_ne!(plain, aliased, "Alias should switch the syntax");
+    }
+
+    #[test]
+    fn test_highlight_fallback_plain_text() {
+        let markdown = "```unknownlang\na < b\n```";
+        let options = default_markdown_options()
+            .with_highlight_fallback(HighlightFallback::PlainText);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html
+            .contains(r#"<pre><code class="language-unknownlang">"#));
+        assert!(html.contains("a &lt; b"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_fallback_error() {
+        let markdown = "```unknownlang\na < b\n```";
+        let options = default_markdown_options()
+            .with_highlight_fallback(HighlightFallback::Error);
+
+        let result = process_markdown(markdown, &options);
+        assert!(matches!(
+            result,
+            Err(MarkdownError::ConversionError(ref msg)) if msg.contains("unknownlang")
+        ));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_error_position() {
+        // The failing block opens on line 11 of the source; the custom
+        // block and the code block before it take a different number of
+        // lines in the HTML.
+        let markdown = "# Title\n\n<div class=\"note\">One.\n\nTwo.</div>\n\n```rust\nfn main() {}\n```\n\n```unknownlang\na < b\n```\n";
+        let options = default_markdown_options()
+            .with_highlight_fallback(HighlightFallback::Error);
+        let error = process_markdown(markdown, &options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to convert Markdown to HTML: Unknown code block language 'unknownlang' (at line 11 of the Markdown source)"
+        );
+
+        let markdown = "Intro\n\n```text\n~~~\n```\n\n```rust\nfn main() {}\n```\n\n```nosuchlang\nx\n```\n";
+        let error = process_markdown(markdown, &options).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("(at line 11 of the Markdown source)"));
+
+        let markdown = "Intro\n\n<pre><code class=\"language-nosuchlang\">x</code></pre>\n";
+        let error = process_markdown(markdown, &options).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("(at byte 13 of the rendered HTML)"));
+
+        // A raw HTML block before a fenced one of the same language
+        let markdown = "<pre><code class=\"language-nosuchlang\">x</code></pre>\n\n```nosuchlang\ny\n```\n";
+        let error = process_markdown(markdown, &options).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("(at byte 0 of the rendered HTML)"));
+
+        // Blocks added by the pre-processor are not in the source
+        let markdown = "Intro\n\n```nolang\ny\n```\n";
+        let options = options.with_pre_processor(std::sync::Arc::new(
+            |markdown| format!("```nolang\nx\n```\n\n{}", markdown),
+        ));
+        let error = process_markdown(markdown, &options).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("(at byte 0 of the rendered HTML)"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_line_of_offset() {
+        assert_eq!(line_of_offset("", 0), 1);
+        assert_eq!(line_of_offset("a\nb\nc", 2), 2);
+        assert_eq!(line_of_offset("a\nb\nc", 4), 3);
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_fallback_skip_highlighting() {
+        let markdown =
+            "```unknownlang\na < b\n```\n\n```rust\nfn main() {}\n```";
+        let options = default_markdown_options()
+            .with_highlight_fallback(
+                HighlightFallback::SkipHighlighting,
+            );
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            "<pre><code class=\"language-unknownlang\">a &lt; b\n</code></pre>"
+        ));
+        assert!(html.contains("<span style=\"color:"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_fallback_guess() {
+        let script = "```\n#!/bin/bash\necho \"hi\"\n```";
+        let prose = "```\nSome words & more words\n```";
+        let options = default_markdown_options()
+            .with_highlight_fallback(HighlightFallback::Guess);
+
+        let html = process_markdown(script, &options).unwrap();
+        assert!(html.starts_with(r#"<pre><code class="language-sh">"#));
+        assert!(html.contains("<span style=\"color:"));
+
+        let html = process_markdown(prose, &options).unwrap();
+        assert_eq!(
+            html,
+            "<pre><code>Some words &amp; more words\n</code></pre>\n"
+        );
+
+        let html =
+            process_markdown(script, &default_markdown_options())
+                .unwrap();
+        assert!(html.starts_with("<pre><code>#!/bin/bash"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_with_syntax_theme() {
+        let markdown = "```rust\nfn main() {}\n```";
+        for theme in [
+            SyntaxTheme::Base16OceanDark,
+            SyntaxTheme::Base16OceanLight,
+            SyntaxTheme::InspiredGitHub,
+            SyntaxTheme::SolarizedDark,
+            SyntaxTheme::SolarizedLight,
+        ] {
+            let options =
+                default_markdown_options().with_syntax_theme(theme);
+            assert_eq!(
+                options.syntax_theme.as_deref(),
+                Some(theme.as_name())
+            );
+
+            let expected = process_markdown(
+                markdown,
+                &default_markdown_options()
+                    .with_custom_theme(theme.as_name().to_string()),
+            )
+            .unwrap();
+            assert_eq!(
+                process_markdown(markdown, &options).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_theme_pair() {
+        let markdown = "```rust\nfn main() {}\n```";
+        let options = default_markdown_options().with_theme_pair(
+            "InspiredGitHub".to_string(),
+            "base16-ocean.dark".to_string(),
+        );
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert_eq!(
+            html.matches(r#"<pre data-theme="light" style="#).count(),
+            1
+        );
+        assert_eq!(
+            html.matches(r#"<pre data-theme="dark" style="#).count(),
+            1
+        );
+        assert_eq!(
+            html.matches(r#"<pre><code class="language-rust">"#)
+                .count(),
+            1
+        );
+
+        let single =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!single.contains("data-theme"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_unknown_theme_in_pair() {
+        let options = default_markdown_options().with_theme_pair(
+            "InspiredGitHub".to_string(),
+            "Missing Dark".to_string(),
+        );
+        let result =
+            process_markdown("```rust\nfn main() {}\n```", &options);
+        assert!(matches!(
+            result,
+            Err(MarkdownError::SyntaxHighlightError(ref msg)) if msg.contains("Missing Dark")
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_with_registered_custom_block() {
+        let markdown = "<div class=\"success\">All done.</div>";
+        let options = default_markdown_options().with_custom_block(
+            "success",
+            "alert-success",
+            "Success",
+        );
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(r#"<div class="alert alert-success" role="note"><strong>Success:</strong> All done.</div>"#));
+    }
+
+    #[test]
+    fn test_process_markdown_with_github_alerts() {
+        let markdown =
+            "> [!WARNING]\n> Mind the gap.\n\n> Just a quote.";
+
+        let html = process_markdown(
+            markdown,
+            &default_markdown_options().with_github_alerts(true),
+        )
+        .unwrap();
+        assert!(html.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong> <p>Mind the gap.</p></div>"#));
+        assert!(html.contains(
+            "<blockquote>\n<p>Just a quote.</p>\n</blockquote>"
+        ));
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html.contains("[!WARNING]"));
+    }
+
+    #[test]
+    fn test_process_markdown_with_collapsible_blocks() {
+        let markdown = "<div class=\"warning\">Long warning.</div>";
+        let options =
+            default_markdown_options().with_collapsible_blocks(true);
+
+        let html = process_markdown(markdown, &options).unwrap();
         assert!(
-            html.contains("<div class=\"note\">This is a note.</div>"),
-            "Custom block should not be processed when disabled"
+            html.contains(r#"<details class="alert alert-warning">"#)
+        );
+        assert!(html.contains(
+            "<summary><strong>Warning:</strong></summary> Long warning.</details>"
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_with_table_classes() {
+        let markdown = "| A |\n|---|\n| 1 |";
+        let options =
+            default_markdown_options().with_table_classes(vec![
+                "table".to_string(),
+                "table-striped".to_string(),
+                "table-bordered".to_string(),
+            ]);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            r#"<table class="table table-striped table-bordered">"#
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_with_table_captions() {
+        let markdown =
+            "Intro\n[Table: Quarterly & yearly results]\n| A |\n|---|\n| 1 |";
+        let options =
+            default_markdown_options().with_table_captions(true);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            "<div class=\"table-responsive\"><table class=\"table\">\n<caption>Quarterly &amp; yearly results</caption>\n<thead>"
+        ));
+        assert!(!html.contains("[Table:"));
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(
+            html.contains("[Table: Quarterly &amp; yearly results]")
+        );
+        assert!(!html.contains("<caption>"));
+    }
+
+    #[test]
+    fn test_process_markdown_with_table_footer() {
+        let markdown =
+            "| Item | Qty |\n|---|--:|\n| Apples | 3 |\n| = Total | = 3 |";
+        let options = default_markdown_options()
+            .with_table_footer_marker("=".to_string());
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains("</tbody>\n<tfoot>\n<tr>\n<td class=\"text-left\">Total</td>\n<td align=\"right\" class=\"text-right\">3</td>\n</tr>\n</tfoot>"));
+    }
+
+    #[test]
+    fn test_text_direction() {
+        let markdown =
+            "| A |\n|---|\n| ب |\n\n<div class=\"note\">ملاحظة</div>\n";
+        let options = default_markdown_options()
+            .with_github_alerts(true)
+            .with_text_direction(Direction::Rtl);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            r#"<div class="table-responsive" dir="rtl"><table class="table">"#
+        ));
+        assert!(html.contains(
+            r#"<div class="alert alert-info" role="note" dir="rtl"><strong>Note:</strong>"#
+        ));
+
+        let alert =
+            process_markdown("> [!TIP]\n> نصيحة\n", &options).unwrap();
+        assert!(alert.contains(r#"role="note" dir="rtl">"#));
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("dir="));
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_text_direction_with_sanitize_html() {
+        let markdown =
+            "| A |\n|---|\n| ب |\n\n<div class=\"note\">ملاحظة</div>\n";
+        let options = default_markdown_options()
+            .with_text_direction(Direction::Rtl)
+            .with_sanitize_html(true);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            r#"<div class="table-responsive" dir="rtl"><table class="table">"#
+        ));
+        assert!(html.contains(r#"role="note" dir="rtl">"#));
+    }
+
+    #[test]
+    fn test_heading_scroll_margin() {
+        let markdown =
+            "# Title\n\n<h2 class=\"raw\">Raw</h2>\n\n## Section\n";
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_heading_scroll_margin(64);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(!html.contains("scroll-margin-top"));
+
+        let html = process_markdown(
+            markdown,
+            &options.with_heading_anchors(true),
+        )
+        .unwrap();
+        assert!(html.contains(
+            r#"<h1 id="title" style="scroll-margin-top:64px">Title</h1>"#
+        ));
+        assert!(html.contains(
+            r#"<h2 id="section" style="scroll-margin-top:64px">Section</h2>"#
+        ));
+        assert!(html.contains(r#"<h2 class="raw">Raw</h2>"#));
+        assert_eq!(html.matches("scroll-margin-top").count(), 2);
+    }
+
+    #[test]
+    fn test_process_markdown_with_heading_anchors() {
+        let markdown = "# Hello, World!\n\n## Hello, World!";
+        let options = default_markdown_options()
+            .with_heading_anchors(true)
+            .with_heading_permalinks(true);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(r##"<h1 id="hello-world">Hello, World! <a class="heading-anchor" href="#hello-world""##));
+        assert!(html.contains(r#"<h2 id="hello-world-1">"#));
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html.contains("<h1>Hello, World!</h1>"));
+    }
+
+    #[test]
+    fn test_process_markdown_with_metadata() {
+        let markdown = "# Title\n\nSome *emphasised* <span class=\"x\">text</span> here.\n\n## Next Part";
+        let options =
+            default_markdown_options().with_heading_anchors(true);
+
+        let document =
+            process_markdown_with_metadata(markdown, &options).unwrap();
+        assert_eq!(
+            document.html,
+            process_markdown(markdown, &options).unwrap()
+        );
+        assert_eq!(document.word_count, 7);
+        assert_eq!(document.reading_time_minutes, 1);
+        assert_eq!(
+            document.headings,
+            vec![
+                Heading {
+                    level: 1,
+                    text: "Title".to_string(),
+                    id: Some("title".to_string()),
+                },
+                Heading {
+                    level: 2,
+                    text: "Next Part".to_string(),
+                    id: Some("next-part".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_metadata_reading_time_uses_words_per_minute() {
+        let markdown = "word ".repeat(500);
+        let document = process_markdown_with_metadata(
+            &markdown,
+            &default_markdown_options().with_words_per_minute(100),
+        )
+        .unwrap();
+        assert_eq!(document.word_count, 500);
+        assert_eq!(document.reading_time_minutes, 5);
+
+        let document = process_markdown_with_metadata(
+            &markdown,
+            &default_markdown_options(),
+        )
+        .unwrap();
+        assert_eq!(document.reading_time_minutes, 3);
+    }
+
+    #[test]
+    fn test_estimate_reading_time_short_text() {
+        assert_eq!(estimate_reading_time("Just a few words.", 200), 1);
+        assert_eq!(estimate_reading_time("", 200), 1);
+    }
+
+    #[test]
+    fn test_estimate_reading_time_large_document() {
+        let markdown = "# Title\n\n<p>word</p> ".repeat(500);
+        // "#", "Title" and "word" per repetition.
+        assert_eq!(estimate_reading_time(&markdown, 200), 8);
+        assert_eq!(estimate_reading_time(&markdown, 1500), 1);
+    }
+
+    #[test]
+    fn test_estimate_reading_time_excludes_code() {
+        let code = "let x = 1;\n".repeat(100);
+        let markdown = format!("Intro text.\n\n```rust\n{}```\n", code);
+
+        assert_eq!(estimate_reading_time(&markdown, 100), 5);
+        assert_eq!(
+            estimate_reading_time_with_options(&markdown, 100, true),
+            1
+        );
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_strip_comments(true);
+        let html = process_markdown(
+            "<!-- TODO -->\n\n# Title\n\n<pre><!-- example --></pre>\n",
+            &options,
+        )
+        .unwrap();
+        assert!(!html.contains("TODO"));
+        assert!(html.starts_with("<h1>Title</h1>"));
+        assert!(html.contains("<pre><!-- example --></pre>"));
+    }
+
+    #[test]
+    fn test_minify_output() {
+        let markdown = "# Title\n\nSome *text* here.\n\n- one\n- two\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n";
+        let options = default_markdown_options();
+        let html = process_markdown(markdown, &options).unwrap();
+        let minified = process_markdown(
+            markdown,
+            &options.clone().with_minify_output(true),
+        )
+        .unwrap();
+
+        assert!(minified.len() < html.len());
+        assert!(minified.contains("<p>Some <em>text</em> here.</p>"));
+
+        let code_block = |html: &str| {
+            let start = html.find("<pre").unwrap();
+            let end = html.find("</pre>").unwrap();
+            html[start..end].to_string()
+        };
+        assert_eq!(code_block(&minified), code_block(&html));
+    }
+
+    #[test]
+    fn test_pre_processor() {
+        let options = default_markdown_options().with_pre_processor(
+            Arc::new(|markdown: String| {
+                markdown.replace("{{title}}", "# Heading")
+            }),
+        );
+
+        let html =
+            process_markdown("{{title}}\n\nBody text.", &options)
+                .unwrap();
+        assert!(html.contains("<h1>Heading</h1>"));
+        assert!(!html.contains("{{title}}"));
+    }
+
+    #[test]
+    fn test_post_processor() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let options = default_markdown_options()
+            .with_minify_output(true)
+            .with_post_processor(Arc::new(move |html: String| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                html.replace("marker", "MARKER")
+            }));
+
+        let html = process_markdown("Some marker\n\n\nhere.", &options)
+            .unwrap();
+        assert_eq!(html, "<p>Some MARKER</p><p>here.</p>");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_process_markdown_to_writer() {
+        let markdown = "# Title\n\n:::note\nA note\n:::\n\n```rust\nfn main() {}\n```\n";
+        let options = default_markdown_options();
+
+        let mut out = Vec::new();
+        process_markdown_to_writer(markdown, &options, &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            process_markdown(markdown, &options).unwrap()
+        );
+    }
+
+    #[cfg(not(feature = "syntax_highlighting"))]
+    #[test]
+    fn test_syntax_highlighting_without_feature() {
+        let options =
+            default_markdown_options().with_syntax_highlighting(true);
+        let html =
+            process_markdown("```rust\nfn main() {}\n```", &options)
+                .unwrap();
+        assert!(html.contains(
+            "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>"
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_to_writer_io_error() {
+        struct FailingWriter;
+
+        impl Write for FailingWriter {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "pipe closed",
+                ))
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let result = process_markdown_to_writer(
+            "# Title",
+            &default_markdown_options(),
+            &mut FailingWriter,
+        );
+        assert!(matches!(result, Err(MarkdownError::IoError(_))));
+    }
+
+    #[test]
+    fn test_process_markdown_batch() {
+        let documents: Vec<String> = (0..10)
+            .map(|i| {
+                format!(
+                    "# Document {}\n\n:::note\nNote {}\n:::\n\n```rust\nlet x = {};\n```\n",
+                    i, i, i
+                )
+            })
+            .collect();
+        let inputs: Vec<&str> =
+            documents.iter().map(String::as_str).collect();
+        let options = default_markdown_options();
+
+        let results = process_markdown_batch(&inputs, &options);
+        assert_eq!(results.len(), inputs.len());
+        for (input, result) in inputs.iter().zip(results) {
+            assert_eq!(
+                result.unwrap(),
+                process_markdown(input, &options).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_emoji_shortcodes() {
+        let markdown = "Ship it :rocket: :unknown_code:\n\n```text\n:rocket:\n```\n\nInline `:tada:`";
+        let options =
+            default_markdown_options().with_emoji_shortcodes(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains("Ship it 🚀 :unknown_code:"));
+        assert!(html.contains("<code>:tada:</code>"));
+        assert_eq!(html.matches('🚀').count(), 1);
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains('🚀'));
+    }
+
+    #[test]
+    fn test_abbreviations() {
+        let markdown = "HTML is a markup language.\n\n```text\nHTML\n```\n\nSee [HTML](https://html.spec.whatwg.org) and `HTML`.\n";
+        let options = default_markdown_options().with_abbreviations(
+            HashMap::from([(
+                "HTML".to_string(),
+                "HyperText Markup Language".to_string(),
+            )]),
+        );
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(r#"<p><abbr title="HyperText Markup Language">HTML</abbr> is a markup language.</p>"#));
+        assert!(html.contains(
+            "<pre><code class=\"language-text\">HTML\n</code></pre>"
+        ));
+        assert!(html.contains(
+            r#"<a href="https://html.spec.whatwg.org">HTML</a>"#
+        ));
+        assert!(html.contains("<code>HTML</code>"));
+        assert_eq!(html.matches("<abbr").count(), 1);
+    }
+
+    #[test]
+    fn test_kbd_shortcuts() {
+        let markdown = "Press [[Enter]] or [[Ctrl+C]].\n\n```text\n[[Ctrl+C]]\n```\n";
+        let options =
+            default_markdown_options().with_kbd_shortcuts(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(
+            "Press <kbd>Enter</kbd> or <kbd>Ctrl</kbd>+<kbd>C</kbd>."
+        ));
+        assert!(html.contains("[[Ctrl+C]]\n</code></pre>"));
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("<kbd>"));
+    }
+
+    #[test]
+    fn test_external_link_attributes() {
+        let markdown = "[External](https://rust-lang.org), [internal](/docs/intro), [fragment](#usage) and [home](https://example.com/about)";
+        let options = default_markdown_options()
+            .with_external_link_attributes(true)
+            .with_base_url("https://example.com".to_string());
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(r#"<a href="https://rust-lang.org" target="_blank" rel="noopener noreferrer">External</a>"#));
+        assert!(html.contains(r#"<a href="/docs/intro">internal</a>"#));
+        assert!(html.contains(r##"<a href="#usage">fragment</a>"##));
+        assert!(html.contains(
+            r#"<a href="https://example.com/about">home</a>"#
+        ));
+    }
+
+    #[test]
+    fn test_url_rewriters() {
+        let markdown = "[Guide](/docs/guide) [Rust](https://rust-lang.org)\n\n![Logo](/docs/logo.png) ![Badge](https://example.com/b.svg)";
+        let options = default_markdown_options()
+            .with_link_url_rewriter(|url: &str| {
+                match url.strip_prefix("/docs/") {
+                    Some(path) => {
+                        format!("https://cdn.example.com/{}", path)
+                    }
+                    None => url.to_string(),
+                }
+            })
+            .with_image_url_rewriter(|url: &str| {
+                format!("{}?v=2", url.replace("https://", "//"))
+            });
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(
+            r#"<a href="https://cdn.example.com/guide">Guide</a>"#
+        ));
+        assert!(html
+            .contains(r#"<a href="https://rust-lang.org">Rust</a>"#));
+        assert!(html.contains(r#"src="/docs/logo.png?v=2""#));
+        assert!(html.contains(r#"src="//example.com/b.svg?v=2""#));
+    }
+
+    #[test]
+    fn test_image_dimensions_from_query() {
+        let markdown =
+            "![Photo](photo.jpg?w=800&h=600)\n\n![Logo](logo.png)";
+        let options = default_markdown_options()
+            .with_image_dimensions_from_query(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(r#"<img src="photo.jpg?w=800&amp;h=600" alt="Photo" width="800" height="600" />"#));
+        assert!(html.contains(r#"<img src="logo.png" alt="Logo" />"#));
+
+        let html = process_markdown(
+            markdown,
+            &options.with_strip_image_dimension_params(true),
+        )
+        .unwrap();
+        assert!(html.contains(r#"<img src="photo.jpg" alt="Photo" width="800" height="600" />"#));
+
+        let options = default_markdown_options()
+            .with_strip_image_dimension_params(true);
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_lazy_images() {
+        let markdown = "![Chart](chart.png)\n\n<img src=\"hero.png\" loading=\"eager\">\n\n<div class=\"note\"><img src=\"note.png\"></div>";
+        let options = default_markdown_options().with_lazy_images(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(r#"<img src="chart.png" alt="Chart" loading="lazy" decoding="async" />"#));
+        assert!(html.contains(
+            r#"<img src="hero.png" loading="eager" decoding="async">"#
+        ));
+        assert!(html.contains(
+            r#"<div class="alert alert-info" role="note"><strong>Note:</strong> <img src="note.png" loading="lazy" decoding="async"></div>"#
+        ));
+        assert_eq!(html.matches("loading=").count(), 3);
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_code_blocks_without_code_borrows() {
+        let html =
+            "<h1>Title</h1>\n<p>Just <code>inline</code> prose.</p>\n";
+        let options = default_markdown_options();
+
+        let result =
+            highlight_code_blocks(html, Some(html), &options).unwrap();
+        assert!(matches!(result, Cow::Borrowed(_)));
+        assert_eq!(result, html);
+
+        let html = "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n";
+        let result =
+            highlight_code_blocks(html, Some(html), &options).unwrap();
+        assert!(matches!(result, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_process_markdown_accepts_string_types() {
+        let options = default_markdown_options();
+        let expected = process_markdown("# Title", &options).unwrap();
+
+        assert_eq!(
+            process_markdown(String::from("# Title"), &options)
+                .unwrap(),
+            expected
+        );
+        let cow: Cow<str> = Cow::Owned("# Title".to_string());
+        assert_eq!(process_markdown(cow, &options).unwrap(), expected);
+        assert_eq!(
+            process_markdown_bytes(b"# Title", &options).unwrap(),
+            expected
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_stages() {
+        let markdown = "```rust\nfn main() {}\n```\n\n| A | B |\n|---|---|\n| 1 | 2 |\n";
+        let options = default_markdown_options();
+        let stages =
+            process_markdown_stages(markdown, &options).unwrap();
+
+        assert!(stages
+            .comrak_html
+            .contains("<pre><code class=\"language-rust\">"));
+        assert!(stages.comrak_html.contains("<table>"));
+
+        assert_ne!(stages.after_highlight, stages.comrak_html);
+        assert!(stages
+            .after_highlight
+            .contains("<span style=\"color:"));
+        assert!(stages.after_highlight.contains("<table>"));
+
+        assert_ne!(stages.after_tables, stages.after_highlight);
+        assert!(stages
+            .after_tables
+            .contains("<div class=\"table-responsive\">"));
+
+        assert_eq!(stages.final_html, stages.after_tables);
+        assert_eq!(
+            stages.final_html,
+            process_markdown(markdown, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_max_input_bytes() {
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_max_input_bytes(1024);
+
+        assert!(process_markdown("# Small", &options).is_ok());
+
+        let oversized = "> ".repeat(1_000_000);
+        let result = process_markdown(&oversized, &options);
+        assert!(matches!(
+            result,
+            Err(MarkdownError::ParseError(ref msg))
+                if msg == "Input is 2000000 bytes, exceeding the limit of 1024 bytes"
+        ));
+        assert!(process_markdown("x".repeat(1024), &options).is_ok());
+        assert_eq!(MarkdownOptions::new().max_input_bytes, None);
+    }
+
+    #[test]
+    fn test_unclosed_custom_blocks() {
+        let markdown = "<div class=\"note\">\n".repeat(2000);
+        let html =
+            process_markdown(&markdown, &default_markdown_options())
+                .unwrap();
+        assert_eq!(html.matches("<div class=\"note\">").count(), 2000);
+    }
+
+    #[test]
+    fn test_process_markdown_bytes_invalid_utf8() {
+        let result = process_markdown_bytes(
+            &[b'#', b' ', 0xff, 0xfe],
+            &default_markdown_options(),
+        );
+        assert!(matches!(
+            result,
+            Err(MarkdownError::ParseError(ref msg)) if msg.contains("UTF-8")
+        ));
+    }
+
+    #[test]
+    fn test_multi_paragraph_custom_block() {
+        let markdown = "<div class=\"note\">\nFirst *paragraph*.\n\nSecond paragraph.\n</div>\n\nAfter\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+
+        assert!(html.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> "#));
+        assert!(html.contains(
+            "<p>First <em>paragraph</em>.</p>\n<p>Second paragraph.</p>\n</div>"
+        ));
+        assert!(html.contains("<p>After</p>"));
+    }
+
+    #[test]
+    fn test_fast_path_matches_unguarded_passes() {
+        let prose = "# Title\n\nSome *prose* with a [link](/a) and `code`.\n\n- item\n\n---\n";
+        let options =
+            default_markdown_options().with_github_alerts(true);
+        let html = markdown_to_html(prose, &options.comrak_options);
+        assert!(!html.contains(CUSTOM_BLOCK_MARKER));
+        assert!(!html.contains(GITHUB_ALERT_MARKER));
+        assert!(!may_contain_table(&html));
+
+        // Each skipped pass would have left the HTML unchanged
+        let block_options = options.custom_block_options();
+        assert_eq!(
+            process_custom_blocks_with_options(
+                &html,
+                &options.custom_block_registry,
+                &block_options,
+            ),
+            html
+        );
+        assert_eq!(
+            process_github_alerts(
+                &html,
+                &options.custom_block_registry,
+                &block_options,
+            ),
+            html
+        );
+        assert_eq!(
+            process_tables_with_options(
+                &html,
+                &options.table_options()
+            ),
+            html
+        );
+        #[cfg(feature = "syntax_highlighting")]
+        assert!(matches!(
+            highlight_code_blocks(&html, Some(&html), &options)
+                .unwrap(),
+            Cow::Borrowed(_)
+        ));
+
+        let bare = options
+            .clone()
+            .with_syntax_highlighting(false)
+            .with_custom_blocks(false)
+            .with_enhanced_tables(false)
+            .with_github_alerts(false);
+        assert_eq!(
+            process_markdown(prose, &options).unwrap(),
+            process_markdown(prose, &bare).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_table_alignment_comment() {
+        let markdown = "<!-- align: right -->\n| Name | Count |\n|:-----|------:|\n| a | 1 |\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+
+        assert!(html.contains(
+            r#"<th align="right" class="text-right">Name</th>"#
+        ));
+        assert!(html.contains(
+            r#"<td align="right" class="text-right">a</td>"#
+        ));
+        assert!(html.contains(
+            r#"<td align="right" class="text-right">1</td>"#
+        ));
+    }
+
+    #[test]
+    fn test_table_responsive_wrapper_disabled() {
+        let markdown = "| A | B |\n|---|---|\n| 1 | 2 |\n\nAfter\n";
+        let options = default_markdown_options()
+            .with_table_responsive_wrapper(false);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.starts_with(r#"<table class="table">"#));
+        assert!(!html.contains("table-responsive"));
+        assert!(!html.contains("</div>"));
+        assert!(html.contains("</table>\n<p>After</p>"));
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = "| Left | Center | Right |\n|:-----|:------:|------:|\n| a | b | c |\n";
+        let html = render_table(table, &MarkdownOptions::new());
+
+        assert!(html.starts_with(
+            r#"<div class="table-responsive"><table class="table">"#
+        ));
+        assert!(html.contains(
+            r#"<th align="left" class="text-left">Left</th>"#
+        ));
+        assert!(html.contains(
+            r#"<td align="center" class="text-center">b</td>"#
+        ));
+        assert!(html.contains(
+            r#"<td align="right" class="text-right">c</td>"#
+        ));
+        assert!(html.trim_end().ends_with("</table></div>"));
+    }
+
+    #[test]
+    fn test_render_table_rejects_oversized_input() {
+        let table = "| Left | Center | Right |\n|:-----|:------:|------:|\n| a | b | c |\n";
+        let options = MarkdownOptions::new().with_max_input_bytes(10);
+
+        assert_eq!(render_table(table, &options), "");
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_render_table_with_sanitize_html() {
+        let table = "| A |\n|---|\n| <img src=x onerror=alert(1)> |\n";
+        let options = MarkdownOptions::new()
+            .with_sanitize_html(true)
+            .with_allow_raw_html(true);
+        let html = render_table(table, &options);
+
+        assert!(html.contains("<img src=\"x\">"));
+        assert!(!html.contains("onerror"));
+    }
+
+    #[test]
+    fn test_process_markdown_fragment_one_liner() {
+        let options = default_markdown_options();
+        assert_eq!(
+            process_markdown_fragment("Hello *world*\n", &options)
+                .unwrap(),
+            "<p>Hello <em>world</em></p>"
+        );
+
+        let options = options.with_strip_fragment_paragraph(true);
+        assert_eq!(
+            process_markdown_fragment("Hello *world*\n", &options)
+                .unwrap(),
+            "Hello <em>world</em>"
         );
     }
 
     #[test]
-    fn test_process_markdown_without_enhanced_tables() {
-        let markdown = r#"
-# Test Markdown
+    fn test_process_markdown_fragment_multi_block() {
+        let options = default_markdown_options()
+            .with_strip_fragment_paragraph(true);
+        assert_eq!(
+            process_markdown_fragment(
+                "\nFirst\n\nSecond\n\n",
+                &options
+            )
+            .unwrap(),
+            "<p>First</p>\n<p>Second</p>"
+        );
+        assert_eq!(
+            process_markdown_fragment("# Title\n\nText\n", &options)
+                .unwrap(),
+            "<h1>Title</h1>\n<p>Text</p>"
+        );
+    }
 
-| Left | Center | Right |
-|:-----|:------:|------:|
-| 1    |   2    |     3 |
-"#;
+    #[test]
+    fn test_math_katex() {
+        let markdown = "Euler: $e^{i\\pi} + 1 = 0$.\n\n$$\n\\int_0^1 x\\,dx\n$$\n\nIt costs $5 and $10.\n\n`$x$` and\n\n```text\n$y$\n```\n";
+        let options =
+            default_markdown_options().with_math(MathRenderer::KaTeX);
+        let html = process_markdown(markdown, &options).unwrap();
 
-        let options = MarkdownOptions::new()
-            .with_enhanced_tables(false)
-            .with_comrak_options({
-                let mut opts = ComrakOptions::default();
-                opts.extension.table = true;
-                opts
-            });
+        assert!(html.contains(
+            r#"<span class="math inline">e^{i\pi} + 1 = 0</span>"#
+        ));
+        assert!(html.contains(
+            "<div class=\"math display\">\n\\int_0^1 x\\,dx\n</div>"
+        ));
+        assert!(html.contains("It costs $5 and $10."));
+        assert!(html.contains("<code>$x$</code>"));
+        assert_eq!(html.matches("class=\"math").count(), 2);
+    }
 
-        let result = process_markdown(markdown, &options);
-        assert!(result.is_ok(), "Markdown processing failed");
+    #[test]
+    fn test_math_mathjax() {
+        let options =
+            default_markdown_options().with_math(MathRenderer::MathJax);
+        let html =
+            process_markdown("Area $\\pi r^2$", &options).unwrap();
+        assert!(html.contains(
+            r#"<span class="math inline">\(\pi r^2\)</span>"#
+        ));
+    }
 
-        let html = result.unwrap();
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_passthrough_languages() {
+        let markdown = "```mermaid\ngraph TD;\n  A-->B;\n```\n\n```dot\ndigraph { a -> b }\n```\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html.contains(
+            "<pre class=\"mermaid\">graph TD;\n  A--&gt;B;\n</pre>"
+        ));
+        assert!(!html.contains("<pre class=\"dot\">"));
+
+        let options = default_markdown_options()
+            .with_passthrough_languages(vec!["dot".to_string()]);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            "<pre class=\"dot\">digraph { a -&gt; b }\n</pre>"
+        ));
+        assert!(!html.contains("<pre class=\"mermaid\">"));
+    }
+
+    #[test]
+    fn test_class_names() {
+        let markdown = "Run `make`.\n\n```text\nmake all\n```\n\n| A |\n|---|\n| 1 |\n\n<div class=\"note\">Read this.</div>\n\n<div class=\"bogus\">?</div>\n";
+        let options = default_markdown_options()
+            .with_block_icons(true)
+            .with_class_names(ClassNames {
+                code_pre: "code-block".to_string(),
+                code_inline: "code-inline".to_string(),
+                alert_prefix: "callout".to_string(),
+                table_wrapper: "table-scroll".to_string(),
+            })
+            .with_table_classes(vec!["data-table".to_string()]);
+
+        let html = process_markdown(markdown, &options).unwrap();
         assert!(
-            !html.contains(
-                r#"<div class="table-responsive"><table class="table">"#
+            html.contains(r#"<code class="code-inline">make</code>"#)
+        );
+        #[cfg(feature = "syntax_highlighting")]
+        assert!(html.contains(
+            r#"<pre class="code-block"><code class="language-text">"#
+        ));
+        assert!(html.contains(
+            r#"<div class="table-scroll"><table class="data-table">"#
+        ));
+        assert!(html.contains(r#"<div class="callout callout-info" role="note"><span class="callout-icon "#));
+        assert!(html.contains(r#"<div class="callout callout-danger" role="alert"><strong>Error:</strong>"#));
+        assert!(!html.contains(r#"class="alert"#));
+        assert_eq!(
+            process_tables_with_options(
+                &html,
+                &options.table_options()
             ),
-            "Enhanced table processing applied when disabled"
+            html
         );
-        assert!(
-            html.contains("<table>"),
-            "Basic table should still be present"
+
+        let defaults = default_markdown_options();
+        assert_eq!(
+            process_markdown(
+                markdown,
+                &defaults
+                    .clone()
+                    .with_class_names(ClassNames::default())
+            )
+            .unwrap(),
+            process_markdown(markdown, &defaults).unwrap()
         );
+
+        let mut options = defaults
+            .with_table_classes(vec!["striped".to_string()])
+            .with_class_names(ClassNames::default());
+        options.class_names.table_wrapper = "scroll".to_string();
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            r#"<div class="scroll"><table class="striped">"#
+        ));
     }
 
     #[test]
-    fn test_markdown_options_validation() {
+    fn test_ordered_list_style() {
+        let markdown = "3. First\n   1. Nested\n4. Second\n";
+        let options = default_markdown_options()
+            .with_ordered_list_style(ListStyle::LowerAlpha);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.starts_with("<ol type=\"a\" start=\"3\">"));
+        assert!(html.contains("<li>First\n<ol type=\"a\">"));
+        assert_eq!(html.matches("<ol type=\"a\"").count(), 2);
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("type="));
+    }
+
+    #[test]
+    fn test_ordered_list_style_skips_footnotes() {
+        let markdown = "1. Item[^1]\n\n[^1]: A note.\n";
+        let options = default_markdown_options()
+            .with_footnotes(true)
+            .with_ordered_list_style(ListStyle::UpperAlpha);
+
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.starts_with("<ol type=\"A\">"));
+        assert!(html.contains(
+            "<section class=\"footnotes\" data-footnotes>\n<ol>\n"
+        ));
+        assert_eq!(html.matches("type=").count(), 1);
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_ordered_list_style_with_sanitize_html() {
+        let options = default_markdown_options()
+            .with_ordered_list_style(ListStyle::LowerAlpha)
+            .with_sanitize_html(true);
+        let html = process_markdown("3. First\n4. Second\n", &options)
+            .unwrap();
+        assert!(html.starts_with("<ol type=\"a\" start=\"3\">"));
+    }
+
+    #[test]
+    fn test_definition_list_classes() {
+        let mut comrak_options = ComrakOptions::default();
+        comrak_options.extension.description_lists = true;
         let options = MarkdownOptions::new()
-            .with_enhanced_tables(true)
-            .with_comrak_options({
-                let mut opts = ComrakOptions::default();
-                opts.extension.table = false;
-                opts
+            .with_enhanced_tables(false)
+            .with_comrak_options(comrak_options)
+            .with_definition_list_classes(DefinitionListClasses {
+                dt: vec!["term".to_string()],
+                dd: vec!["definition".to_string()],
+                ..DefinitionListClasses::default()
             });
 
-        assert!(options.validate().is_err(), "Validation should fail when enhanced tables are enabled but Comrak table extension is disabled");
+        let html = process_markdown(
+            "Apple\n: A fruit.\n: A company.\n\nRust\n: A language.\n",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "<dl class=\"dl-horizontal\">\n\
+             <dt class=\"term\">Apple</dt>\n\
+             <dd class=\"definition\">A fruit.</dd>\n\
+             <dd class=\"definition\">A company.</dd>\n\
+             <dt class=\"term\">Rust</dt>\n\
+             <dd class=\"definition\">A language.</dd>\n\
+             </dl>\n"
+        );
+
+        let invalid = MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_definition_list_classes(
+                DefinitionListClasses::default(),
+            );
+        assert!(matches!(
+            invalid.validate(),
+            Err(MarkdownError::InvalidOptionsError(_))
+        ));
+    }
 
+    #[test]
+    fn test_footnote_heading() {
+        let mut comrak_options = ComrakOptions::default();
+        comrak_options.extension.footnotes = true;
         let options = MarkdownOptions::new()
-            .with_enhanced_tables(true)
-            .with_comrak_options({
-                let mut opts = ComrakOptions::default();
-                opts.extension.table = true;
-                opts
-            });
+            .with_enhanced_tables(false)
+            .with_comrak_options(comrak_options)
+            .with_footnote_heading("Notas".to_string());
 
+        let html = process_markdown(
+            "<section class=\"intro\">\n\nIntro\n\n</section>\n\nA[^1] and B[^2].\n\n[^1]: Uno.\n[^2]: Dos.\n",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            html.matches(r#"<h2 class="footnotes-heading">Notas</h2>"#)
+                .count(),
+            1
+        );
+        assert!(html.contains(
+            "<section class=\"footnotes\" data-footnotes>\n<h2 class=\"footnotes-heading\">Notas</h2>\n<ol>"
+        ));
         assert!(
-            options.validate().is_ok(),
-            "Validation should pass when options are consistent"
+            html.contains("<section class=\"intro\">\n<p>Intro</p>")
         );
+
+        let html =
+            process_markdown("No footnotes here.", &options).unwrap();
+        assert!(!html.contains("footnotes-heading"));
     }
 
     #[test]
-    fn test_markdown_options_builder() {
+    fn test_preset_github() {
+        let options = MarkdownOptions::preset_github();
+        let extension = &options.comrak_options.extension;
+        assert!(extension.table);
+        assert!(extension.strikethrough);
+        assert!(extension.tasklist);
+        assert!(extension.autolink);
+        assert!(extension.tagfilter);
+        assert!(options.enable_enhanced_tables);
+        assert!(options.enable_custom_blocks);
+        assert!(options.enable_syntax_highlighting);
+        assert!(options.validate().is_ok());
+
+        let html =
+            process_markdown("- [x] Done\n- [ ] Todo\n", &options)
+                .unwrap();
+        assert!(html.contains(
+            r#"<li><input type="checkbox" checked="" disabled="" /> Done</li>"#
+        ));
+        assert!(html.contains(
+            r#"<li><input type="checkbox" disabled="" /> Todo</li>"#
+        ));
+    }
+
+    #[test]
+    fn test_comrak_extension_toggles() {
         let options = MarkdownOptions::new()
-            .with_custom_blocks(false)
-            .with_syntax_highlighting(true)
-            .with_enhanced_tables(false);
+            .with_tables(true)
+            .with_strikethrough(true)
+            .with_tasklist(true)
+            .with_autolink(true)
+            .with_footnotes(true)
+            .with_syntax_highlighting(false);
+        assert!(options.validate().is_ok());
 
-        assert!(!options.enable_custom_blocks);
-        assert!(options.enable_syntax_highlighting);
+        let html = process_markdown(
+            "| A |\n|---|\n| 1 |\n\n~~old~~[^1] https://example.com\n\n- [x] Done\n\n[^1]: Note.\n",
+            &options,
+        )
+        .unwrap();
+        assert!(html.contains("<table class=\"table\">"));
+        assert!(html.contains("<del>old</del>"));
+        assert!(html.contains(
+            "<a href=\"https://example.com\">https://example.com</a>"
+        ));
+        assert!(html.contains(
+            r#"<input type="checkbox" checked="" disabled="" />"#
+        ));
+        assert!(html.contains("<section class=\"footnotes\""));
+
+        let options = MarkdownOptions::new().with_tables(false);
+        assert!(!options.comrak_options.extension.table);
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_preset_commonmark() {
+        let options = MarkdownOptions::preset_commonmark();
+        let extension = &options.comrak_options.extension;
+        assert!(!extension.table);
+        assert!(!extension.strikethrough);
+        assert!(!extension.tasklist);
+        assert!(!extension.autolink);
         assert!(!options.enable_enhanced_tables);
+        assert!(!options.enable_custom_blocks);
+        assert!(!options.enable_syntax_highlighting);
+        assert!(options.validate().is_ok());
+
+        let html =
+            process_markdown("- [ ] Todo\n\n~~old~~\n", &options)
+                .unwrap();
+        assert!(html.contains("<li>[ ] Todo</li>"));
+        assert!(html.contains("~~old~~"));
     }
 
+    #[cfg(feature = "syntax_highlighting")]
     #[test]
-    fn test_process_markdown_with_invalid_options() {
-        let markdown = "# Test\n\n| Column 1 | Column 2 |\n| -------- | -------- |\n| Value 1  | Value 2  |";
-        let options = MarkdownOptions::new()
-            .with_enhanced_tables(true)
-            .with_comrak_options({
-                let mut opts = ComrakOptions::default();
-                opts.extension.table = false;
-                opts
-            });
-        let result = process_markdown(markdown, &options);
-        assert!(result.is_err());
+    fn test_unhighlighted_languages() {
+        let options = default_markdown_options();
+        for lang in
+            ["text", "plain", "nohighlight", "no-highlight", "TEXT"]
+        {
+            let html = process_markdown(
+                format!("```{}\nlet x = <b>1</b>;\n```", lang),
+                &options,
+            )
+            .unwrap();
+            assert!(!html.contains("<span style"), "{}", html);
+            assert_eq!(
+                html,
+                format!(
+                    "<pre><code class=\"language-{}\">let x = &lt;b&gt;1&lt;/b&gt;;\n</code></pre>\n",
+                    lang
+                )
+            );
+        }
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_theme_for_language() {
+        let options = default_markdown_options()
+            .with_theme_for_language("rust", "InspiredGitHub")
+            .with_theme_for_language("Bash", "Solarized (dark)");
+        let html = process_markdown(
+            "```rust\nfn main() {}\n```\n\n```bash\necho hi\n```\n\n```python\npass\n```\n",
+            &options,
+        )
+        .unwrap();
+
+        let backgrounds: Vec<&str> = html
+            .match_indices("<pre style=\"background-color:")
+            .map(|(start, _)| {
+                let rest = &html[start + 29..];
+                &rest[..rest.find(';').unwrap()]
+            })
+            .collect();
+        assert_eq!(backgrounds, vec!["#ffffff", "#002b36", "#2b303b"]);
+
+        let options = default_markdown_options()
+            .with_theme_for_language("bash", "No Such Theme");
         assert!(matches!(
-            result,
-            Err(MarkdownError::InvalidOptionsError(_))
+            process_markdown("```bash\necho hi\n```", &options),
+            Err(MarkdownError::SyntaxHighlightError(ref msg))
+                if msg == "Unknown theme 'No Such Theme' for language 'bash'"
         ));
     }
 
+    #[cfg(feature = "syntax_highlighting")]
     #[test]
-    fn test_process_markdown_with_empty_content() {
-        let markdown = "";
-        let options = MarkdownOptions::new()
-            .with_enhanced_tables(false) // No need for enhanced tables in an empty document
-            .with_comrak_options({
-                let mut opts = ComrakOptions::default();
-                opts.extension.table = false; // Disable table extension
-                opts
-            });
+    fn test_repeated_code_blocks_highlight_identically() {
+        let block = "```rust\nuse std::io;\n```\n\n";
+        let options = default_markdown_options();
+        let html = process_markdown(block.repeat(3), &options).unwrap();
+        let single = process_markdown(block, &options).unwrap();
 
-        let result = process_markdown(markdown, &options);
-        assert!(
-            result.is_ok(),
-            "Markdown processing failed for empty content: {:?}",
-            result
+        let blocks: Vec<&str> = html
+            .split("<code class=\"language-rust\">")
+            .skip(1)
+            .map(|b| &b[..b.find("</code>").unwrap()])
+            .collect();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks[0].contains("<span style=\"color:"));
+        assert!(blocks.iter().all(|b| *b == blocks[0]));
+        assert_eq!(html, single.repeat(3));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_code_copy_button() {
+        let markdown = "<div class=\"note\">See below</div>\n\n```rust\nfn main() {}\n```\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("copy-btn"));
+        assert!(!html.contains("code-block"));
+
+        let options =
+            default_markdown_options().with_code_copy_button(true);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(r#"<div class="code-block"><button class="copy-btn" aria-label="Copy">Copy</button><pre><code class="language-rust">"#));
+        assert!(html.trim_end().ends_with("</code></pre></div>"));
+        assert!(html
+            .contains(r#"<div class="alert alert-info" role="note">"#));
+        assert!(!html.contains("alert-danger"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_code_aria_labels() {
+        let markdown =
+            "```rust\nfn main() {}\n```\n\n```\nplain\n```\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("aria-label"));
+
+        let options =
+            default_markdown_options().with_code_aria_labels(true);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(r#"<pre aria-label="Rust code"><code class="language-rust">"#));
+        assert_eq!(html.matches("aria-label").count(), 1);
+        assert!(html.contains("<pre><code>plain"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_tab_width() {
+        let markdown = "```text\nfn main() {\n\tbody();\n}\n```\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html.contains("\tbody();"));
+
+        let options = default_markdown_options().with_tab_width(2);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains("\n  body();"));
+        assert!(!html.contains('\t'));
+
+        let options = default_markdown_options().with_tab_width(4);
+        let html = process_markdown(
+            markdown.replace("text", "rust"),
+            &options,
+        )
+        .unwrap();
+        assert!(!html.contains('\t'));
+        assert!(html.contains(">    </span>"));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_error_attribute() {
+        let markdown = "```nosuchlang\n<a> & b\n```\n\n```rust\nfn main() {}\n```\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("data-highlight-error"));
+
+        let options = default_markdown_options()
+            .with_highlight_error_attribute(true);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(r#"<pre data-highlight-error="Failed to convert Markdown to HTML: Unknown code block language 'nosuchlang'"><code class="language-nosuchlang">"#));
+        assert!(html.contains("&lt;a&gt; &amp; b"));
+        assert_eq!(html.matches("data-highlight-error").count(), 1);
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_disable_spellcheck_in_code() {
+        let markdown = "Some prose\n\n```rust\nfn main() {}\n```\n";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("spellcheck"));
+
+        let options = default_markdown_options()
+            .with_disable_spellcheck_in_code(true);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            r#"<pre spellcheck="false"><code class="language-rust">"#
+        ));
+        assert_eq!(html.matches("spellcheck").count(), 1);
+        assert!(html.contains("<p>Some prose</p>"));
+    }
+
+    #[test]
+    fn test_unknown_block_behavior() {
+        let markdown = "<div class=\"unknown\">Mystery <div class=\"note\">Inner</div></div>\n\nAfter";
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html.contains("Failed to process custom block: Unknown block type: unknown"));
+
+        let options = default_markdown_options()
+            .with_unknown_block_behavior(
+                UnknownBlockBehavior::PassThrough,
+            );
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(r#"<div class="unknown">Mystery <div class="alert alert-info" role="note"><strong>Note:</strong> Inner</div></div>"#));
+        assert!(!html.contains("alert-danger"));
+
+        let options = default_markdown_options()
+            .with_unknown_block_behavior(UnknownBlockBehavior::Strip);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(!html.contains("Mystery"));
+        assert!(!html.contains("alert"));
+        assert!(html.contains("<p>After</p>"));
+    }
+
+    #[test]
+    fn test_sanitize_custom_blocks() {
+        let markdown =
+            "<div class=\"note\"><script>alert('x')</script></div>";
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html.contains("<script>"));
+
+        let options = default_markdown_options()
+            .with_sanitize_custom_blocks(true);
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains(r#"<strong>Note:</strong> &lt;script&gt;alert('x')&lt;/script&gt;</div>"#));
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_sanitize_html() {
+        let markdown = "<div class=\"note\">Be careful</div>\n\n<script>alert('x')</script>\n\n<a href=\"#\" onclick=\"steal()\">link</a>\n\n| A |\n|---|\n| 1 |\n";
+        let options =
+            default_markdown_options().with_sanitize_html(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onclick"));
+        assert!(html.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> Be careful</div>"#));
+        assert!(html.contains(r#"<table class="table">"#));
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_sanitize_html_keeps_task_lists() {
+        let options =
+            MarkdownOptions::preset_github().with_sanitize_html(true);
+        let html =
+            process_markdown("- [x] Done\n- [ ] Todo\n", &options)
+                .unwrap();
+        assert!(html.contains(
+            r#"<li><input type="checkbox" checked="" disabled=""> Done</li>"#
+        ));
+        assert!(html.contains(
+            r#"<li><input type="checkbox" disabled=""> Todo</li>"#
+        ));
+
+        let html = process_markdown(
+            "<input type=\"text\" value=\"x\">",
+            &options.with_tasklist(false),
+        )
+        .unwrap();
+        assert!(!html.contains("text"));
+        assert!(!html.contains("value"));
+    }
+
+    #[cfg(all(feature = "sanitize", feature = "syntax_highlighting"))]
+    #[test]
+    fn test_sanitize_html_keeps_spellcheck() {
+        let options = default_markdown_options()
+            .with_disable_spellcheck_in_code(true)
+            .with_sanitize_html(true);
+        let html =
+            process_markdown("```rust\nfn main() {}\n```\n", &options)
+                .unwrap();
+        assert!(html.contains(r#"<pre spellcheck="false">"#));
+    }
+
+    #[cfg(all(feature = "sanitize", feature = "syntax_highlighting"))]
+    #[test]
+    fn test_sanitize_html_keeps_highlight_error_attribute() {
+        let options = default_markdown_options()
+            .with_highlight_error_attribute(true)
+            .with_sanitize_html(true);
+        let html =
+            process_markdown("```nosuchlang\nx\n```\n", &options)
+                .unwrap();
+        assert!(html.contains(r#"<pre data-highlight-error="Failed to convert Markdown to HTML: Unknown code block language 'nosuchlang'">"#));
+    }
+
+    #[cfg(all(feature = "sanitize", feature = "syntax_highlighting"))]
+    #[test]
+    fn test_sanitize_html_restricts_style_and_id() {
+        let options =
+            default_markdown_options().with_sanitize_html(true);
+        let html = process_markdown(
+            "<p id=\"p\" style=\"position:fixed\">Hi</p>\n\n```rust\nfn main() {}\n```\n",
+            &options,
+        )
+        .unwrap();
+        assert!(html.contains("<p>Hi</p>"));
+        assert!(html.contains("<pre style="));
+        assert!(html.contains("<span style="));
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn test_sanitize_html_strips_user_style() {
+        let options =
+            default_markdown_options().with_sanitize_html(true);
+        let html = process_markdown(
+            "<span style=\"position:fixed;top:0;left:0;width:100%;height:100%\">Hi</span> <span style=\"color:#ff0000;background:url(x)\">Red</span>\n",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            html.trim(),
+            "<p><span>Hi</span> <span style=\"color:#ff0000;\">Red</span></p>"
+        );
+
+        // Highlighted code keeps all of its styles
+        let code = "```rust\nfn main() {}\n```\n";
+        let styles = |html: String| -> Vec<String> {
+            html.split("style=\"")
+                .skip(1)
+                .map(|rest| rest.split('"').next().unwrap().to_string())
+                .collect()
+        };
+        let sanitized =
+            styles(process_markdown(code, &options).unwrap());
+        assert_eq!(sanitized.len(), 4);
+        assert_eq!(
+            sanitized,
+            styles(
+                process_markdown(code, &default_markdown_options())
+                    .unwrap()
+            )
         );
-        assert_eq!(result.unwrap().trim(), "");
     }
 
+    #[cfg(not(feature = "sanitize"))]
     #[test]
-    fn test_process_markdown_with_only_custom_blocks() {
-        let markdown = "<div class=\"note\">This is a note.</div>";
-        let options = MarkdownOptions::new()
-            .with_custom_blocks(true)
-            .with_enhanced_tables(false) // Disable enhanced tables since they're not used here
-            .with_comrak_options({
-                let mut opts = ComrakOptions::default();
-                opts.extension.table = false; // Ensure table extension is disabled
-                opts
-            });
+    fn test_sanitize_html_requires_feature() {
+        let options =
+            default_markdown_options().with_sanitize_html(true);
+        assert!(matches!(
+            process_markdown("# Title", &options),
+            Err(MarkdownError::InvalidOptionsError(_))
+        ));
+    }
 
-        let result = process_markdown(markdown, &options);
+    #[test]
+    fn test_disallow_raw_html() {
+        let markdown = "<div class=\"note\">Hello</div>\n\nSome <b>bold</b> text\n\n> [!TIP]\n> Still works";
+        let options = default_markdown_options()
+            .with_github_alerts(true)
+            .with_allow_raw_html(false);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(
+            "&lt;div class=&quot;note&quot;&gt;Hello&lt;/div&gt;"
+        ));
+        assert!(html.contains("Some &lt;b&gt;bold&lt;/b&gt; text"));
+        assert!(!html.contains("alert-info"));
+        assert!(html.contains(r#"<div class="alert alert-success" role="note"><strong>Tip:</strong>"#));
+    }
+
+    #[test]
+    fn test_block_role_overrides() {
+        let markdown = "<div class=\"note\">A</div>\n\n<div class=\"caution\">B</div>";
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html
+            .contains(r#"<div class="alert alert-info" role="note">"#));
+        assert!(html.contains(
+            r#"<div class="alert alert-secondary" role="alert">"#
+        ));
+
+        let options = default_markdown_options()
+            .with_block_role_overrides(
+                [("NOTE".to_string(), "alert".to_string())]
+                    .into_iter()
+                    .collect(),
+            );
+        let html = process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            r#"<div class="alert alert-info" role="alert">"#
+        ));
+    }
+
+    #[test]
+    fn test_aside_for_notes() {
+        let markdown = "<div class=\"note\">A</div>\n\n<div class=\"tip\">B</div>\n\n<div class=\"info\">C</div>\n\n<div class=\"warning\">D</div>\n\n<div class=\"important\">E</div>\n\n<div class=\"caution\">F</div>";
+        let options =
+            default_markdown_options().with_aside_for_notes(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        for (class, element) in [
+            ("alert-info", "aside"),
+            ("alert-success", "aside"),
+            ("alert-primary", "aside"),
+            ("alert-warning", "div"),
+            ("alert-danger", "div"),
+            ("alert-secondary", "div"),
+        ] {
+            let role =
+                if element == "aside" { "note" } else { "alert" };
+            assert!(
+                html.contains(&format!(
+                    r#"<{} class="alert {}" role="{}">"#,
+                    element, class, role
+                )),
+                "{} should render as <{}>: {}",
+                class,
+                element,
+                html
+            );
+        }
+        assert_eq!(html.matches("</aside>").count(), 3);
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(!html.contains("<aside"));
+    }
+
+    #[test]
+    fn test_fenced_custom_blocks() {
+        let markdown = ":::warning\nBe **careful**.\n:::\n\n:::tip Try this\n- one\n- two\n:::\n";
+        let options =
+            default_markdown_options().with_fenced_custom_blocks(true);
+        let html = process_markdown(markdown, &options).unwrap();
+
+        assert!(html.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong> "#));
+        assert!(html.contains("<p>Be <strong>careful</strong>.</p>"));
+        assert!(html.contains(r#"<div class="alert alert-success" role="note"><strong>Try this:</strong> "#));
         assert!(
-            result.is_ok(),
-            "Markdown processing failed for custom blocks: {:?}",
-            result
+            html.contains("<ul>\n<li>one</li>\n<li>two</li>\n</ul>")
         );
-        let html = result.unwrap();
-        assert!(html.contains(r#"<div class="alert alert-info" role="alert"><strong>Note:</strong>"#), "Custom block not processed correctly");
+        assert!(!html.contains(":::"));
+
+        let html = process_markdown(
+            "```text\n~~~\n```\n\n:::note\nHi\n:::\n",
+            &options,
+        )
+        .unwrap();
+        assert!(html.contains(
+            "<pre><code class=\"language-text\">~~~\n</code></pre>"
+        ));
+        assert!(html.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> "#));
+        assert!(html.contains("<p>Hi</p>"));
+
+        let html =
+            process_markdown(markdown, &default_markdown_options())
+                .unwrap();
+        assert!(html.contains(":::warning"));
+    }
+
+    #[test]
+    fn test_custom_block_preserves_code_indentation() {
+        let code = "fn main() {\n    if ready {\n\t\tgo();\n    }\n}\n";
+        let markdown = format!(
+            "<div class=\"note\">\n\n```\n{}```\n\n    indented\n        deeper\n\n</div>\n",
+            code
+        );
+        let expected = [
+            format!("<pre><code>{}</code></pre>", code),
+            "<pre><code>indented\n    deeper\n</code></pre>"
+                .to_string(),
+        ];
+
+        for options in [
+            default_markdown_options(),
+            default_markdown_options().with_collapsible_blocks(true),
+        ] {
+            let html = process_markdown(&markdown, &options).unwrap();
+            assert!(html.contains("<strong>Note:</strong>"));
+            for block in &expected {
+                assert!(html.contains(block.as_str()), "{}", html);
+            }
+        }
     }
 
+    #[cfg(feature = "syntax_highlighting")]
     #[test]
     fn test_apply_syntax_highlighting() {
         let code = r#"fn main() { println!("Hello, world!"); }"#;
@@ -75,9 +75,9 @@ fn main() {
 
     // Check for presence of key elements
     assert!(html.contains("Advanced Markdown Processing Test"));
-    assert!(html.contains(r#"<div class="alert alert-info" role="alert"><strong>Note:</strong>"#));
+    assert!(html.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong>"#));
     assert!(html.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong>"#));
-    assert!(html.contains(r#"<div class="alert alert-success" role="alert"><strong>Tip:</strong>"#));
+    assert!(html.contains(r#"<div class="alert alert-success" role="note"><strong>Tip:</strong>"#));
     assert!(html.contains(r#"<pre><code class="language-rust">"#));
     assert!(html.contains("Hello, world!"));
     assert!(html.contains(
@@ -74,6 +74,41 @@ fn test_markdown_error_syntax_set_error() {
         );
     }
 
+    /// Test the MarkdownError::FrontmatterError variant.
+    #[test]
+    fn test_markdown_error_frontmatter_error() {
+        let error =
+            MarkdownError::FrontmatterError("Invalid YAML".to_string());
+        assert_eq!(
+            format!("{}", error),
+            "Failed to parse frontmatter: Invalid YAML"
+        );
+    }
+
+    /// Test the MarkdownError::IncludeError variant.
+    #[test]
+    fn test_markdown_error_include_error() {
+        let error = MarkdownError::IncludeError(
+            "Circular include of a.md".to_string(),
+        );
+        assert_eq!(
+            format!("{}", error),
+            "Failed to include file: Circular include of a.md"
+        );
+    }
+
+    /// Test the MarkdownError::IoError variant.
+    #[test]
+    fn test_markdown_error_io_error() {
+        let error: MarkdownError = std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "file missing",
+        )
+        .into();
+        assert!(matches!(error, MarkdownError::IoError(_)));
+        assert_eq!(format!("{}", error), "I/O error: file missing");
+    }
+
     /// Test for parsing valid markdown content.
     #[test]
     fn test_parse_markdown_with_context_success() -> Result<()> {
@@ -1,13 +1,20 @@
 #[cfg(test)]
 mod tests {
-    use mdx_gen::extensions::{process_custom_blocks, process_tables};
-    use mdx_gen::{ColumnAlignment, CustomBlockType};
+    use mdx_gen::extensions::{
+        process_custom_blocks, process_custom_blocks_with_options,
+        process_tables, process_tables_with_options,
+    };
+    use mdx_gen::{ColumnAlignment, CustomBlockType, MarkdownOptions};
 
     #[test]
     fn test_column_alignment() {
         assert_eq!(ColumnAlignment::Left, ColumnAlignment::Left);
         assert_eq!(ColumnAlignment::Center, ColumnAlignment::Center);
         assert_eq!(ColumnAlignment::Right, ColumnAlignment::Right);
+        assert_eq!(
+            ColumnAlignment::from_html_attr(r#"align="justify""#),
+            ColumnAlignment::Justify
+        );
     }
 
     #[test]
@@ -38,6 +45,15 @@ fn test_custom_block_get_alert_class() {
         );
     }
 
+    #[test]
+    fn test_custom_block_get_icon_class() {
+        assert_eq!(CustomBlockType::Note.get_icon_class(), "icon-info");
+        assert_eq!(
+            CustomBlockType::Warning.get_icon_class(),
+            "icon-alert"
+        );
+    }
+
     #[test]
     fn test_custom_block_get_title() {
         assert_eq!(CustomBlockType::Note.get_title(), "Note");
@@ -48,6 +64,31 @@ fn test_custom_block_get_title() {
         assert_eq!(CustomBlockType::Caution.get_title(), "Caution");
     }
 
+    #[test]
+    fn test_bootstrap_custom_block_types() {
+        let input = r#"
+            <div class="danger">Danger zone.</div>
+            <div class="success">All good.</div>
+            <div class="primary">Primary.</div>
+            <div class="secondary">Secondary.</div>
+            <div class="important">Still important.</div>
+            <div class="tip">Still a tip.</div>
+        "#;
+
+        let processed = process_custom_blocks(input);
+
+        assert!(processed.contains(r#"<div class="alert alert-danger" role="alert"><strong>Danger:</strong> Danger zone.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-success" role="note"><strong>Success:</strong> All good.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-primary" role="note"><strong>Primary:</strong> Primary.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-secondary" role="note"><strong>Secondary:</strong> Secondary.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-danger" role="alert"><strong>Important:</strong> Still important.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-success" role="note"><strong>Tip:</strong> Still a tip.</div>"#));
+        assert_eq!(
+            "Danger".parse::<CustomBlockType>().unwrap(),
+            CustomBlockType::Danger
+        );
+    }
+
     #[test]
     fn test_process_custom_blocks() {
         let input = r#"
@@ -61,10 +102,10 @@ fn test_process_custom_blocks() {
 
         let processed = process_custom_blocks(input);
 
-        assert!(processed.contains(r#"<div class="alert alert-info" role="alert"><strong>Note:</strong> This is a note.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> This is a note.</div>"#));
         assert!(processed.contains(r#"<div class="alert alert-warning" role="alert"><strong>Warning:</strong> This is a warning.</div>"#));
-        assert!(processed.contains(r#"<div class="alert alert-success" role="alert"><strong>Tip:</strong> This is a tip.</div>"#));
-        assert!(processed.contains(r#"<div class="alert alert-primary" role="alert"><strong>Info:</strong> This is an info block.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-success" role="note"><strong>Tip:</strong> This is a tip.</div>"#));
+        assert!(processed.contains(r#"<div class="alert alert-primary" role="note"><strong>Info:</strong> This is an info block.</div>"#));
         assert!(processed.contains(r#"<div class="alert alert-danger" role="alert"><strong>Important:</strong> This is important.</div>"#));
         assert!(processed.contains(r#"<div class="alert alert-secondary" role="alert"><strong>Caution:</strong> This is a caution.</div>"#));
     }
@@ -89,4 +130,55 @@ fn test_process_tables() {
         );
         assert!(processed.contains("</table></div>"));
     }
+
+    #[test]
+    fn test_justified_table_column() {
+        let markdown = "<table>\n<tr><td align=\"justify\">Justified</td><td>Left</td></tr>\n</table>\n";
+        let options = MarkdownOptions::default().with_comrak_options({
+            let mut opts = mdx_gen::ComrakOptions::default();
+            opts.extension.table = true;
+            opts
+        });
+
+        let html =
+            mdx_gen::process_markdown(markdown, &options).unwrap();
+        assert!(html.contains(
+            r#"<td align="justify" class="text-justify">Justified</td>"#
+        ));
+        assert!(html.contains(r#"<td class="text-left">Left</td>"#));
+    }
+
+    #[test]
+    fn test_extensions_with_markdown_options() {
+        let options = MarkdownOptions::default();
+        let blocks = r#"<div class="note">A note.</div><div class="unknown">?</div>"#;
+        let table = r#"<table><thead><tr><th align="right">A</th></tr></thead><tbody><tr><td>1</td></tr></tbody></table>"#;
+
+        assert_eq!(
+            process_custom_blocks_with_options(
+                blocks,
+                &options.custom_block_registry,
+                &options.custom_block_options(),
+            ),
+            process_custom_blocks(blocks)
+        );
+        assert_eq!(
+            process_tables_with_options(
+                table,
+                &options.table_options()
+            ),
+            process_tables(table)
+        );
+
+        let options =
+            MarkdownOptions::default().with_table_classes(vec![
+                "table".into(),
+                "table-sm".into(),
+            ]);
+        assert!(process_tables_with_options(
+            table,
+            &options.table_options()
+        )
+        .contains(r#"<table class="table table-sm">"#));
+    }
 }
@@ -46,9 +46,10 @@ fn test_process_markdown_with_custom_blocks() {
             });
         let result = process_markdown(markdown, &options)
             .expect("Failed to process markdown with custom blocks");
-        assert!(result.contains(r#"<div class="alert alert-info" role="alert"><strong>Note:</strong>"#), "Custom block was not processed correctly");
+        assert!(result.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong>"#), "Custom block was not processed correctly");
     }
 
+    #[cfg(feature = "syntax_highlighting")]
     #[test]
     fn test_process_markdown_with_syntax_highlighting() {
         let markdown = "```rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n```";
@@ -113,6 +114,33 @@ fn test_process_markdown_with_enhanced_tables() {
         );
     }
 
+    #[test]
+    fn test_markdown_options_builder_renders_gfm() {
+        let markdown = "# Release\n\n| Item | Done |\n|:-----|-----:|\n| ~~Old~~ | [x] |\n\n- [x] Ship it\n- [ ] Announce at https://example.com\n\n<div class=\"note\">Read the notes.</div>\n\n```rust\nfn main() {}\n```\n";
+        let options = MarkdownOptions::builder()
+            .gfm()
+            .enhanced_tables()
+            .syntax_highlighting()
+            .custom_blocks()
+            .build();
+        assert!(options.validate().is_ok());
+
+        let html = process_markdown(markdown, &options)
+            .expect("Failed to process markdown");
+        assert!(html.contains(
+            r#"<div class="table-responsive"><table class="table">"#
+        ));
+        assert!(html.contains("<del>Old</del>"));
+        assert!(html.contains(
+            r#"<input type="checkbox" checked="" disabled="" />"#
+        ));
+        assert!(html.contains(
+            r#"<a href="https://example.com">https://example.com</a>"#
+        ));
+        assert!(html.contains(r#"<div class="alert alert-info""#));
+        assert!(html.contains(r#"<pre><code class="language-rust">"#));
+    }
+
     #[test]
     fn test_markdown_options_customization() {
         let options = MarkdownOptions::new()
@@ -68,6 +68,7 @@ fn test_process_markdown_with_enhanced_tables() {
         );
     }
 
+    #[cfg(feature = "syntax_highlighting")]
     #[test]
     fn test_process_markdown_with_syntax_highlighting() {
         let markdown = "```rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n```";
@@ -178,7 +179,7 @@ fn test_process_markdown_with_only_custom_blocks() {
         println!("Processed HTML:\n{}", html); // Print the actual HTML output
 
         // Use a relaxed assertion to check the critical parts of the custom block
-        assert!(html.contains(r#"<div class="alert alert-info" role="alert"><strong>Note:</strong> This is a note."#),
+        assert!(html.contains(r#"<div class="alert alert-info" role="note"><strong>Note:</strong> This is a note."#),
         "Custom block not processed correctly");
     }
 
@@ -241,4 +242,215 @@ fn test_process_markdown_failure() {
             "Markdown processing should fail for invalid input."
         );
     }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_theme_from_file() {
+        let theme_path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/custom.tmTheme"
+        );
+        let options = MarkdownOptions::new()
+            .with_custom_theme("Solarized (light)".to_string())
+            .with_enhanced_tables(false)
+            .with_theme_from_file(theme_path)
+            .expect("Failed to load theme fixture");
+
+        let result =
+            process_markdown("```rust\nfn main() {}\n```", &options)
+                .expect("Failed to process markdown");
+        assert!(
+            result.contains("color:#ab0000;"),
+            "Theme loaded from file should take precedence: {}",
+            result
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_with_theme_from_missing_file() {
+        let result = MarkdownOptions::new()
+            .with_theme_from_file("missing.tmTheme");
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::SyntaxSetError(ref msg)) if msg.contains("missing.tmTheme")
+        ));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_process_markdown_with_extra_syntaxes_dir() {
+        let syntaxes_dir = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/syntaxes"
+        );
+        let options = MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_extra_syntaxes_dir(syntaxes_dir)
+            .expect("Failed to load syntax fixtures");
+
+        let result =
+            process_markdown("```mdxtest\nbeep\n```", &options)
+                .expect("Failed to process markdown");
+        assert!(
+            result.contains("<span style=\"color:"),
+            "Custom syntax should produce coloured spans: {}",
+            result
+        );
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_with_extra_syntaxes_missing_dir() {
+        let result = MarkdownOptions::new()
+            .with_extra_syntaxes_dir("missing/syntaxes");
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::SyntaxSetError(ref msg)) if msg.contains("missing/syntaxes")
+        ));
+    }
+
+    fn include_options() -> MarkdownOptions<'static> {
+        MarkdownOptions::new()
+            .with_enhanced_tables(false)
+            .with_include_base_dir(
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/fixtures/includes"
+                )
+                .into(),
+            )
+    }
+
+    #[test]
+    fn test_process_markdown_with_include() {
+        let markdown =
+            "{{include:guide.md}}\n\n```text\n{{include:snippet.md}}\n```\n";
+        let result = process_markdown(markdown, &include_options())
+            .expect("Failed to process markdown");
+        assert!(result.contains("<h1>Guide</h1>"));
+        assert!(
+            result.contains("<p>Shared <strong>snippet</strong>.</p>")
+        );
+        assert!(
+            result.contains("{{include:snippet.md}}"),
+            "Directives in code blocks should be left as written: {}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_process_markdown_with_missing_include() {
+        let result = process_markdown(
+            "{{include:missing.md}}",
+            &include_options(),
+        );
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::IoError(ref err)) if err.to_string().contains("missing.md")
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_with_circular_include() {
+        let result =
+            process_markdown("{{include:self.md}}", &include_options());
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::IncludeError(ref msg)) if msg.contains("Circular include")
+        ));
+
+        let result = process_markdown(
+            "{{include:guide.md}}",
+            &include_options().with_max_include_depth(1),
+        );
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::IncludeError(ref msg)) if msg.contains("maximum depth")
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_rejects_absolute_include() {
+        let guide = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/includes/guide.md"
+        );
+        let result = process_markdown(
+            format!("{{{{include:{}}}}}", guide),
+            &include_options(),
+        );
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::IncludeError(ref msg)) if msg.contains("must be relative")
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_rejects_parent_include() {
+        let result = process_markdown(
+            "{{include:../includes/guide.md}}",
+            &include_options(),
+        );
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::IncludeError(ref msg)) if msg.contains("'..'")
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_markdown_rejects_symlink_escaping_include() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.md"), "Secret")
+            .unwrap();
+        let base_dir = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret.md"),
+            base_dir.path().join("link.md"),
+        )
+        .unwrap();
+
+        let result = process_markdown(
+            "{{include:link.md}}",
+            &include_options()
+                .with_include_base_dir(base_dir.path().to_path_buf()),
+        );
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::IncludeError(ref msg)) if msg.contains("resolves outside")
+        ));
+    }
+
+    #[test]
+    fn test_process_markdown_include_exceeding_input_limit() {
+        let result = process_markdown(
+            "{{include:guide.md}}",
+            &include_options().with_max_input_bytes(24),
+        );
+        assert!(matches!(
+            result,
+            Err(mdx_gen::MarkdownError::ParseError(ref msg)) if msg.contains("exceeding the limit")
+        ));
+    }
+
+    #[cfg(feature = "syntax_highlighting")]
+    #[test]
+    fn test_highlight_error_position_with_include() {
+        let base_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            base_dir.path().join("code.md"),
+            "```nolang\nx\n```\n",
+        )
+        .unwrap();
+        let markdown = "{{include:code.md}}\n\nIntro\n\nMore\n\n```nolang\ny\n```\n";
+        let options = include_options()
+            .with_include_base_dir(base_dir.path().to_path_buf())
+            .with_highlight_fallback(mdx_gen::HighlightFallback::Error);
+
+        let error = process_markdown(markdown, &options).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("(at byte 0 of the rendered HTML)"));
+    }
 }